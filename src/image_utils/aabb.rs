@@ -2,6 +2,7 @@ use crate::image_utils::ray::Ray;
 use crate::utils::vec3::Vec3;
 use std::mem::swap;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug)]
 pub struct AABB {
     pub(crate) min: Vec3,
@@ -13,7 +14,23 @@ impl AABB {
         AABB { min, max }
     }
 
+    pub fn from_points(points: &[Vec3]) -> AABB {
+        let mut min = Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for point in points {
+            for c in 0..3 {
+                min[c] = min[c].min(point[c]);
+                max[c] = max[c].max(point[c]);
+            }
+        }
+        AABB::new(min, max)
+    }
+
     pub(crate) fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        // Narrowed by each slab in turn, so the ray must be inside all three
+        // at once.
+        let mut t_min = t_min;
+        let mut t_max = t_max;
         for i in 0..3 {
             let inv_d = 1.0 / ray.direction[i];
             let mut t0 = (self.min[i] - ray.origin[i]) * inv_d;
@@ -21,8 +38,8 @@ impl AABB {
             if inv_d < 0.0 {
                 swap(&mut t0, &mut t1);
             }
-            let t_min = if t0 > t_min { t0 } else { t_min };
-            let t_max = if t1 < t_max { t1 } else { t_max };
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
             if t_max <= t_min {
                 return false;
             }
@@ -30,6 +47,19 @@ impl AABB {
         true
     }
 
+    pub(crate) fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub(crate) fn diagonal(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub(crate) fn surface_area(&self) -> f32 {
+        let d = self.diagonal();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
     pub(crate) fn surrounding_box(&self, second_box: AABB) -> AABB {
         let small = Vec3::new(
             self.min.x.min(second_box.min.x),
@@ -44,3 +74,19 @@ impl AABB {
         AABB::new(small, big)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_must_be_inside_all_slabs_at_once() {
+        let aabb = AABB::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+        // Inside the x slab for t in [1, 2] and the y slab for t in [3, 4],
+        // never both.
+        let ray = Ray::new(Vec3::new(-1.0, -3.0, 0.5), Vec3::new(1.0, 1.0, 0.0), 0.0);
+        assert!(!aabb.hit(&ray, 0.0, f32::INFINITY));
+        let ray = Ray::new(Vec3::new(-1.0, -0.5, 0.5), Vec3::new(1.0, 1.0, 0.0), 0.0);
+        assert!(aabb.hit(&ray, 0.0, f32::INFINITY));
+    }
+}
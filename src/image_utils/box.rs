@@ -2,6 +2,7 @@ use crate::image_utils::aabb::AABB;
 use crate::image_utils::hittable::{HitRecord, Hittable, HittableList};
 use crate::image_utils::ray::Ray;
 use crate::image_utils::rectangle::{XYRect, XZRect, YZRect};
+use crate::lights::light::LightBounds;
 use crate::materials::materials::Material;
 use crate::utils::vec3::Vec3;
 use std::sync::Arc;
@@ -76,10 +77,14 @@ impl Hittable for Box {
         self.sides.hit(ray, t_min, t_max)
     }
 
-    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
         Some(AABB::new(self.box_min, self.box_max))
     }
 
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        self.sides.light_bounds(time0, time1)
+    }
+
     fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
         Arc::new(Box {
             box_min: self.box_min,
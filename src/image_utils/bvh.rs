@@ -1,6 +1,7 @@
 use crate::image_utils::aabb::AABB;
 use crate::image_utils::hittable::{HitRecord, Hittable, HittableList};
 use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
 use std::sync::Arc;
 
 pub struct BVHNode {
@@ -89,6 +90,17 @@ impl Hittable for BVHNode {
         Some(self.box_)
     }
 
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        match (
+            self.left.light_bounds(time0, time1),
+            self.right.light_bounds(time0, time1),
+        ) {
+            (Some(left), Some(right)) => Some(left.union(&right)),
+            (left, None) => left,
+            (None, right) => right,
+        }
+    }
+
     fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
         Arc::new(BVHNode::new(
             self.left.clone(),
@@ -16,6 +16,7 @@ pub struct Camera {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Vec3,
         look_at: Vec3,
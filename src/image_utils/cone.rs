@@ -0,0 +1,182 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::disk::disk_extent;
+use crate::image_utils::hittable::{HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
+use crate::materials::materials::Material;
+use crate::utils::onb::ONB;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Cone closed by a disk at its base.
+pub struct Cone {
+    pub base: Vec3,
+    pub apex: Vec3,
+    pub radius: f32,
+    pub material: Arc<dyn Material + Send + Sync>,
+    height: f32,
+    frame: ONB,
+}
+
+impl Cone {
+    pub fn new(
+        base: Vec3,
+        apex: Vec3,
+        radius: f32,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Cone {
+        let axis = apex - base;
+        Cone {
+            base,
+            apex,
+            radius,
+            material,
+            height: axis.length(),
+            frame: ONB::build_from_w(axis),
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let origin = self.frame.world_to_local(ray.origin - self.base);
+        let direction = self.frame.world_to_local(ray.direction);
+        let mut closest: Option<(f32, Vec3, f32, f32)> = None;
+        let mut closest_t = t_max;
+
+        // x^2 + y^2 = k^2 (h - z)^2 with k = radius / height.
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h = self.height;
+        let a =
+            direction.x * direction.x + direction.y * direction.y - k2 * direction.z * direction.z;
+        let half_b =
+            origin.x * direction.x + origin.y * direction.y + k2 * (h - origin.z) * direction.z;
+        let c = origin.x * origin.x + origin.y * origin.y - k2 * (h - origin.z) * (h - origin.z);
+        let roots = if a.abs() < 1e-12 {
+            if half_b.abs() < 1e-12 {
+                vec![]
+            } else {
+                vec![-c / (2.0 * half_b)]
+            }
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                vec![]
+            } else {
+                let discriminant_sqrt = discriminant.sqrt();
+                vec![
+                    (-half_b - discriminant_sqrt) / a,
+                    (-half_b + discriminant_sqrt) / a,
+                ]
+            }
+        };
+        for root in roots {
+            if root < t_min || root > closest_t {
+                continue;
+            }
+            let p = origin + root * direction;
+            if p.z < 0.0 || p.z > h {
+                continue;
+            }
+            let normal = Vec3::new(p.x, p.y, k2 * (h - p.z)).unit_vector();
+            let u = (p.y.atan2(p.x) + PI) / (2.0 * PI);
+            closest = Some((root, normal, u, p.z / h));
+            closest_t = root;
+        }
+
+        if direction.z.abs() > 1e-12 {
+            let root = -origin.z / direction.z;
+            if root >= t_min && root <= closest_t {
+                let p = origin + root * direction;
+                let distance = (p.x * p.x + p.y * p.y).sqrt();
+                if distance <= self.radius {
+                    let u = (p.y.atan2(p.x) + PI) / (2.0 * PI);
+                    closest = Some((root, Vec3::new(0.0, 0.0, -1.0), u, distance / self.radius));
+                }
+            }
+        }
+
+        let (t, local_normal, u, v) = closest?;
+        let mut record = HitRecord::new(ray.at(t), self.material.clone(), t);
+        record.u = u;
+        record.v = v;
+        record.set_face_normal(ray, self.frame.local_to_world(local_normal));
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        let extent = disk_extent(self.frame.w, self.radius);
+        let base = AABB::new(self.base - extent, self.base + extent);
+        Some(base.surrounding_box(AABB::new(self.apex, self.apex)))
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        let emission = self.material.emission_bounds()?;
+        let slant = (self.radius * self.radius + self.height * self.height).sqrt();
+        Some(LightBounds::from_emission(
+            self.bounding_box(time0, time1)?,
+            Vec3::new(0.0, 0.0, 1.0),
+            -1.0,
+            PI * self.radius * (slant + self.radius),
+            emission,
+        ))
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Cone::new(
+            self.base,
+            self.apex,
+            self.radius,
+            self.material.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::Lambertian;
+
+    fn cone() -> Cone {
+        Cone::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hits_side() {
+        // Half way up the radius is 0.5 and the slope tilts the normal up.
+        let ray = Ray::new(Vec3::new(-3.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let record = cone().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 2.5).abs() < 1e-4);
+        let expected = Vec3::new(-2.0, 1.0, 0.0).unit_vector();
+        assert!((record.normal - expected).length() < 1e-4);
+    }
+
+    #[test]
+    fn hits_base() {
+        let ray = Ray::new(Vec3::new(0.2, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let record = cone().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 3.0).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn misses_above_apex() {
+        // The mirrored nappe above the apex is not part of the cone.
+        let ray = Ray::new(Vec3::new(-3.0, 3.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(cone().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn misses_beside_tip() {
+        // Near the apex the radius, 0.05, is less than the ray's offset.
+        let ray = Ray::new(Vec3::new(-3.0, 1.9, 0.1), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(cone().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+}
@@ -29,24 +29,16 @@ impl ConstantMedium {
         color: Vec3,
         density: f32,
     ) -> ConstantMedium {
-        ConstantMedium {
-            boundary,
-            phase_function: Arc::new(Isotropic::new(color)),
-            neg_inv_density: -1.0 / density,
-        }
+        ConstantMedium::new(boundary, Arc::new(Isotropic::new(color)), density)
     }
 }
 
 impl Hittable for ConstantMedium {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        if let Some(mut record) = self
-            .boundary
-            .hit(ray, -std::f32::INFINITY, std::f32::INFINITY)
-        {
-            if let Some(mut record2) = self
-                .boundary
-                .hit(ray, record.t + 0.0001, std::f32::INFINITY)
-            {
+        if let Some(mut record) = self.boundary.hit(ray, -f32::INFINITY, f32::INFINITY) {
+            // Relative offset, a fixed one vanishes against large t.
+            let t_exit_min = record.t + (record.t.abs() * 1e-5).max(0.0001);
+            if let Some(mut record2) = self.boundary.hit(ray, t_exit_min, f32::INFINITY) {
                 if record.t < t_min {
                     record.t = t_min;
                 }
@@ -67,7 +59,9 @@ impl Hittable for ConstantMedium {
                 }
                 let t = record.t + hit_distance / ray_length;
                 let mut record = HitRecord::new(ray.at(t), self.phase_function.clone(), t);
-                record.set_face_normal(ray, record.normal);
+                // Arbitrary, but a valid frame for the phase function.
+                record.normal = Vec3::new(1.0, 0.0, 0.0);
+                record.front_face = true;
                 Some(record)
             } else {
                 None
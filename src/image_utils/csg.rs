@@ -0,0 +1,247 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{HitInterval, HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// Boolean combination of two closed objects. Surfaces of the subtracted
+// object keep the material they were hit with.
+pub struct Csg {
+    pub left: Arc<dyn Hittable + Send + Sync>,
+    pub right: Arc<dyn Hittable + Send + Sync>,
+    pub operation: CsgOperation,
+}
+
+impl Csg {
+    pub fn new(
+        left: Arc<dyn Hittable + Send + Sync>,
+        right: Arc<dyn Hittable + Send + Sync>,
+        operation: CsgOperation,
+    ) -> Csg {
+        Csg {
+            left,
+            right,
+            operation,
+        }
+    }
+
+    pub fn union(
+        left: Arc<dyn Hittable + Send + Sync>,
+        right: Arc<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg::new(left, right, CsgOperation::Union)
+    }
+
+    pub fn intersection(
+        left: Arc<dyn Hittable + Send + Sync>,
+        right: Arc<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg::new(left, right, CsgOperation::Intersection)
+    }
+
+    pub fn difference(
+        left: Arc<dyn Hittable + Send + Sync>,
+        right: Arc<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg::new(left, right, CsgOperation::Difference)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        for interval in self.intervals(ray) {
+            if interval.enter.t > t_max {
+                break;
+            }
+            if interval.enter.t >= t_min {
+                return Some(interval.enter);
+            }
+            if interval.exit.t >= t_min && interval.exit.t <= t_max {
+                return Some(interval.exit);
+            }
+        }
+        None
+    }
+
+    // Sweeps the boundaries of both operands along the ray and keeps those
+    // where the result switches between outside and inside. Since normals
+    // always face the ray, a boundary taken from the subtracted object only
+    // needs its `front_face` flag updated to become a correctly oriented
+    // surface of the result.
+    fn intervals(&self, ray: &Ray) -> Vec<HitInterval> {
+        let mut events: Vec<(HitRecord, bool, bool)> = Vec::new();
+        for (intervals, is_left) in [
+            (self.left.intervals(ray), true),
+            (self.right.intervals(ray), false),
+        ] {
+            for interval in intervals {
+                events.push((interval.enter, is_left, true));
+                events.push((interval.exit, is_left, false));
+            }
+        }
+        events.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+        let mut result = Vec::new();
+        let mut in_left = false;
+        let mut in_right = false;
+        let mut enter: Option<HitRecord> = None;
+        for (mut record, is_left, entering) in events {
+            let was_inside = self.operation.inside(in_left, in_right);
+            if is_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+            let inside = self.operation.inside(in_left, in_right);
+            if !was_inside && inside {
+                record.front_face = true;
+                enter = Some(record);
+            } else if was_inside && !inside {
+                record.front_face = false;
+                if let Some(enter) = enter.take() {
+                    result.push(HitInterval::new(enter, record));
+                }
+            }
+        }
+        result
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+        let left = self.left.bounding_box(time0, time1);
+        let right = self.right.bounding_box(time0, time1);
+        match self.operation {
+            CsgOperation::Union => Some(left?.surrounding_box(right?)),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(left), Some(right)) => Some(AABB::new(
+                    Vec3::new(
+                        left.min.x.max(right.min.x),
+                        left.min.y.max(right.min.y),
+                        left.min.z.max(right.min.z),
+                    ),
+                    Vec3::new(
+                        left.max.x.min(right.max.x),
+                        left.max.y.min(right.max.y),
+                        left.max.z.min(right.max.z),
+                    ),
+                )),
+                (left, None) => left,
+                (None, right) => right,
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Csg::new(
+            self.left.clone_dyn(),
+            self.right.clone_dyn(),
+            self.operation,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_utils::sphere::Sphere;
+    use crate::materials::materials::Lambertian;
+
+    // Two unit spheres overlapping between x = -0.5 and x = 0.5.
+    fn spheres() -> (
+        Arc<dyn Hittable + Send + Sync>,
+        Arc<dyn Hittable + Send + Sync>,
+    ) {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        (
+            Arc::new(Sphere::new(
+                Vec3::new(-0.5, 0.0, 0.0),
+                1.0,
+                material.clone(),
+            )),
+            Arc::new(Sphere::new(Vec3::new(0.5, 0.0, 0.0), 1.0, material)),
+        )
+    }
+
+    fn along_x() -> Ray {
+        Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0)
+    }
+
+    fn spans(csg: &Csg, ray: &Ray) -> Vec<(f32, f32)> {
+        csg.intervals(ray)
+            .iter()
+            .map(|interval| (interval.enter.t, interval.exit.t))
+            .collect()
+    }
+
+    fn assert_spans(actual: Vec<(f32, f32)>, expected: &[(f32, f32)]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a.0 - e.0).abs() < 1e-3 && (a.1 - e.1).abs() < 1e-3,
+                "{:?}",
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn union_spans_both_operands() {
+        let (left, right) = spheres();
+        let csg = Csg::union(left, right);
+        assert_spans(spans(&csg, &along_x()), &[(3.5, 6.5)]);
+        let record = csg.hit(&along_x(), 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 3.5).abs() < 1e-3);
+        assert!(record.front_face);
+    }
+
+    #[test]
+    fn intersection_keeps_the_overlap() {
+        let (left, right) = spheres();
+        let csg = Csg::intersection(left, right);
+        assert_spans(spans(&csg, &along_x()), &[(4.5, 5.5)]);
+        // Crosses both spheres but passes above the lens they share.
+        let above = Ray::new(Vec3::new(-5.0, 0.95, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(csg.hit(&above, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn difference_ends_at_the_subtracted_surface() {
+        let (left, right) = spheres();
+        let csg = Csg::difference(left, right);
+        let intervals = csg.intervals(&along_x());
+        assert_spans(spans(&csg, &along_x()), &[(3.5, 4.5)]);
+        let exit = &intervals[0].exit;
+        assert!(!exit.front_face);
+        assert!((exit.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn difference_flips_the_subtracted_surface() {
+        let (left, right) = spheres();
+        let csg = Csg::difference(left, right);
+        // From inside the carved-out hole, the subtracted sphere is hit from
+        // within but it is the outside of the result, facing +x.
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        let record = csg.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 0.5).abs() < 1e-3);
+        assert!(record.front_face);
+        assert!((record.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-4);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{HitRecord, Hittable, HittableList};
+use crate::image_utils::ray::Ray;
+use crate::materials::materials::Material;
+use crate::utils::onb::ONB;
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CurveType {
+    // Flat strip always facing the incoming ray.
+    Ribbon,
+    // Same strip, shaded with the normals of a tube of the curve's width.
+    Round,
+}
+
+// Cubic Bézier curve whose width varies linearly from `width0` to `width1`.
+// Intersection follows pbrt: the curve is projected in a frame where the ray
+// is the z axis and recursively split until each piece is nearly straight,
+// pieces whose widened control hull misses the ray being skipped.
+pub struct Curve {
+    pub control_points: [Vec3; 4],
+    pub width0: f32,
+    pub width1: f32,
+    pub curve_type: CurveType,
+    pub material: Arc<dyn Material + Send + Sync>,
+}
+
+struct CurveHit {
+    z: f32,
+    u: f32,
+    v: f32,
+    // From the curve center to the ray, in ray space.
+    offset: Vec3,
+    width: f32,
+    tangent: Vec3,
+}
+
+impl Curve {
+    pub fn new(
+        control_points: [Vec3; 4],
+        width0: f32,
+        width1: f32,
+        curve_type: CurveType,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Curve {
+        Curve {
+            control_points,
+            width0,
+            width1,
+            curve_type,
+            material,
+        }
+    }
+
+    // Uniform cubic B-spline segment, converted to its Bézier form.
+    pub fn from_bspline(
+        points: [Vec3; 4],
+        width0: f32,
+        width1: f32,
+        curve_type: CurveType,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Curve {
+        let [p0, p1, p2, p3] = points;
+        let control_points = [
+            (p0 + 4.0 * p1 + p2) / 6.0,
+            (2.0 * p1 + p2) / 3.0,
+            (p1 + 2.0 * p2) / 3.0,
+            (p1 + 4.0 * p2 + p3) / 6.0,
+        ];
+        Curve::new(control_points, width0, width1, curve_type, material)
+    }
+
+    // One curve per span of a B-spline through `points` (a hair strand or a
+    // grass blade), the width going from `root_width` to `tip_width`. The
+    // end points are repeated so that the strand starts and ends on them.
+    pub fn bspline_strand(
+        points: &[Vec3],
+        root_width: f32,
+        tip_width: f32,
+        curve_type: CurveType,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> HittableList {
+        let mut padded = vec![points[0], points[0]];
+        padded.extend_from_slice(points);
+        padded.push(points[points.len() - 1]);
+        padded.push(points[points.len() - 1]);
+        let segments = padded.len() - 3;
+        let width_at =
+            |i: usize| root_width + (tip_width - root_width) * i as f32 / segments as f32;
+        let mut list = HittableList::new();
+        for i in 0..segments {
+            list.add(Arc::new(Curve::from_bspline(
+                [padded[i], padded[i + 1], padded[i + 2], padded[i + 3]],
+                width_at(i),
+                width_at(i + 1),
+                curve_type,
+                material.clone(),
+            )));
+        }
+        list
+    }
+
+    fn recursive_intersect(
+        &self,
+        cp: [Vec3; 4],
+        u0: f32,
+        u1: f32,
+        depth: u32,
+        z_max: f32,
+    ) -> Option<CurveHit> {
+        if depth > 0 {
+            let split = subdivide_bezier(cp);
+            let u_mid = 0.5 * (u0 + u1);
+            let mut closest: Option<CurveHit> = None;
+            let mut z_max = z_max;
+            for (segment, (seg_u0, seg_u1)) in [
+                ([split[0], split[1], split[2], split[3]], (u0, u_mid)),
+                ([split[3], split[4], split[5], split[6]], (u_mid, u1)),
+            ] {
+                let max_width = self.width_at(seg_u0).max(self.width_at(seg_u1));
+                if !hull_overlaps_ray(&segment, 0.5 * max_width, z_max) {
+                    continue;
+                }
+                if let Some(hit) =
+                    self.recursive_intersect(segment, seg_u0, seg_u1, depth - 1, z_max)
+                {
+                    z_max = hit.z;
+                    closest = Some(hit);
+                }
+            }
+            return closest;
+        }
+
+        // Reject hits beyond the start or end of this piece.
+        let edge = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        if edge < 0.0 {
+            return None;
+        }
+        let edge = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge < 0.0 {
+            return None;
+        }
+
+        let segment = Vec3::new(cp[3].x - cp[0].x, cp[3].y - cp[0].y, 0.0);
+        let denominator = segment.length_squared();
+        if denominator == 0.0 {
+            return None;
+        }
+        let w = ((-cp[0].x * segment.x - cp[0].y * segment.y) / denominator).clamp(0.0, 1.0);
+        let u = (u0 + w * (u1 - u0)).clamp(u0, u1);
+        let width = self.width_at(u);
+        let (pc, derivative) = eval_bezier(&cp, w);
+        let distance_squared = pc.x * pc.x + pc.y * pc.y;
+        if distance_squared > width * width * 0.25 || pc.z < 0.0 || pc.z > z_max {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let edge = derivative.x * -pc.y + pc.x * derivative.y;
+        let v = if edge > 0.0 {
+            0.5 + distance / width
+        } else {
+            0.5 - distance / width
+        };
+        Some(CurveHit {
+            z: pc.z,
+            u,
+            v,
+            offset: Vec3::new(-pc.x, -pc.y, 0.0),
+            width,
+            tangent: derivative,
+        })
+    }
+
+    fn width_at(&self, u: f32) -> f32 {
+        self.width0 + u * (self.width1 - self.width0)
+    }
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let length = ray.direction.length();
+        let frame = ONB::build_from_w(ray.direction);
+        let cp = self
+            .control_points
+            .map(|p| frame.world_to_local(p - ray.origin));
+        let z_max = t_max * length;
+        let max_width = self.width0.max(self.width1);
+        if !hull_overlaps_ray(&cp, 0.5 * max_width, z_max) {
+            return None;
+        }
+
+        // Enough splits for the pieces to be flat to within 5% of the width.
+        let mut l0: f32 = 0.0;
+        for i in 0..2 {
+            let second_difference = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+            l0 = l0
+                .max(second_difference.x.abs())
+                .max(second_difference.y.abs())
+                .max(second_difference.z.abs());
+        }
+        let epsilon = max_width * 0.05;
+        let depth = if l0 > 0.0 {
+            ((std::f32::consts::SQRT_2 * 6.0 * l0 / (8.0 * epsilon)).log2() * 0.5)
+                .round()
+                .clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+
+        let hit = self.recursive_intersect(cp, 0.0, 1.0, depth, z_max)?;
+        let t = hit.z / length;
+        if t < t_min {
+            return None;
+        }
+        let view = -ray.direction / length;
+        let tangent = frame.local_to_world(hit.tangent).unit_vector();
+        let outward_normal = match self.curve_type {
+            CurveType::Ribbon => view,
+            CurveType::Round => {
+                let h = (2.0 * hit.offset.length() / hit.width).min(1.0);
+                let side = if hit.offset.near_zero() {
+                    Vec3::new(0.0, 0.0, 0.0)
+                } else {
+                    frame.local_to_world(hit.offset).unit_vector()
+                };
+                let normal = h * side + (1.0 - h * h).sqrt() * view;
+                let normal = normal - normal.dot(tangent) * tangent;
+                if normal.near_zero() {
+                    view
+                } else {
+                    normal.unit_vector()
+                }
+            }
+        };
+
+        let mut record = HitRecord::new(ray.at(t), self.material.clone(), t);
+        record.u = hit.u;
+        record.v = hit.v;
+        record.tangent = tangent;
+        record.set_face_normal(ray, outward_normal);
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        let bbox = AABB::from_points(&self.control_points);
+        let half_width = 0.5 * self.width0.max(self.width1);
+        let pad = Vec3::new(half_width, half_width, half_width);
+        Some(AABB::new(bbox.min - pad, bbox.max + pad))
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Curve::new(
+            self.control_points,
+            self.width0,
+            self.width1,
+            self.curve_type,
+            self.material.clone(),
+        ))
+    }
+}
+
+// Whether the control hull of a ray space curve, widened by `half_width`,
+// can touch the z axis between 0 and `z_max`.
+fn hull_overlaps_ray(cp: &[Vec3; 4], half_width: f32, z_max: f32) -> bool {
+    let bbox = AABB::from_points(cp);
+    bbox.min.x - half_width <= 0.0
+        && bbox.max.x + half_width >= 0.0
+        && bbox.min.y - half_width <= 0.0
+        && bbox.max.y + half_width >= 0.0
+        && bbox.max.z + half_width >= 0.0
+        && bbox.min.z - half_width <= z_max
+}
+
+fn lerp(t: f32, a: Vec3, b: Vec3) -> Vec3 {
+    (1.0 - t) * a + t * b
+}
+
+// Point at `u` and derivative with respect to `u`.
+fn eval_bezier(cp: &[Vec3; 4], u: f32) -> (Vec3, Vec3) {
+    let cp1 = [
+        lerp(u, cp[0], cp[1]),
+        lerp(u, cp[1], cp[2]),
+        lerp(u, cp[2], cp[3]),
+    ];
+    let cp2 = [lerp(u, cp1[0], cp1[1]), lerp(u, cp1[1], cp1[2])];
+    let derivative = if (cp2[1] - cp2[0]).length_squared() > 0.0 {
+        3.0 * (cp2[1] - cp2[0])
+    } else {
+        cp[3] - cp[0]
+    };
+    (lerp(u, cp2[0], cp2[1]), derivative)
+}
+
+// Splits at u = 0.5; the halves are [0..4] and [3..7].
+fn subdivide_bezier(cp: [Vec3; 4]) -> [Vec3; 7] {
+    [
+        cp[0],
+        (cp[0] + cp[1]) / 2.0,
+        (cp[0] + 2.0 * cp[1] + cp[2]) / 4.0,
+        (cp[0] + 3.0 * cp[1] + 3.0 * cp[2] + cp[3]) / 8.0,
+        (cp[1] + 2.0 * cp[2] + cp[3]) / 4.0,
+        (cp[2] + cp[3]) / 2.0,
+        cp[3],
+    ]
+}
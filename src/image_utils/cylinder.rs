@@ -0,0 +1,175 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::disk::disk_extent;
+use crate::image_utils::hittable::{HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
+use crate::materials::materials::Material;
+use crate::utils::onb::ONB;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Capped cylinder between the centers of its two caps.
+pub struct Cylinder {
+    pub base: Vec3,
+    pub top: Vec3,
+    pub radius: f32,
+    pub material: Arc<dyn Material + Send + Sync>,
+    height: f32,
+    frame: ONB,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Vec3,
+        top: Vec3,
+        radius: f32,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Cylinder {
+        let axis = top - base;
+        Cylinder {
+            base,
+            top,
+            radius,
+            material,
+            height: axis.length(),
+            frame: ONB::build_from_w(axis),
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let origin = self.frame.world_to_local(ray.origin - self.base);
+        let direction = self.frame.world_to_local(ray.direction);
+        let mut closest: Option<(f32, Vec3, f32, f32)> = None;
+        let mut closest_t = t_max;
+
+        let a = direction.x * direction.x + direction.y * direction.y;
+        if a > 1e-12 {
+            let half_b = origin.x * direction.x + origin.y * direction.y;
+            let c = origin.x * origin.x + origin.y * origin.y - self.radius * self.radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant >= 0.0 {
+                let discriminant_sqrt = discriminant.sqrt();
+                for root in [
+                    (-half_b - discriminant_sqrt) / a,
+                    (-half_b + discriminant_sqrt) / a,
+                ] {
+                    if root < t_min || root > closest_t {
+                        continue;
+                    }
+                    let p = origin + root * direction;
+                    if p.z < 0.0 || p.z > self.height {
+                        continue;
+                    }
+                    let normal = Vec3::new(p.x / self.radius, p.y / self.radius, 0.0);
+                    let u = (p.y.atan2(p.x) + PI) / (2.0 * PI);
+                    closest = Some((root, normal, u, p.z / self.height));
+                    closest_t = root;
+                }
+            }
+        }
+
+        if direction.z.abs() > 1e-12 {
+            for (k, normal_z) in [(0.0, -1.0), (self.height, 1.0)] {
+                let root = (k - origin.z) / direction.z;
+                if root < t_min || root > closest_t {
+                    continue;
+                }
+                let p = origin + root * direction;
+                let distance = (p.x * p.x + p.y * p.y).sqrt();
+                if distance > self.radius {
+                    continue;
+                }
+                let u = (p.y.atan2(p.x) + PI) / (2.0 * PI);
+                closest = Some((
+                    root,
+                    Vec3::new(0.0, 0.0, normal_z),
+                    u,
+                    distance / self.radius,
+                ));
+                closest_t = root;
+            }
+        }
+
+        let (t, local_normal, u, v) = closest?;
+        let mut record = HitRecord::new(ray.at(t), self.material.clone(), t);
+        record.u = u;
+        record.v = v;
+        record.set_face_normal(ray, self.frame.local_to_world(local_normal));
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        let extent = disk_extent(self.frame.w, self.radius);
+        let cap0 = AABB::new(self.base - extent, self.base + extent);
+        let cap1 = AABB::new(self.top - extent, self.top + extent);
+        Some(cap0.surrounding_box(cap1))
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        let emission = self.material.emission_bounds()?;
+        Some(LightBounds::from_emission(
+            self.bounding_box(time0, time1)?,
+            Vec3::new(0.0, 0.0, 1.0),
+            -1.0,
+            2.0 * PI * self.radius * (self.height + self.radius),
+            emission,
+        ))
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Cylinder::new(
+            self.base,
+            self.top,
+            self.radius,
+            self.material.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::Lambertian;
+
+    fn cylinder() -> Cylinder {
+        Cylinder::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hits_side() {
+        let ray = Ray::new(Vec3::new(-3.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let record = cylinder().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 2.0).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn hits_top_cap() {
+        let ray = Ray::new(Vec3::new(0.2, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let record = cylinder().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 3.0).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn hits_far_side_from_inside() {
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let record = cylinder().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 1.0).abs() < 1e-4);
+        assert!(!record.front_face);
+    }
+
+    #[test]
+    fn misses_above_top() {
+        let ray = Ray::new(Vec3::new(-3.0, 3.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(cylinder().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
+use crate::materials::materials::Material;
+use crate::utils::onb::ONB;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+pub struct Disk {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub radius: f32,
+    pub material: Arc<dyn Material + Send + Sync>,
+    frame: ONB,
+}
+
+impl Disk {
+    pub fn new(
+        center: Vec3,
+        normal: Vec3,
+        radius: f32,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Disk {
+        let normal = normal.unit_vector();
+        Disk {
+            center,
+            normal,
+            radius,
+            material,
+            frame: ONB::build_from_w(normal),
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.center - ray.origin).dot(self.normal) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let position = ray.at(t);
+        let local = self.frame.world_to_local(position - self.center);
+        let distance = (local.x * local.x + local.y * local.y).sqrt();
+        if distance > self.radius {
+            return None;
+        }
+        let mut record = HitRecord::new(position, self.material.clone(), t);
+        record.u = (local.y.atan2(local.x) + PI) / (2.0 * PI);
+        record.v = distance / self.radius;
+        record.set_face_normal(ray, self.normal);
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        let extent = disk_extent(self.normal, self.radius) + Vec3::new(0.0001, 0.0001, 0.0001);
+        Some(AABB::new(self.center - extent, self.center + extent))
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        let emission = self.material.emission_bounds()?;
+        Some(LightBounds::from_emission(
+            self.bounding_box(time0, time1)?,
+            self.normal,
+            1.0,
+            PI * self.radius * self.radius,
+            emission,
+        ))
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Disk::new(
+            self.center,
+            self.normal,
+            self.radius,
+            self.material.clone(),
+        ))
+    }
+}
+
+// Half extents of the box around a circle of `radius` whose plane has the
+// unit `normal`.
+pub fn disk_extent(normal: Vec3, radius: f32) -> Vec3 {
+    Vec3::new(
+        radius * (1.0 - normal.x * normal.x).max(0.0).sqrt(),
+        radius * (1.0 - normal.y * normal.y).max(0.0).sqrt(),
+        radius * (1.0 - normal.z * normal.z).max(0.0).sqrt(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::Lambertian;
+
+    fn disk() -> Disk {
+        Disk::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hits_from_above() {
+        let ray = Ray::new(Vec3::new(0.5, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let record = disk().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 2.0).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-4);
+        assert!(record.front_face);
+    }
+
+    #[test]
+    fn flips_normal_from_below() {
+        let ray = Ray::new(Vec3::new(0.0, -1.0, 0.5), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let record = disk().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.normal - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-4);
+        assert!(!record.front_face);
+    }
+
+    #[test]
+    fn misses_outside_radius() {
+        let ray = Ray::new(Vec3::new(1.5, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(disk().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn misses_parallel_ray() {
+        let ray = Ray::new(Vec3::new(-2.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(disk().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+}
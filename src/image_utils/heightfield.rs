@@ -0,0 +1,421 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::materials::materials::Material;
+use crate::utils::perlin::Perlin;
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+// Terrain over a regular grid of height samples in [0, 1], spanning `size`
+// from `origin` (`size.y` being the height of a sample of 1). Rays descend a
+// min-max pyramid of the grid cells and only test the two triangles of the
+// cells whose height range they cross.
+pub struct Heightfield {
+    pub origin: Vec3,
+    pub size: Vec3,
+    pub material: Arc<dyn Material + Send + Sync>,
+    width: usize,
+    depth: usize,
+    heights: Vec<f32>,
+    normals: Vec<Vec3>,
+    // levels[0] holds the height range of each cell, every next level the
+    // range of 2x2 blocks of the previous one.
+    levels: Vec<MinMaxLevel>,
+}
+
+#[derive(Clone)]
+struct MinMaxLevel {
+    width: usize,
+    depth: usize,
+    ranges: Vec<(f32, f32)>,
+}
+
+impl Heightfield {
+    pub fn new(
+        heights: Vec<f32>,
+        width: usize,
+        depth: usize,
+        origin: Vec3,
+        size: Vec3,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Heightfield {
+        assert!(width >= 2 && depth >= 2, "a heightfield needs 2x2 samples");
+        assert_eq!(heights.len(), width * depth);
+        let mut heightfield = Heightfield {
+            origin,
+            size,
+            material,
+            width,
+            depth,
+            heights,
+            normals: Vec::new(),
+            levels: Vec::new(),
+        };
+        heightfield.normals = heightfield.compute_normals();
+        heightfield.levels = heightfield.build_levels();
+        heightfield
+    }
+
+    // Uses the luminance of the image, its top row being the far (max z)
+    // edge of the terrain.
+    pub fn from_image(
+        filename: &str,
+        origin: Vec3,
+        size: Vec3,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Result<Heightfield, String> {
+        let image = image::open(filename)
+            .map_err(|e| format!("cannot read height map {filename}: {e}"))?
+            .into_luma16();
+        let (width, depth) = (image.width() as usize, image.height() as usize);
+        let mut heights = Vec::with_capacity(width * depth);
+        for j in 0..depth {
+            for i in 0..width {
+                let pixel = image.get_pixel(i as u32, (depth - 1 - j) as u32);
+                heights.push(pixel[0] as f32 / u16::MAX as f32);
+            }
+        }
+        Ok(Heightfield::new(
+            heights, width, depth, origin, size, material,
+        ))
+    }
+
+    pub fn from_noise(
+        noise: &Perlin,
+        resolution: usize,
+        frequency: f32,
+        octaves: i32,
+        origin: Vec3,
+        size: Vec3,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Heightfield {
+        let mut heights = Vec::with_capacity(resolution * resolution);
+        for j in 0..resolution {
+            for i in 0..resolution {
+                let p = Vec3::new(
+                    frequency * i as f32 / (resolution - 1) as f32,
+                    0.0,
+                    frequency * j as f32 / (resolution - 1) as f32,
+                );
+                heights.push(noise.turbulence(p, octaves));
+            }
+        }
+        let max = heights.iter().cloned().fold(f32::EPSILON, f32::max);
+        for height in heights.iter_mut() {
+            *height /= max;
+        }
+        Heightfield::new(heights, resolution, resolution, origin, size, material)
+    }
+
+    fn height(&self, i: usize, j: usize) -> f32 {
+        self.heights[j * self.width + i]
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        self.origin
+            + Vec3::new(
+                self.size.x * i as f32 / (self.width - 1) as f32,
+                self.size.y * self.height(i, j),
+                self.size.z * j as f32 / (self.depth - 1) as f32,
+            )
+    }
+
+    fn compute_normals(&self) -> Vec<Vec3> {
+        let mut normals = Vec::with_capacity(self.width * self.depth);
+        for j in 0..self.depth {
+            for i in 0..self.width {
+                let left = self.vertex(i.saturating_sub(1), j);
+                let right = self.vertex((i + 1).min(self.width - 1), j);
+                let back = self.vertex(i, j.saturating_sub(1));
+                let front = self.vertex(i, (j + 1).min(self.depth - 1));
+                normals.push((front - back).cross(right - left).unit_vector());
+            }
+        }
+        normals
+    }
+
+    fn build_levels(&self) -> Vec<MinMaxLevel> {
+        let (cells_x, cells_z) = (self.width - 1, self.depth - 1);
+        let mut ranges = Vec::with_capacity(cells_x * cells_z);
+        for j in 0..cells_z {
+            for i in 0..cells_x {
+                let corners = [
+                    self.height(i, j),
+                    self.height(i + 1, j),
+                    self.height(i, j + 1),
+                    self.height(i + 1, j + 1),
+                ];
+                let min = corners.iter().cloned().fold(f32::INFINITY, f32::min);
+                let max = corners.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                ranges.push((min, max));
+            }
+        }
+        let mut levels = vec![MinMaxLevel {
+            width: cells_x,
+            depth: cells_z,
+            ranges,
+        }];
+        while levels[levels.len() - 1].width > 1 || levels[levels.len() - 1].depth > 1 {
+            let previous = &levels[levels.len() - 1];
+            let width = previous.width.div_ceil(2);
+            let depth = previous.depth.div_ceil(2);
+            let mut ranges = Vec::with_capacity(width * depth);
+            for j in 0..depth {
+                for i in 0..width {
+                    let mut range = (f32::INFINITY, f32::NEG_INFINITY);
+                    for (ci, cj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let (x, z) = (2 * i + ci, 2 * j + cj);
+                        if x < previous.width && z < previous.depth {
+                            let child = previous.ranges[z * previous.width + x];
+                            range = (range.0.min(child.0), range.1.max(child.1));
+                        }
+                    }
+                    ranges.push(range);
+                }
+            }
+            levels.push(MinMaxLevel {
+                width,
+                depth,
+                ranges,
+            });
+        }
+        levels
+    }
+
+    fn node_box(&self, level: usize, i: usize, j: usize) -> AABB {
+        let span = 1 << level;
+        let (cells_x, cells_z) = (self.width - 1, self.depth - 1);
+        let x0 = (i * span).min(cells_x) as f32 / cells_x as f32;
+        let x1 = ((i + 1) * span).min(cells_x) as f32 / cells_x as f32;
+        let z0 = (j * span).min(cells_z) as f32 / cells_z as f32;
+        let z1 = ((j + 1) * span).min(cells_z) as f32 / cells_z as f32;
+        let (min, max) = self.levels[level].ranges[j * self.levels[level].width + i];
+        AABB::new(
+            self.origin
+                + Vec3::new(
+                    x0 * self.size.x,
+                    min * self.size.y - 0.0001,
+                    z0 * self.size.z,
+                ),
+            self.origin
+                + Vec3::new(
+                    x1 * self.size.x,
+                    max * self.size.y + 0.0001,
+                    z1 * self.size.z,
+                ),
+        )
+    }
+
+    // Tests the two triangles of cell (i, j), returning t and the
+    // interpolated normal.
+    fn hit_cell(
+        &self,
+        ray: &Ray,
+        i: usize,
+        j: usize,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(f32, Vec3)> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest: Option<(f32, Vec3)> = None;
+        let mut closest_t = t_max;
+        for triangle in [[0, 1, 2], [0, 2, 3]] {
+            let [a, b, c] = triangle.map(|k| corners[k]);
+            let (p0, p1, p2) = (
+                self.vertex(a.0, a.1),
+                self.vertex(b.0, b.1),
+                self.vertex(c.0, c.1),
+            );
+            let edge1 = p1 - p0;
+            let edge2 = p2 - p0;
+            let p = ray.direction.cross(edge2);
+            let determinant = edge1.dot(p);
+            if determinant.abs() < 1e-12 {
+                continue;
+            }
+            let inv_determinant = 1.0 / determinant;
+            let s = ray.origin - p0;
+            let u = s.dot(p) * inv_determinant;
+            if !(0.0..=1.0).contains(&u) {
+                continue;
+            }
+            let q = s.cross(edge1);
+            let v = ray.direction.dot(q) * inv_determinant;
+            if v < 0.0 || u + v > 1.0 {
+                continue;
+            }
+            let t = edge2.dot(q) * inv_determinant;
+            if t < t_min || t > closest_t {
+                continue;
+            }
+            let normal = (1.0 - u - v) * self.normals[a.1 * self.width + a.0]
+                + u * self.normals[b.1 * self.width + b.0]
+                + v * self.normals[c.1 * self.width + c.0];
+            closest = Some((t, normal.unit_vector()));
+            closest_t = t;
+        }
+        closest
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let top = self.levels.len() - 1;
+        let mut stack = vec![(top, 0, 0)];
+        let mut closest: Option<(f32, Vec3)> = None;
+        let mut closest_t = t_max;
+        // Children are pushed far to near so the nearest is visited first.
+        let order_x = if ray.direction.x >= 0.0 {
+            [1, 0]
+        } else {
+            [0, 1]
+        };
+        let order_z = if ray.direction.z >= 0.0 {
+            [1, 0]
+        } else {
+            [0, 1]
+        };
+        while let Some((level, i, j)) = stack.pop() {
+            if !self.node_box(level, i, j).hit(ray, t_min, closest_t) {
+                continue;
+            }
+            if level == 0 {
+                if let Some(hit) = self.hit_cell(ray, i, j, t_min, closest_t) {
+                    closest_t = hit.0;
+                    closest = Some(hit);
+                }
+                continue;
+            }
+            let below = &self.levels[level - 1];
+            for dz in order_z {
+                for dx in order_x {
+                    let (x, z) = (2 * i + dx, 2 * j + dz);
+                    if x < below.width && z < below.depth {
+                        stack.push((level - 1, x, z));
+                    }
+                }
+            }
+        }
+
+        let (t, normal) = closest?;
+        let position = ray.at(t);
+        let mut record = HitRecord::new(position, self.material.clone(), t);
+        record.u = ((position.x - self.origin.x) / self.size.x).clamp(0.0, 1.0);
+        record.v = ((position.z - self.origin.z) / self.size.z).clamp(0.0, 1.0);
+        record.set_face_normal(ray, normal);
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        Some(self.node_box(self.levels.len() - 1, 0, 0))
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Heightfield {
+            origin: self.origin,
+            size: self.size,
+            material: self.material.clone(),
+            width: self.width,
+            depth: self.depth,
+            heights: self.heights.clone(),
+            normals: self.normals.clone(),
+            levels: self.levels.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::Lambertian;
+
+    fn heightfield(
+        width: usize,
+        depth: usize,
+        height: impl Fn(usize, usize) -> f32,
+    ) -> Heightfield {
+        let mut heights = Vec::new();
+        for j in 0..depth {
+            for i in 0..width {
+                heights.push(height(i, j));
+            }
+        }
+        Heightfield::new(
+            heights,
+            width,
+            depth,
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(3.0, 3.0, 3.0),
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hits_flat_field() {
+        let flat = heightfield(4, 4, |_, _| 0.5);
+        let ray = Ray::new(Vec3::new(1.3, 5.0, 1.7), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let record = flat.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 3.5).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-4);
+        assert!((record.u - 1.3 / 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hits_ramp_with_tilted_normal() {
+        // Heights rise with x, so the surface is the plane y = x.
+        let ramp = heightfield(4, 4, |i, _| i as f32 / 3.0);
+        let ray = Ray::new(Vec3::new(1.5, 5.0, 1.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let record = ramp.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 3.5).abs() < 1e-4);
+        let expected = Vec3::new(-1.0, 1.0, 0.0).unit_vector();
+        assert!((record.normal - expected).length() < 1e-4);
+    }
+
+    #[test]
+    fn misses_outside_grid() {
+        let flat = heightfield(4, 4, |_, _| 0.5);
+        let ray = Ray::new(Vec3::new(4.0, 5.0, 1.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(flat.hit(&ray, 0.001, f32::INFINITY).is_none());
+        let ray = Ray::new(Vec3::new(-1.0, 2.0, 1.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(flat.hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn pyramid_matches_every_cell() {
+        // An odd grid, so some pyramid nodes have missing children.
+        let bumpy = heightfield(10, 7, |i, j| ((i * 7 + j * 3) % 5) as f32 / 4.0);
+        let rays = [
+            Ray::new(Vec3::new(-1.0, 2.5, 0.3), Vec3::new(1.0, -0.3, 0.4), 0.0),
+            Ray::new(Vec3::new(4.0, 4.0, 4.0), Vec3::new(-1.0, -1.2, -0.8), 0.0),
+            Ray::new(Vec3::new(2.9, 3.5, -0.5), Vec3::new(-0.2, -0.5, 1.0), 0.0),
+            Ray::new(Vec3::new(0.1, 1.5, 2.9), Vec3::new(1.0, 0.05, -1.0), 0.0),
+        ];
+        for ray in rays.iter() {
+            let mut expected: Option<f32> = None;
+            for j in 0..6 {
+                for i in 0..9 {
+                    let limit = expected.unwrap_or(f32::INFINITY);
+                    if let Some((t, _)) = bumpy.hit_cell(ray, i, j, 0.001, limit) {
+                        expected = Some(t);
+                    }
+                }
+            }
+            let found = bumpy.hit(ray, 0.001, f32::INFINITY).map(|record| record.t);
+            assert_eq!(found.is_some(), expected.is_some());
+            if let (Some(found), Some(expected)) = (found, expected) {
+                assert!((found - expected).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_height_map_is_an_error() {
+        let result = Heightfield::from_image(
+            "no_such_height_map.png",
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -1,6 +1,11 @@
 use crate::image_utils::aabb::AABB;
 use crate::image_utils::ray::Ray;
+use crate::lights::area::probe_bounds;
+use crate::lights::light::LightBounds;
+use crate::lights::linking::ObjectLinking;
 use crate::materials::materials::Material;
+use crate::utils::onb::ONB;
+use crate::utils::random_double;
 use crate::utils::vec3::Vec3;
 use std::sync::Arc;
 
@@ -9,25 +14,62 @@ pub trait Hittable {
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB>;
     fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync>;
 
-    fn box_compare(&self, b: &Arc<dyn Hittable + Send + Sync>, axis: usize) -> bool {
-        let aabb = self.bounding_box(0.0, 0.0).unwrap();
-        let babb = b.bounding_box(0.0, 0.0).unwrap();
-        aabb.min[axis] < babb.min[axis]
+    // Every span of the ray inside this object, sorted along the ray. Only
+    // meaningful for closed objects. The default walks successive hits from
+    // the far past of the ray and pairs them by parity, which does not rely
+    // on the object reporting outward normals (`Box` faces do not).
+    // It gives up after `MAX_INTERVAL_HITS` hits, so a ray crossing more than
+    // half that many spans loses the rest, and it steps 0.0001 past each hit,
+    // so two surfaces closer than that along the ray (a very thin shell, or a
+    // grazing ray touching a surface twice) are counted as one and the parity
+    // of everything after them is inverted. Objects that can do better should
+    // override it.
+    fn intervals(&self, ray: &Ray) -> Vec<HitInterval> {
+        let mut intervals = Vec::new();
+        let mut enter: Option<HitRecord> = None;
+        let mut t_min = f32::NEG_INFINITY;
+        for _ in 0..MAX_INTERVAL_HITS {
+            let mut record = match self.hit(ray, t_min, f32::INFINITY) {
+                Some(record) => record,
+                None => break,
+            };
+            t_min = record.t + 0.0001;
+            match enter.take() {
+                None => {
+                    record.front_face = true;
+                    enter = Some(record);
+                }
+                Some(enter) => {
+                    record.front_face = false;
+                    intervals.push(HitInterval::new(enter, record));
+                }
+            }
+        }
+        intervals
     }
 
-    fn box_x_compare(&self, b: &Arc<dyn Hittable + Send + Sync>) -> bool {
-        self.box_compare(b, 0)
+    // Density, in solid angle seen from `origin`, of the directions `random`
+    // picks towards this object as it is at `time`. Only needed for objects
+    // sampled as lights.
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3, _time: f32) -> f32 {
+        0.0
     }
 
-    fn box_y_compare(&self, b: &Arc<dyn Hittable + Send + Sync>) -> bool {
-        self.box_compare(b, 1)
+    fn random(&self, _origin: Vec3, _time: f32) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
     }
 
-    fn box_z_compare(&self, b: &Arc<dyn Hittable + Send + Sync>) -> bool {
-        self.box_compare(b, 2)
+    // Where and how strongly the object emits over [`time0`, `time1`], for
+    // picking it among other lights; `None` if it emits nothing. The
+    // default estimates them by shooting rays at the object.
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        probe_bounds(self, time0, time1)
     }
 }
 
+const MAX_INTERVAL_HITS: usize = 64;
+
+#[derive(Clone)]
 pub struct HitRecord {
     pub position: Vec3,
     pub normal: Vec3,
@@ -35,7 +77,11 @@ pub struct HitRecord {
     pub t: f32,
     pub u: f32,
     pub v: f32,
+    // Direction of increasing `u` on the surface, zero when the primitive
+    // does not provide one.
+    pub tangent: Vec3,
     pub front_face: bool,
+    pub linking: ObjectLinking,
 }
 
 impl HitRecord {
@@ -47,7 +93,24 @@ impl HitRecord {
             t,
             u: 0.0,
             v: 0.0,
+            tangent: Vec3::new(0.0, 0.0, 0.0),
             front_face: false,
+            linking: ObjectLinking::new(),
+        }
+    }
+
+    // Local frame materials work in: z is the normal and x follows the
+    // tangent when there is one.
+    pub fn shading_frame(&self) -> ONB {
+        let tangent = self.tangent - self.tangent.dot(self.normal) * self.normal;
+        if tangent.near_zero() {
+            return ONB::build_from_w(self.normal);
+        }
+        let u = tangent.unit_vector();
+        ONB {
+            u,
+            v: self.normal.cross(u),
+            w: self.normal,
         }
     }
 
@@ -61,6 +124,31 @@ impl HitRecord {
     }
 }
 
+// Solid angle density of a direction from the ray origin that uniformly
+// sampling a surface of `area` produces, `record` being where it lands.
+pub fn area_pdf(record: &HitRecord, direction: Vec3, area: f32) -> f32 {
+    let distance_squared = record.t * record.t * direction.length_squared();
+    let cosine = (direction.dot(record.normal) / direction.length()).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+// A span of a ray inside a closed object. `enter` is a front face hit and
+// `exit` a back face hit; both normals face the ray as usual.
+#[derive(Clone)]
+pub struct HitInterval {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
+impl HitInterval {
+    pub fn new(enter: HitRecord, exit: HitRecord) -> HitInterval {
+        HitInterval { enter, exit }
+    }
+}
+
 pub struct HittableList {
     pub objects: Vec<Arc<dyn Hittable + Send + Sync>>,
 }
@@ -75,10 +163,6 @@ impl HittableList {
     pub fn add(&mut self, object: Arc<dyn Hittable + Send + Sync>) {
         self.objects.push(object);
     }
-
-    pub fn clear(&mut self) {
-        self.objects.clear();
-    }
 }
 
 impl Hittable for HittableList {
@@ -112,6 +196,35 @@ impl Hittable for HittableList {
         output_box
     }
 
+    // Picks one of the objects uniformly.
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.objects.len() as f32;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction, time))
+            .sum()
+    }
+
+    // An empty list has nothing to aim at; its density is zero everywhere.
+    fn random(&self, origin: Vec3, time: f32) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = (random_double() * self.objects.len() as f32) as usize;
+        self.objects[index.min(self.objects.len() - 1)].random(origin, time)
+    }
+
+    // The emitting objects together.
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        self.objects
+            .iter()
+            .filter_map(|object| object.light_bounds(time0, time1))
+            .reduce(|bounds, other| bounds.union(&other))
+    }
+
     fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
         Arc::new(self.clone())
     }
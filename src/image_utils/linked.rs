@@ -0,0 +1,57 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{HitInterval, HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
+use crate::lights::linking::ObjectLinking;
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+// Gives the hits of `object` its light linking, for the integrator to
+// honour. Sampling the object as a light goes through unchanged.
+pub struct Linked {
+    pub object: Arc<dyn Hittable + Send + Sync>,
+    pub linking: ObjectLinking,
+}
+
+impl Linked {
+    pub fn new(object: Arc<dyn Hittable + Send + Sync>, linking: ObjectLinking) -> Linked {
+        Linked { object, linking }
+    }
+}
+
+impl Hittable for Linked {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let mut record = self.object.hit(ray, t_min, t_max)?;
+        record.linking = self.linking;
+        Some(record)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<HitInterval> {
+        let mut intervals = self.object.intervals(ray);
+        for interval in &mut intervals {
+            interval.enter.linking = self.linking;
+            interval.exit.linking = self.linking;
+        }
+        intervals
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+        self.object.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        self.object.pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: Vec3, time: f32) -> Vec3 {
+        self.object.random(origin, time)
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        self.object.light_bounds(time0, time1)
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Linked::new(self.object.clone_dyn(), self.linking))
+    }
+}
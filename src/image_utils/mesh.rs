@@ -0,0 +1,546 @@
+use crate::image_utils::hittable::HittableList;
+use crate::image_utils::texture::Texture;
+use crate::image_utils::triangle::Triangle;
+use crate::materials::materials::Material;
+use crate::utils::vec3::Vec3;
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Subdivision {
+    // Quads from any polygons, converging to bicubic B-spline patches.
+    CatmullClark,
+    // Triangles, polygons being fanned first, converging to box splines.
+    Loop,
+}
+
+// Polygon mesh kept in shared-vertex form so it can be refined and displaced
+// before being turned into triangles. Texture coordinates, when present, are
+// one per position and interpolated linearly while subdividing.
+#[derive(Clone)]
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub faces: Vec<Vec<usize>>,
+}
+
+// Edge and vertex adjacency of a mesh. Edges are keyed by their sorted end
+// points, and kept in that order so that subdivision numbers new vertices the
+// same way on every run; an edge without exactly two faces is treated as a
+// boundary.
+struct Topology {
+    edge_faces: BTreeMap<(usize, usize), Vec<usize>>,
+    neighbours: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Vec3>, faces: Vec<Vec<usize>>) -> Mesh {
+        Mesh::with_uvs(positions, Vec::new(), faces)
+    }
+
+    pub fn with_uvs(positions: Vec<Vec3>, uvs: Vec<(f32, f32)>, faces: Vec<Vec<usize>>) -> Mesh {
+        assert!(uvs.is_empty() || uvs.len() == positions.len());
+        Mesh {
+            positions,
+            uvs,
+            faces,
+        }
+    }
+
+    // Reads the `v`, `vt` and `f` statements of a Wavefront OBJ file. Each
+    // position takes the first texture coordinate it is paired with.
+    pub fn from_obj(filename: &str) -> Result<Mesh, String> {
+        let source = std::fs::read_to_string(filename)
+            .map_err(|e| format!("cannot read OBJ file {filename}: {e}"))?;
+        Mesh::parse_obj(&source)
+    }
+
+    pub fn parse_obj(source: &str) -> Result<Mesh, String> {
+        let mut positions = Vec::new();
+        let mut texture_coordinates = Vec::new();
+        let mut uv_indices: Vec<Option<usize>> = Vec::new();
+        let mut faces = Vec::new();
+        for (number, line) in source.lines().enumerate() {
+            let error = |message: String| format!("OBJ line {}: {message}", number + 1);
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let c = parse_numbers(tokens, 3).map_err(error)?;
+                    positions.push(Vec3::new(c[0], c[1], c[2]));
+                    uv_indices.push(None);
+                }
+                Some("vt") => {
+                    let c = parse_numbers(tokens.take(2), 1).map_err(error)?;
+                    texture_coordinates.push((c[0], c.get(1).copied().unwrap_or(0.0)));
+                }
+                Some("f") => {
+                    let mut face = Vec::new();
+                    for vertex in tokens {
+                        let mut indices = vertex.split('/');
+                        let position = resolve_index(indices.next().unwrap(), positions.len())
+                            .map_err(error)?;
+                        if let Some(uv) = indices.next().filter(|t| !t.is_empty()) {
+                            let uv = resolve_index(uv, texture_coordinates.len()).map_err(error)?;
+                            uv_indices[position].get_or_insert(uv);
+                        }
+                        face.push(position);
+                    }
+                    if face.len() < 3 {
+                        return Err(error("face with fewer than three vertices".to_string()));
+                    }
+                    faces.push(face);
+                }
+                _ => {}
+            }
+        }
+        let uvs = if texture_coordinates.is_empty() {
+            Vec::new()
+        } else {
+            uv_indices
+                .iter()
+                .map(|i| i.map_or((0.0, 0.0), |i| texture_coordinates[i]))
+                .collect()
+        };
+        Ok(Mesh::with_uvs(positions, uvs, faces))
+    }
+
+    pub fn subdivide(&self, scheme: Subdivision, levels: u32) -> Mesh {
+        let mut mesh = self.clone();
+        for _ in 0..levels {
+            mesh = match scheme {
+                Subdivision::CatmullClark => mesh.catmull_clark(),
+                Subdivision::Loop => mesh.loop_subdivision(),
+            };
+        }
+        mesh
+    }
+
+    // One Catmull-Clark step. Boundaries use the cubic B-spline curve rules
+    // and boundary vertices with more than two boundary edges stay put.
+    pub fn catmull_clark(&self) -> Mesh {
+        let topology = self.topology();
+        let vertex_count = self.positions.len();
+        let face_points: Vec<Vec3> = self.faces.iter().map(|f| self.centroid(f)).collect();
+
+        let mut positions = Vec::with_capacity(vertex_count + self.faces.len());
+        for (i, &p) in self.positions.iter().enumerate() {
+            let faces = &topology.vertex_faces[i];
+            let boundary = topology.boundary_neighbours(i);
+            positions.push(if faces.is_empty() {
+                p
+            } else if !boundary.is_empty() {
+                if boundary.len() == 2 {
+                    (self.positions[boundary[0]] + 6.0 * p + self.positions[boundary[1]]) / 8.0
+                } else {
+                    p
+                }
+            } else {
+                let n = topology.neighbours[i].len() as f32;
+                let face_average = average(faces.iter().map(|&f| face_points[f]));
+                let edge_average = average(
+                    topology.neighbours[i]
+                        .iter()
+                        .map(|&j| (p + self.positions[j]) / 2.0),
+                );
+                (face_average + 2.0 * edge_average + (n - 3.0) * p) / n
+            });
+        }
+        positions.extend_from_slice(&face_points);
+
+        let mut edge_points = BTreeMap::new();
+        for (&(a, b), faces) in &topology.edge_faces {
+            let point = if faces.len() == 2 {
+                (self.positions[a]
+                    + self.positions[b]
+                    + face_points[faces[0]]
+                    + face_points[faces[1]])
+                    / 4.0
+            } else {
+                (self.positions[a] + self.positions[b]) / 2.0
+            };
+            edge_points.insert((a, b), positions.len());
+            positions.push(point);
+        }
+
+        let mut uvs = Vec::new();
+        if !self.uvs.is_empty() {
+            uvs = vec![(0.0, 0.0); positions.len()];
+            uvs[..vertex_count].copy_from_slice(&self.uvs);
+            for (f, face) in self.faces.iter().enumerate() {
+                uvs[vertex_count + f] = average_uv(face.iter().map(|&i| self.uvs[i]));
+            }
+            for (&(a, b), &index) in &edge_points {
+                uvs[index] = average_uv([self.uvs[a], self.uvs[b]].into_iter());
+            }
+        }
+
+        let mut faces = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let n = face.len();
+            for i in 0..n {
+                let (previous, current, next) = (face[(i + n - 1) % n], face[i], face[(i + 1) % n]);
+                faces.push(vec![
+                    current,
+                    edge_points[&edge_key(current, next)],
+                    vertex_count + f,
+                    edge_points[&edge_key(previous, current)],
+                ]);
+            }
+        }
+        Mesh::with_uvs(positions, uvs, faces)
+    }
+
+    // One Loop step, with the crease rules of Hoppe et al. on boundaries.
+    pub fn loop_subdivision(&self) -> Mesh {
+        let mesh = self.triangulate();
+        let topology = mesh.topology();
+        let vertex_count = mesh.positions.len();
+
+        let mut positions = Vec::with_capacity(vertex_count + topology.edge_faces.len());
+        for (i, &p) in mesh.positions.iter().enumerate() {
+            let neighbours = &topology.neighbours[i];
+            let boundary = topology.boundary_neighbours(i);
+            positions.push(if neighbours.is_empty() {
+                p
+            } else if !boundary.is_empty() {
+                if boundary.len() == 2 {
+                    0.75 * p + (mesh.positions[boundary[0]] + mesh.positions[boundary[1]]) / 8.0
+                } else {
+                    p
+                }
+            } else {
+                let n = neighbours.len() as f32;
+                let beta = (0.625 - (0.375 + 0.25 * (2.0 * PI / n).cos()).powi(2)) / n;
+                let sum = neighbours
+                    .iter()
+                    .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &j| sum + mesh.positions[j]);
+                (1.0 - n * beta) * p + beta * sum
+            });
+        }
+
+        let mut edge_points = BTreeMap::new();
+        for (&(a, b), faces) in &topology.edge_faces {
+            let opposite = |f: usize| {
+                let face = &mesh.faces[f];
+                face.iter()
+                    .find(|&&v| v != a && v != b)
+                    .map(|&v| mesh.positions[v])
+            };
+            // A degenerate face has no third vertex, in which case the edge
+            // is split like a boundary.
+            let point = match faces[..] {
+                [f, g] => match (opposite(f), opposite(g)) {
+                    (Some(c), Some(d)) => {
+                        0.375 * (mesh.positions[a] + mesh.positions[b]) + 0.125 * (c + d)
+                    }
+                    _ => (mesh.positions[a] + mesh.positions[b]) / 2.0,
+                },
+                _ => (mesh.positions[a] + mesh.positions[b]) / 2.0,
+            };
+            edge_points.insert((a, b), positions.len());
+            positions.push(point);
+        }
+
+        let mut uvs = Vec::new();
+        if !mesh.uvs.is_empty() {
+            uvs = vec![(0.0, 0.0); positions.len()];
+            uvs[..vertex_count].copy_from_slice(&mesh.uvs);
+            for (&(a, b), &index) in &edge_points {
+                uvs[index] = average_uv([mesh.uvs[a], mesh.uvs[b]].into_iter());
+            }
+        }
+
+        let mut faces = Vec::with_capacity(4 * mesh.faces.len());
+        for face in &mesh.faces {
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let ab = edge_points[&edge_key(a, b)];
+            let bc = edge_points[&edge_key(b, c)];
+            let ca = edge_points[&edge_key(c, a)];
+            faces.push(vec![a, ab, ca]);
+            faces.push(vec![ab, b, bc]);
+            faces.push(vec![ca, bc, c]);
+            faces.push(vec![ab, bc, ca]);
+        }
+        Mesh::with_uvs(positions, uvs, faces)
+    }
+
+    // Fans every polygon from its first vertex.
+    pub fn triangulate(&self) -> Mesh {
+        let mut faces = Vec::new();
+        for face in &self.faces {
+            for i in 1..face.len().saturating_sub(1) {
+                faces.push(vec![face[0], face[i], face[i + 1]]);
+            }
+        }
+        Mesh::with_uvs(self.positions.clone(), self.uvs.clone(), faces)
+    }
+
+    // Area weighted average of the (Newell) normals of the faces around each
+    // vertex.
+    pub fn vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::new(0.0, 0.0, 0.0); self.positions.len()];
+        for face in &self.faces {
+            let mut normal = Vec3::new(0.0, 0.0, 0.0);
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                normal = normal + self.positions[a].cross(self.positions[b]);
+            }
+            for &i in face {
+                normals[i] = normals[i] + normal;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|n| {
+                if n.near_zero() {
+                    Vec3::new(0.0, 1.0, 0.0)
+                } else {
+                    n.unit_vector()
+                }
+            })
+            .collect()
+    }
+
+    // Moves every vertex along its normal by `scale` times the average of the
+    // texture's channels at the vertex.
+    pub fn displace(&self, texture: &dyn Texture, scale: f32) -> Mesh {
+        let normals = self.vertex_normals();
+        let positions = self
+            .positions
+            .iter()
+            .zip(normals)
+            .enumerate()
+            .map(|(i, (&p, normal))| {
+                let (u, v) = self.uvs.get(i).copied().unwrap_or((0.0, 0.0));
+                let value = texture.value(u, v, p);
+                p + scale * (value.x + value.y + value.z) / 3.0 * normal
+            })
+            .collect();
+        Mesh::with_uvs(positions, self.uvs.clone(), self.faces.clone())
+    }
+
+    // Smooth shaded triangles, usually put in a `BVHNode`.
+    pub fn triangles(&self, material: Arc<dyn Material + Send + Sync>) -> HittableList {
+        let normals = self.vertex_normals();
+        let mut list = HittableList::new();
+        for face in &self.triangulate().faces {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            let uvs = if self.uvs.is_empty() {
+                None
+            } else {
+                Some([self.uvs[a], self.uvs[b], self.uvs[c]])
+            };
+            list.add(Arc::new(Triangle::with_vertex_attributes(
+                [self.positions[a], self.positions[b], self.positions[c]],
+                Some([normals[a], normals[b], normals[c]]),
+                uvs,
+                material.clone(),
+            )));
+        }
+        list
+    }
+
+    fn centroid(&self, face: &[usize]) -> Vec3 {
+        average(face.iter().map(|&i| self.positions[i]))
+    }
+
+    fn topology(&self) -> Topology {
+        let mut topology = Topology {
+            edge_faces: BTreeMap::new(),
+            neighbours: vec![Vec::new(); self.positions.len()],
+            vertex_faces: vec![Vec::new(); self.positions.len()],
+        };
+        for (f, face) in self.faces.iter().enumerate() {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                let faces = topology.edge_faces.entry(edge_key(a, b)).or_default();
+                if faces.is_empty() {
+                    topology.neighbours[a].push(b);
+                    topology.neighbours[b].push(a);
+                }
+                faces.push(f);
+                topology.vertex_faces[a].push(f);
+            }
+        }
+        topology
+    }
+}
+
+impl Topology {
+    // Neighbours of `vertex` across boundary edges.
+    fn boundary_neighbours(&self, vertex: usize) -> Vec<usize> {
+        self.neighbours[vertex]
+            .iter()
+            .copied()
+            .filter(|&j| self.edge_faces[&edge_key(vertex, j)].len() != 2)
+            .collect()
+    }
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn average(points: impl Iterator<Item = Vec3>) -> Vec3 {
+    let (sum, count) = points.fold((Vec3::new(0.0, 0.0, 0.0), 0), |(sum, count), p| {
+        (sum + p, count + 1)
+    });
+    sum / count as f32
+}
+
+fn average_uv(uvs: impl Iterator<Item = (f32, f32)>) -> (f32, f32) {
+    let (u, v, count) = uvs.fold((0.0, 0.0, 0), |(u, v, count), uv| {
+        (u + uv.0, v + uv.1, count + 1)
+    });
+    (u / count as f32, v / count as f32)
+}
+
+// At least `count` numbers from the tokens.
+fn parse_numbers<'a>(
+    tokens: impl Iterator<Item = &'a str>,
+    count: usize,
+) -> Result<Vec<f32>, String> {
+    let numbers = tokens
+        .map(|t| t.parse().map_err(|_| format!("invalid number {t}")))
+        .collect::<Result<Vec<f32>, String>>()?;
+    if numbers.len() < count {
+        return Err(format!("expected {count} numbers, found {}", numbers.len()));
+    }
+    Ok(numbers)
+}
+
+// One based OBJ index, or negative counting back from the last of the
+// `count` elements read so far.
+fn resolve_index(token: &str, count: usize) -> Result<usize, String> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| format!("invalid index {token}"))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {index} out of range for {count} elements"));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube() -> Mesh {
+        Mesh::parse_obj(
+            "v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\n\
+             v -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\n\
+             f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 3 4 8 7\nf 2 3 7 6\nf 4 1 5 8\n",
+        )
+        .unwrap()
+    }
+
+    fn tetrahedron() -> Mesh {
+        Mesh::new(
+            vec![
+                Vec3::new(1.0, 1.0, 1.0),
+                Vec3::new(1.0, -1.0, -1.0),
+                Vec3::new(-1.0, 1.0, -1.0),
+                Vec3::new(-1.0, -1.0, 1.0),
+            ],
+            vec![vec![0, 1, 2], vec![0, 3, 1], vec![0, 2, 3], vec![1, 3, 2]],
+        )
+    }
+
+    #[test]
+    fn parses_positions_and_faces() {
+        let mesh = cube();
+        assert_eq!(mesh.positions.len(), 8);
+        assert_eq!(mesh.faces.len(), 6);
+        assert!(mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn parse_errors_are_returned() {
+        for source in [
+            "v 0 0\n",
+            "v 0 0 zero\n",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n",
+            "v 0 0 0\nv 1 0 0\nf 1 2\n",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n",
+        ] {
+            assert!(Mesh::parse_obj(source).is_err(), "{:?}", source);
+        }
+        assert!(Mesh::from_obj("no_such_mesh.obj").is_err());
+    }
+
+    #[test]
+    fn catmull_clark_counts() {
+        // Every vertex, face and edge gives a point; every quad gives four.
+        let mesh = cube().subdivide(Subdivision::CatmullClark, 1);
+        assert_eq!(mesh.positions.len(), 8 + 6 + 12);
+        assert_eq!(mesh.faces.len(), 24);
+        assert!(mesh.faces.iter().all(|face| face.len() == 4));
+    }
+
+    #[test]
+    fn loop_counts() {
+        let mesh = tetrahedron().subdivide(Subdivision::Loop, 1);
+        assert_eq!(mesh.positions.len(), 4 + 6);
+        assert_eq!(mesh.faces.len(), 16);
+        let mesh = tetrahedron().subdivide(Subdivision::Loop, 2);
+        assert_eq!(mesh.positions.len(), 10 + 24);
+        assert_eq!(mesh.faces.len(), 64);
+    }
+
+    #[test]
+    fn loop_boundary_follows_the_crease_rules() {
+        let mesh = Mesh::new(
+            vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(4.0, 0.0, 0.0),
+                Vec3::new(0.0, 4.0, 0.0),
+            ],
+            vec![vec![0, 1, 2]],
+        );
+        let subdivided = mesh.loop_subdivision();
+        assert_eq!(subdivided.positions.len(), 6);
+        assert_eq!(subdivided.faces.len(), 4);
+        assert!((subdivided.positions[0] - Vec3::new(0.5, 0.5, 0.0)).length() < 1e-5);
+        assert!(subdivided.positions[3..]
+            .iter()
+            .any(|&p| (p - Vec3::new(2.0, 0.0, 0.0)).length() < 1e-5));
+    }
+
+    #[test]
+    fn loop_splits_edges_of_degenerate_faces() {
+        // Edge (0, 1) appears twice in the degenerate face, which has no
+        // vertex opposite it.
+        let mesh = Mesh::new(
+            vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(2.0, 2.0, 0.0),
+                Vec3::new(0.0, 2.0, 0.0),
+            ],
+            vec![vec![0, 1, 0], vec![1, 2, 3]],
+        );
+        let subdivided = mesh.loop_subdivision();
+        assert!(subdivided
+            .positions
+            .iter()
+            .any(|&p| (p - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-5));
+    }
+
+    #[test]
+    fn subdivision_is_deterministic() {
+        let first = cube().subdivide(Subdivision::Loop, 2);
+        let second = cube().subdivide(Subdivision::Loop, 2);
+        assert_eq!(first.faces, second.faces);
+        for (a, b) in first.positions.iter().zip(&second.positions) {
+            assert_eq!((a.x, a.y, a.z), (b.x, b.y, b.z));
+        }
+    }
+}
@@ -2,12 +2,24 @@ pub mod aabb;
 pub mod r#box;
 pub mod bvh;
 pub mod camera;
+pub mod cone;
 pub mod constant_medium;
+pub mod csg;
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod heightfield;
 pub mod hittable;
 pub mod image;
+pub mod linked;
+pub mod mesh;
+pub mod quad;
 pub mod ray;
 pub mod rectangle;
-pub mod rotate;
+pub mod sdf;
 pub mod sphere;
+pub mod subsurface;
 pub mod texture;
-pub mod translate;
+pub mod torus;
+pub mod transformed;
+pub mod triangle;
@@ -0,0 +1,139 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{area_pdf, HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
+use crate::materials::materials::Material;
+use crate::utils::random_double;
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+pub struct Quad {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Arc<dyn Material + Send + Sync>,
+    normal: Vec3,
+    d: f32,
+    w: Vec3,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: Arc<dyn Material + Send + Sync>) -> Quad {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        Quad {
+            q,
+            u,
+            v,
+            material,
+            normal,
+            d: normal.dot(q),
+            w: n / n.dot(n),
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - self.normal.dot(ray.origin)) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let position = ray.at(t);
+        let planar = position - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        let mut record = HitRecord::new(position, self.material.clone(), t);
+        record.u = alpha;
+        record.v = beta;
+        record.set_face_normal(ray, self.normal);
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+        let bbox = AABB::from_points(&[
+            self.q,
+            self.q + self.u,
+            self.q + self.v,
+            self.q + self.u + self.v,
+        ]);
+        Some(AABB::new(bbox.min - pad, bbox.max + pad))
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        match self.hit(&Ray::new(origin, direction, time), 0.001, f32::INFINITY) {
+            Some(record) => area_pdf(&record, direction, self.u.cross(self.v).length()),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Vec3, _time: f32) -> Vec3 {
+        self.q + random_double() * self.u + random_double() * self.v - origin
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        let emission = self.material.emission_bounds()?;
+        Some(LightBounds::from_emission(
+            self.bounding_box(time0, time1)?,
+            self.normal,
+            1.0,
+            self.u.cross(self.v).length(),
+            emission,
+        ))
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Quad::new(self.q, self.u, self.v, self.material.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::Lambertian;
+
+    // Lies in y = 0 with its normal, u x v, pointing down.
+    fn quad() -> Quad {
+        Quad::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hits_back_face() {
+        let ray = Ray::new(Vec3::new(1.0, 3.0, 0.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let record = quad().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 3.0).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-4);
+        assert!(!record.front_face);
+        assert!((record.u - 0.5).abs() < 1e-4 && (record.v - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hits_front_face() {
+        let ray = Ray::new(Vec3::new(0.5, -1.0, 0.25), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let record = quad().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.normal - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-4);
+        assert!(record.front_face);
+    }
+
+    #[test]
+    fn misses_outside_edges() {
+        let ray = Ray::new(Vec3::new(2.5, 3.0, 0.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(quad().hit(&ray, 0.001, f32::INFINITY).is_none());
+        let ray = Ray::new(Vec3::new(1.0, 3.0, -0.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(quad().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,501 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::materials::materials::Material;
+use crate::utils::perlin::Perlin;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+const MAX_STEPS: usize = 256;
+const EPSILON: f32 = 0.0001;
+
+pub trait Sdf {
+    // Signed distance from `p` to the surface, negative inside.
+    fn distance(&self, p: Vec3) -> f32;
+}
+
+// Sphere traces `sdf` inside `extent`, which must enclose the whole surface.
+// Deformations such as twists and displacement are not exact distances; a
+// `step_scale` below one keeps the march from overshooting them.
+pub struct SdfObject {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub extent: AABB,
+    pub step_scale: f32,
+    pub material: Arc<dyn Material + Send + Sync>,
+}
+
+impl SdfObject {
+    pub fn new(
+        sdf: Arc<dyn Sdf + Send + Sync>,
+        extent: AABB,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> SdfObject {
+        SdfObject::new_with_step_scale(sdf, extent, 1.0, material)
+    }
+
+    pub fn new_with_step_scale(
+        sdf: Arc<dyn Sdf + Send + Sync>,
+        extent: AABB,
+        step_scale: f32,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> SdfObject {
+        SdfObject {
+            sdf,
+            extent,
+            step_scale,
+            material,
+        }
+    }
+
+    // Tetrahedron technique: four evaluations instead of six.
+    fn normal(&self, p: Vec3) -> Vec3 {
+        let h = EPSILON;
+        let k = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        k.iter()
+            .fold(Vec3::new(0.0, 0.0, 0.0), |gradient, k| {
+                gradient + *k * self.sdf.distance(p + h * *k)
+            })
+            .unit_vector()
+    }
+
+    fn clip(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let mut t0 = t_min;
+        let mut t1 = t_max;
+        for i in 0..3 {
+            let inv_d = 1.0 / ray.direction[i];
+            let mut near = (self.extent.min[i] - ray.origin[i]) * inv_d;
+            let mut far = (self.extent.max[i] - ray.origin[i]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut near, &mut far);
+            }
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t1 < t0 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t0, t1) = self.clip(ray, t_min, t_max)?;
+        let inv_length = 1.0 / ray.direction.length();
+        let mut t = t0;
+        // Rays spawned on the surface start within the hit threshold and
+        // must leave it before a hit can count.
+        let mut leaving_surface = true;
+        for _ in 0..MAX_STEPS {
+            if t > t1 {
+                return None;
+            }
+            let position = ray.at(t);
+            let distance = self.sdf.distance(position).abs();
+            if leaving_surface {
+                if distance < EPSILON {
+                    t += EPSILON * inv_length;
+                    continue;
+                }
+                leaving_surface = false;
+            }
+            if distance < EPSILON {
+                let mut record = HitRecord::new(position, self.material.clone(), t);
+                let outward_normal = self.normal(position);
+                record.set_face_normal(ray, outward_normal);
+                record.u = (outward_normal.z.atan2(outward_normal.x) + PI) / (2.0 * PI);
+                record.v = (outward_normal.y.clamp(-1.0, 1.0).asin() + PI / 2.0) / PI;
+                return Some(record);
+            }
+            t += distance * self.step_scale * inv_length;
+        }
+        None
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        Some(self.extent)
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(SdfObject::new_with_step_scale(
+            self.sdf.clone(),
+            self.extent,
+            self.step_scale,
+            self.material.clone(),
+        ))
+    }
+}
+
+pub struct SdfSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl SdfSphere {
+    pub fn new(center: Vec3, radius: f32) -> SdfSphere {
+        SdfSphere { center, radius }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Vec3) -> f32 {
+        (p - self.center).length() - self.radius
+    }
+}
+
+// Box centered on `center`, with edges rounded by `radius`.
+pub struct SdfBox {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub radius: f32,
+}
+
+impl SdfBox {
+    pub fn new(center: Vec3, half_extents: Vec3) -> SdfBox {
+        SdfBox::new_rounded(center, half_extents, 0.0)
+    }
+
+    pub fn new_rounded(center: Vec3, half_extents: Vec3, radius: f32) -> SdfBox {
+        SdfBox {
+            center,
+            half_extents,
+            radius,
+        }
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Vec3) -> f32 {
+        let p = p - self.center;
+        let q = Vec3::new(
+            p.x.abs() - self.half_extents.x + self.radius,
+            p.y.abs() - self.half_extents.y + self.radius,
+            p.z.abs() - self.half_extents.z + self.radius,
+        );
+        let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+        let inside = q.x.max(q.y).max(q.z).min(0.0);
+        outside + inside - self.radius
+    }
+}
+
+// Torus lying in the xz plane.
+pub struct SdfTorus {
+    pub center: Vec3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl SdfTorus {
+    pub fn new(center: Vec3, major_radius: f32, minor_radius: f32) -> SdfTorus {
+        SdfTorus {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Vec3) -> f32 {
+        let p = p - self.center;
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (ring * ring + p.y * p.y).sqrt() - self.minor_radius
+    }
+}
+
+pub struct SdfCapsule {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub radius: f32,
+}
+
+impl SdfCapsule {
+    pub fn new(a: Vec3, b: Vec3, radius: f32) -> SdfCapsule {
+        SdfCapsule { a, b, radius }
+    }
+}
+
+impl Sdf for SdfCapsule {
+    fn distance(&self, p: Vec3) -> f32 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+        (pa - h * ba).length() - self.radius
+    }
+}
+
+pub struct SdfUnion {
+    pub a: Arc<dyn Sdf + Send + Sync>,
+    pub b: Arc<dyn Sdf + Send + Sync>,
+}
+
+impl SdfUnion {
+    pub fn new(a: Arc<dyn Sdf + Send + Sync>, b: Arc<dyn Sdf + Send + Sync>) -> SdfUnion {
+        SdfUnion { a, b }
+    }
+}
+
+impl Sdf for SdfUnion {
+    fn distance(&self, p: Vec3) -> f32 {
+        self.a.distance(p).min(self.b.distance(p))
+    }
+}
+
+pub struct SdfIntersection {
+    pub a: Arc<dyn Sdf + Send + Sync>,
+    pub b: Arc<dyn Sdf + Send + Sync>,
+}
+
+impl SdfIntersection {
+    pub fn new(a: Arc<dyn Sdf + Send + Sync>, b: Arc<dyn Sdf + Send + Sync>) -> SdfIntersection {
+        SdfIntersection { a, b }
+    }
+}
+
+impl Sdf for SdfIntersection {
+    fn distance(&self, p: Vec3) -> f32 {
+        self.a.distance(p).max(self.b.distance(p))
+    }
+}
+
+// `a` with `b` carved out of it.
+pub struct SdfDifference {
+    pub a: Arc<dyn Sdf + Send + Sync>,
+    pub b: Arc<dyn Sdf + Send + Sync>,
+}
+
+impl SdfDifference {
+    pub fn new(a: Arc<dyn Sdf + Send + Sync>, b: Arc<dyn Sdf + Send + Sync>) -> SdfDifference {
+        SdfDifference { a, b }
+    }
+}
+
+impl Sdf for SdfDifference {
+    fn distance(&self, p: Vec3) -> f32 {
+        self.a.distance(p).max(-self.b.distance(p))
+    }
+}
+
+// Polynomial smooth minimum; `k` is the size of the blend region.
+pub struct SdfSmoothUnion {
+    pub a: Arc<dyn Sdf + Send + Sync>,
+    pub b: Arc<dyn Sdf + Send + Sync>,
+    pub k: f32,
+}
+
+impl SdfSmoothUnion {
+    pub fn new(
+        a: Arc<dyn Sdf + Send + Sync>,
+        b: Arc<dyn Sdf + Send + Sync>,
+        k: f32,
+    ) -> SdfSmoothUnion {
+        SdfSmoothUnion { a, b, k }
+    }
+}
+
+impl Sdf for SdfSmoothUnion {
+    fn distance(&self, p: Vec3) -> f32 {
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        let h = (0.5 + 0.5 * (d2 - d1) / self.k).clamp(0.0, 1.0);
+        d2 + (d1 - d2) * h - self.k * h * (1.0 - h)
+    }
+}
+
+pub struct SdfTranslate {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub offset: Vec3,
+}
+
+impl SdfTranslate {
+    pub fn new(sdf: Arc<dyn Sdf + Send + Sync>, offset: Vec3) -> SdfTranslate {
+        SdfTranslate { sdf, offset }
+    }
+}
+
+impl Sdf for SdfTranslate {
+    fn distance(&self, p: Vec3) -> f32 {
+        self.sdf.distance(p - self.offset)
+    }
+}
+
+// Rotates the xz plane by `amount` radians per unit of height.
+pub struct SdfTwist {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub amount: f32,
+}
+
+impl SdfTwist {
+    pub fn new(sdf: Arc<dyn Sdf + Send + Sync>, amount: f32) -> SdfTwist {
+        SdfTwist { sdf, amount }
+    }
+}
+
+impl Sdf for SdfTwist {
+    fn distance(&self, p: Vec3) -> f32 {
+        let (sin_theta, cos_theta) = (self.amount * p.y).sin_cos();
+        let q = Vec3::new(
+            cos_theta * p.x - sin_theta * p.z,
+            p.y,
+            sin_theta * p.x + cos_theta * p.z,
+        );
+        self.sdf.distance(q)
+    }
+}
+
+// Repeats the cell around the origin every `period` along each axis, `count`
+// times on each side of it. A zero period leaves that axis alone.
+pub struct SdfRepeat {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub period: Vec3,
+    pub count: Vec3,
+}
+
+impl SdfRepeat {
+    pub fn new(sdf: Arc<dyn Sdf + Send + Sync>, period: Vec3, count: Vec3) -> SdfRepeat {
+        SdfRepeat { sdf, period, count }
+    }
+}
+
+impl Sdf for SdfRepeat {
+    fn distance(&self, p: Vec3) -> f32 {
+        let mut q = p;
+        for i in 0..3 {
+            if self.period[i] > 0.0 {
+                let cell = (p[i] / self.period[i])
+                    .round()
+                    .clamp(-self.count[i], self.count[i]);
+                q[i] = p[i] - self.period[i] * cell;
+            }
+        }
+        self.sdf.distance(q)
+    }
+}
+
+// Offsets the surface along its normal by Perlin noise.
+pub struct SdfDisplace {
+    pub sdf: Arc<dyn Sdf + Send + Sync>,
+    pub noise: Perlin,
+    pub frequency: f32,
+    pub amplitude: f32,
+}
+
+impl SdfDisplace {
+    pub fn new(sdf: Arc<dyn Sdf + Send + Sync>, frequency: f32, amplitude: f32) -> SdfDisplace {
+        SdfDisplace {
+            sdf,
+            noise: Perlin::new(),
+            frequency,
+            amplitude,
+        }
+    }
+}
+
+impl Sdf for SdfDisplace {
+    fn distance(&self, p: Vec3) -> f32 {
+        self.sdf.distance(p) + self.amplitude * self.noise.noise(self.frequency * p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::Lambertian;
+
+    fn traced(sdf: Arc<dyn Sdf + Send + Sync>) -> SdfObject {
+        SdfObject::new(
+            sdf,
+            AABB::new(Vec3::new(-2.0, -2.0, -2.0), Vec3::new(2.0, 2.0, 2.0)),
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    fn unit_sphere() -> Arc<dyn Sdf + Send + Sync> {
+        Arc::new(SdfSphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0))
+    }
+
+    #[test]
+    fn hits_sphere() {
+        let ray = Ray::new(Vec3::new(-3.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let record = traced(unit_sphere())
+            .hit(&ray, 0.001, f32::INFINITY)
+            .unwrap();
+        assert!((record.t - 2.0).abs() < 1e-3);
+        assert!((record.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-2);
+        assert!(record.front_face);
+    }
+
+    #[test]
+    fn misses_sphere() {
+        let ray = Ray::new(Vec3::new(-3.0, 1.5, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(traced(unit_sphere())
+            .hit(&ray, 0.001, f32::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn leaves_surface_it_starts_on() {
+        let object = traced(unit_sphere());
+        let outward = Ray::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(object.hit(&outward, 0.001, f32::INFINITY).is_none());
+        let inward = Ray::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), 0.0);
+        let record = object.hit(&inward, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 2.0).abs() < 1e-3);
+        assert!(!record.front_face);
+    }
+
+    #[test]
+    fn hits_box_face() {
+        let cube = Arc::new(SdfBox::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ));
+        let ray = Ray::new(Vec3::new(0.3, 5.0, 0.2), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let record = traced(cube).hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 4.0).abs() < 1e-3);
+        assert!((record.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-2);
+    }
+
+    #[test]
+    fn hits_floor_of_carved_pocket() {
+        // The normal of the carved surface points back into the removed sphere.
+        let cube = Arc::new(SdfBox::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ));
+        let pocket = Arc::new(SdfSphere::new(Vec3::new(0.0, 1.0, 0.0), 0.5));
+        let carved = Arc::new(SdfDifference::new(cube, pocket));
+        let ray = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let record = traced(carved).hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 4.5).abs() < 1e-3);
+        assert!((record.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-2);
+        assert!(record.front_face);
+    }
+
+    #[test]
+    fn torus_and_capsule_distances() {
+        let torus = SdfTorus::new(Vec3::new(0.0, 0.0, 0.0), 2.0, 0.5);
+        assert!((torus.distance(Vec3::new(2.0, 0.0, 0.0)) + 0.5).abs() < 1e-5);
+        assert!((torus.distance(Vec3::new(0.0, 0.0, 0.0)) - 1.5).abs() < 1e-5);
+        let capsule = SdfCapsule::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), 0.5);
+        assert!((capsule.distance(Vec3::new(1.0, 1.0, 0.0)) - 0.5).abs() < 1e-5);
+        assert!((capsule.distance(Vec3::new(0.0, 3.0, 0.0)) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn repeat_stops_after_count() {
+        let row = SdfRepeat::new(
+            Arc::new(SdfSphere::new(Vec3::new(0.0, 0.0, 0.0), 0.5)),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+        assert!((row.distance(Vec3::new(2.0, 0.0, 0.0)) + 0.5).abs() < 1e-5);
+        assert!((row.distance(Vec3::new(4.0, 0.0, 0.0)) - 1.5).abs() < 1e-5);
+    }
+}
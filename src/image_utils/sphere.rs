@@ -1,8 +1,12 @@
 use crate::image_utils::aabb::AABB;
-use crate::image_utils::hittable::{HitRecord, Hittable};
+use crate::image_utils::hittable::{HitInterval, HitRecord, Hittable};
 use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
 use crate::materials::materials::Material;
+use crate::utils::onb::ONB;
+use crate::utils::random_double;
 use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
 use std::sync::Arc;
 
 pub struct Sphere {
@@ -20,6 +24,16 @@ impl Sphere {
         }
     }
 
+    fn record_at(&self, ray: &Ray, t: f32) -> HitRecord {
+        let mut record = HitRecord::new(ray.at(t), self.material.clone(), t);
+        let outward_normal = (record.position - self.center) / self.radius;
+        record.set_face_normal(ray, outward_normal);
+        let (u, v) = self.get_sphere_uv(outward_normal);
+        record.u = u;
+        record.v = v;
+        record
+    }
+
     fn get_sphere_uv(&self, point: Vec3) -> (f32, f32) {
         let phi = point.z.atan2(point.x);
         let theta = point.y.asin();
@@ -49,13 +63,24 @@ impl Hittable for Sphere {
                 return None;
             }
         }
-        let mut record = HitRecord::new(ray.at(root), self.material.clone(), root);
-        let outward_normal = (record.position - self.center) / self.radius;
-        record.set_face_normal(ray, outward_normal);
-        let (u, v) = self.get_sphere_uv(outward_normal);
-        record.u = u;
-        record.v = v;
-        Some(record)
+        Some(self.record_at(ray, root))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<HitInterval> {
+        let oc = ray.origin - self.center;
+        let a = ray.direction.length_squared();
+        let half_b = oc.dot(ray.direction);
+        let c = oc.length_squared() - self.radius * self.radius;
+
+        let discriminant = half_b * half_b - a * c;
+        if discriminant <= 0.0 {
+            return vec![];
+        }
+        let discriminant_sqrt = discriminant.sqrt();
+        vec![HitInterval::new(
+            self.record_at(ray, (-half_b - discriminant_sqrt) / a),
+            self.record_at(ray, (-half_b + discriminant_sqrt) / a),
+        )]
     }
 
     fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
@@ -65,6 +90,31 @@ impl Hittable for Sphere {
         ))
     }
 
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        if self
+            .hit(&Ray::new(origin, direction, time), 0.001, f32::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+        cone_pdf(self.center, self.radius, origin)
+    }
+
+    fn random(&self, origin: Vec3, _time: f32) -> Vec3 {
+        sample_cone(self.center, self.radius, origin)
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        let emission = self.material.emission_bounds()?;
+        Some(LightBounds::from_emission(
+            self.bounding_box(time0, time1)?,
+            Vec3::new(0.0, 0.0, 1.0),
+            -1.0,
+            4.0 * PI * self.radius * self.radius,
+            emission,
+        ))
+    }
+
     fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
         Arc::new(Sphere::new(self.center, self.radius, self.material.clone()))
     }
@@ -141,6 +191,32 @@ impl Hittable for MovingSphere {
         Some(box0.surrounding_box(box1))
     }
 
+    // Sampled as the sphere is at `time`.
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        if self
+            .hit(&Ray::new(origin, direction, time), 0.001, f32::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+        cone_pdf(self.center(time), self.radius, origin)
+    }
+
+    fn random(&self, origin: Vec3, time: f32) -> Vec3 {
+        sample_cone(self.center(time), self.radius, origin)
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        let emission = self.material.emission_bounds()?;
+        Some(LightBounds::from_emission(
+            self.bounding_box(time0, time1)?,
+            Vec3::new(0.0, 0.0, 1.0),
+            -1.0,
+            4.0 * PI * self.radius * self.radius,
+            emission,
+        ))
+    }
+
     fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
         Arc::new(MovingSphere::new(
             self.center0,
@@ -152,3 +228,33 @@ impl Hittable for MovingSphere {
         ))
     }
 }
+
+// Density of `sample_cone`, uniform over the cone of directions a sphere
+// subtends, or over the whole sphere of directions from inside it.
+fn cone_pdf(center: Vec3, radius: f32, origin: Vec3) -> f32 {
+    let distance_squared = (center - origin).length_squared();
+    let radius_squared = radius * radius;
+    if distance_squared <= radius_squared {
+        return 1.0 / (4.0 * std::f32::consts::PI);
+    }
+    let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+    1.0 / (2.0 * std::f32::consts::PI * (1.0 - cos_theta_max))
+}
+
+fn sample_cone(center: Vec3, radius: f32, origin: Vec3) -> Vec3 {
+    let direction = center - origin;
+    let distance_squared = direction.length_squared();
+    let radius_squared = radius * radius;
+    if distance_squared <= radius_squared {
+        return Vec3::random_unit_vector();
+    }
+    let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+    let z = 1.0 + random_double() * (cos_theta_max - 1.0);
+    let phi = 2.0 * std::f32::consts::PI * random_double();
+    let sin_theta = (1.0 - z * z).sqrt();
+    ONB::build_from_w(direction).local_to_world(Vec3::new(
+        phi.cos() * sin_theta,
+        phi.sin() * sin_theta,
+        z,
+    ))
+}
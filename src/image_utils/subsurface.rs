@@ -0,0 +1,319 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::materials::materials::{
+    henyey_greenstein, sample_henyey_greenstein, BsdfSample, Dielectric, LobeFlags, Material,
+};
+use crate::utils::random_double;
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+// Translucent solid scattering light inside `boundary` by a random walk,
+// for skin, wax, marble or milk. The boundary is a dielectric interface
+// and the inside a homogeneous medium of extinction `sigma_t` and
+// scattering `sigma_s` per unit of distance for each channel, scattering
+// by the phase function of Henyey and Greenstein. The boundary must be
+// closed, like a sphere or a closed mesh.
+//
+// Inside, the distance to the next event is sampled for a random channel,
+// as in `ConstantMedium` but with the coefficients of that channel, and
+// the materials of the events weight the paths reaching them by the
+// transmittance of each channel over the chance of getting there, both
+// taken over the segment sampled from `t_min`. The weights multiply along
+// the walk, so media whose free paths differ by much more than a few times
+// between channels converge slowly.
+//
+// `hit` draws random numbers for the distances, so the same ray can stop at
+// a different place inside each time it is traced.
+pub struct Subsurface {
+    boundary: Arc<dyn Hittable + Send + Sync>,
+    dielectric: Arc<Dielectric>,
+    // The boundary hit from outside, where no medium was crossed.
+    outside: Arc<SubsurfaceInterface>,
+    sigma_s: Vec3,
+    sigma_t: Vec3,
+    anisotropy: f32,
+}
+
+impl Subsurface {
+    // Medium looking `color` once light has scattered through it, light
+    // travelling `mean_free_path` on average between events. The
+    // single-scattering albedo giving `color` follows Chiang et al. 2016,
+    // which holds for isotropic scattering.
+    pub fn new(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        refraction_index: f32,
+        roughness: f32,
+        color: Vec3,
+        mean_free_path: Vec3,
+        anisotropy: f32,
+    ) -> Subsurface {
+        let albedo = |a: f32| {
+            let a = a.clamp(0.0, 0.999);
+            1.0 - (4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt()).powi(2)
+        };
+        let sigma_t = Vec3::new(
+            1.0 / mean_free_path.x,
+            1.0 / mean_free_path.y,
+            1.0 / mean_free_path.z,
+        );
+        let sigma_s = Vec3::new(albedo(color.x), albedo(color.y), albedo(color.z)) * sigma_t;
+        Subsurface::from_coefficients(
+            boundary,
+            refraction_index,
+            roughness,
+            sigma_t - sigma_s,
+            sigma_s,
+            anisotropy,
+        )
+    }
+
+    // Medium of absorption `sigma_a` and scattering `sigma_s`, as measured
+    // for real materials.
+    pub fn from_coefficients(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        refraction_index: f32,
+        roughness: f32,
+        sigma_a: Vec3,
+        sigma_s: Vec3,
+        anisotropy: f32,
+    ) -> Subsurface {
+        let dielectric = Arc::new(Dielectric::new_rough(refraction_index, roughness));
+        Subsurface {
+            boundary,
+            outside: Arc::new(SubsurfaceInterface {
+                dielectric: dielectric.clone(),
+                weight: Vec3::new(1.0, 1.0, 1.0),
+            }),
+            dielectric,
+            sigma_s,
+            sigma_t: sigma_a + sigma_s,
+            anisotropy: anisotropy.clamp(-0.99, 0.99),
+        }
+    }
+
+    // Weight of paths crossing `distance` to the boundary: the
+    // transmittance over the chance of sampling no event on the way.
+    fn crossing_weight(&self, distance: f32) -> Vec3 {
+        let tr = transmittance(self.sigma_t, distance);
+        let passed = average(tr);
+        if passed <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        tr / passed
+    }
+
+    // Weight of paths scattering after `distance`: the scattered
+    // transmittance over the density of sampling the event there.
+    fn scattering_weight(&self, distance: f32) -> Vec3 {
+        let tr = transmittance(self.sigma_t, distance);
+        let density = average(self.sigma_t * tr);
+        if density <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        self.sigma_s * tr / density
+    }
+}
+
+impl Hittable for Subsurface {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let mut record = self.boundary.hit(ray, t_min, f32::INFINITY)?;
+        if record.front_face {
+            if record.t > t_max {
+                return None;
+            }
+            record.material = self.outside.clone();
+            return Some(record);
+        }
+        // Rays leaving through a back face start inside, from `t_min` on as
+        // whatever comes before it is skipped.
+        let speed = ray.direction.length();
+        let channel = ((random_double() * 3.0) as usize).min(2);
+        let distance = -(1.0 - random_double()).ln() / self.sigma_t[channel];
+        let t = t_min + distance / speed;
+        if t < record.t {
+            if t > t_max {
+                return None;
+            }
+            let scattering = SubsurfaceScattering {
+                anisotropy: self.anisotropy,
+                weight: self.scattering_weight(distance),
+            };
+            let mut record = HitRecord::new(ray.at(t), Arc::new(scattering), t);
+            // Arbitrary, but a valid frame for the phase function.
+            record.normal = Vec3::new(1.0, 0.0, 0.0);
+            record.front_face = true;
+            return Some(record);
+        }
+        if record.t > t_max {
+            return None;
+        }
+        record.material = Arc::new(SubsurfaceInterface {
+            dielectric: self.dielectric.clone(),
+            weight: self.crossing_weight((record.t - t_min) * speed),
+        });
+        Some(record)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+        self.boundary.bounding_box(time0, time1)
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Subsurface {
+            boundary: self.boundary.clone_dyn(),
+            dielectric: self.dielectric.clone(),
+            outside: self.outside.clone(),
+            sigma_s: self.sigma_s,
+            sigma_t: self.sigma_t,
+            anisotropy: self.anisotropy,
+        })
+    }
+}
+
+// Transmittance of each channel over `distance`.
+fn transmittance(sigma_t: Vec3, distance: f32) -> Vec3 {
+    Vec3::new(
+        (-sigma_t.x * distance).exp(),
+        (-sigma_t.y * distance).exp(),
+        (-sigma_t.z * distance).exp(),
+    )
+}
+
+fn average(v: Vec3) -> f32 {
+    (v.x + v.y + v.z) / 3.0
+}
+
+// The boundary, weighting paths by how they got there.
+struct SubsurfaceInterface {
+    dielectric: Arc<Dielectric>,
+    weight: Vec3,
+}
+
+impl Material for SubsurfaceInterface {
+    fn eval(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        self.dielectric.eval(hit_record, wo, wi) * self.weight
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let mut sample = self.dielectric.sample(hit_record, wo, uc, u)?;
+        sample.weight = sample.weight * self.weight;
+        Some(sample)
+    }
+
+    fn pdf(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        self.dielectric.pdf(hit_record, wo, wi)
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        self.dielectric.lobes()
+    }
+}
+
+// A scattering event inside, weighting paths by how they got there.
+struct SubsurfaceScattering {
+    anisotropy: f32,
+    weight: Vec3,
+}
+
+impl Material for SubsurfaceScattering {
+    fn eval(&self, _hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        henyey_greenstein(wo.dot(wi), self.anisotropy) * self.weight
+    }
+
+    fn sample(
+        &self,
+        _hit_record: &HitRecord,
+        wo: Vec3,
+        _uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let wi = sample_henyey_greenstein(wo, self.anisotropy, u);
+        Some(BsdfSample::new(
+            wi,
+            self.weight,
+            henyey_greenstein(wo.dot(wi), self.anisotropy),
+            LobeFlags::VOLUME,
+        ))
+    }
+
+    fn pdf(&self, _hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        henyey_greenstein(wo.dot(wi), self.anisotropy)
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        LobeFlags::VOLUME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_utils::cylinder::Cylinder;
+    use crate::materials::materials::Lambertian;
+
+    // Light falling on a slab that scatters without absorbing, each channel
+    // with a free path of its own, is all reflected or transmitted.
+    #[test]
+    fn non_absorbing_slab_conserves_energy() {
+        let slab = Arc::new(Cylinder::new(
+            Vec3::new(0.0, 0.0, -0.5),
+            Vec3::new(0.0, 0.0, 0.5),
+            50.0,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        ));
+        let medium = Subsurface::from_coefficients(
+            slab,
+            1.33,
+            0.0,
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(4.0, 3.5, 3.0),
+            0.3,
+        );
+        let paths = 20000;
+        let mut reflected = Vec3::new(0.0, 0.0, 0.0);
+        let mut transmitted = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..paths {
+            // Directions of any length, as camera rays need not be unit.
+            let mut ray = Ray::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.6, 0.0, -1.6), 0.0);
+            let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+            for _ in 0..10_000 {
+                // Events closer than `t_min` to the boundary would leave
+                // without crossing it.
+                let record = match medium.hit(&ray, 0.0001, f32::INFINITY) {
+                    Some(record) => record,
+                    None => {
+                        if ray.direction.z > 0.0 {
+                            reflected = reflected + throughput;
+                        } else {
+                            transmitted = transmitted + throughput;
+                        }
+                        break;
+                    }
+                };
+                let frame = record.shading_frame();
+                let wo = frame.world_to_local(-ray.direction.unit_vector());
+                let u = (random_double(), random_double());
+                let sample = record
+                    .material
+                    .sample(&record, wo, random_double(), u)
+                    .unwrap();
+                throughput = throughput * sample.weight;
+                ray = Ray::new(record.position, 2.0 * frame.local_to_world(sample.wi), 0.0);
+            }
+        }
+        let total = (reflected + transmitted) / paths as f32;
+        for channel in 0..3 {
+            assert!(
+                (total[channel] - 1.0).abs() < 0.03,
+                "{reflected:?} reflected and {transmitted:?} transmitted of {paths}"
+            );
+        }
+    }
+}
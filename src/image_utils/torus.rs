@@ -0,0 +1,183 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::disk::disk_extent;
+use crate::image_utils::hittable::{HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
+use crate::materials::materials::Material;
+use crate::utils::onb::ONB;
+use crate::utils::polynomial::solve_normalized_quartic;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Ring of `minor_radius` swept at `major_radius` around `axis`.
+pub struct Torus {
+    pub center: Vec3,
+    pub axis: Vec3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material: Arc<dyn Material + Send + Sync>,
+    frame: ONB,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vec3,
+        axis: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Torus {
+        Torus {
+            center,
+            axis: axis.unit_vector(),
+            major_radius,
+            minor_radius,
+            material,
+            frame: ONB::build_from_w(axis),
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let origin = self.frame.world_to_local(ray.origin - self.center);
+        let direction = self.frame.world_to_local(ray.direction);
+
+        // Solved with a unit direction and the origin moved next to the torus,
+        // which keeps the quartic coefficients well conditioned.
+        let length = direction.length() as f64;
+        let d = [
+            direction.x as f64 / length,
+            direction.y as f64 / length,
+            direction.z as f64 / length,
+        ];
+        let o = [origin.x as f64, origin.y as f64, origin.z as f64];
+        let shift = -(o[0] * d[0] + o[1] * d[1] + o[2] * d[2]);
+        let o = [
+            o[0] + shift * d[0],
+            o[1] + shift * d[1],
+            o[2] + shift * d[2],
+        ];
+
+        let big_r2 = (self.major_radius as f64).powi(2);
+        let small_r2 = (self.minor_radius as f64).powi(2);
+        let od = o[0] * d[0] + o[1] * d[1] + o[2] * d[2];
+        let oo = o[0] * o[0] + o[1] * o[1] + o[2] * o[2];
+        let k = oo - big_r2 - small_r2;
+        // (|o + s d|^2 - R^2 - r^2)^2 = 4 R^2 (r^2 - (o_z + s d_z)^2)
+        let a3 = 4.0 * od;
+        let a2 = 2.0 * k + 4.0 * od * od + 4.0 * big_r2 * d[2] * d[2];
+        let a1 = 4.0 * k * od + 8.0 * big_r2 * o[2] * d[2];
+        let a0 = k * k - 4.0 * big_r2 * (small_r2 - o[2] * o[2]);
+
+        let t = solve_normalized_quartic(a3, a2, a1, a0)
+            .into_iter()
+            .map(|s| ((s + shift) / length) as f32)
+            .filter(|t| *t >= t_min && *t <= t_max)
+            .fold(None, |closest: Option<f32>, t| match closest {
+                Some(closest) if closest <= t => Some(closest),
+                _ => Some(t),
+            })?;
+
+        let p = origin + t * direction;
+        let s = p.length_squared() + self.major_radius * self.major_radius
+            - self.minor_radius * self.minor_radius;
+        let local_normal = Vec3::new(
+            p.x * (s - 2.0 * self.major_radius * self.major_radius),
+            p.y * (s - 2.0 * self.major_radius * self.major_radius),
+            p.z * s,
+        )
+        .unit_vector();
+        let ring_distance = (p.x * p.x + p.y * p.y).sqrt() - self.major_radius;
+
+        let mut record = HitRecord::new(ray.at(t), self.material.clone(), t);
+        record.u = (p.y.atan2(p.x) + PI) / (2.0 * PI);
+        record.v = (p.z.atan2(ring_distance) + PI) / (2.0 * PI);
+        record.set_face_normal(ray, self.frame.local_to_world(local_normal));
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        let extent = disk_extent(self.axis, self.major_radius)
+            + Vec3::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        Some(AABB::new(self.center - extent, self.center + extent))
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        let emission = self.material.emission_bounds()?;
+        Some(LightBounds::from_emission(
+            self.bounding_box(time0, time1)?,
+            Vec3::new(0.0, 0.0, 1.0),
+            -1.0,
+            4.0 * PI * PI * self.major_radius * self.minor_radius,
+            emission,
+        ))
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Torus::new(
+            self.center,
+            self.axis,
+            self.major_radius,
+            self.minor_radius,
+            self.material.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::Lambertian;
+
+    fn torus() -> Torus {
+        Torus::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hits_outer_side() {
+        let ray = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let record = torus().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 2.5).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn hits_tube_from_above() {
+        // Non unit direction, so t is half the distance travelled.
+        let ray = Ray::new(Vec3::new(2.0, 3.0, 0.0), Vec3::new(0.0, -2.0, 0.0), 0.0);
+        let record = torus().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 1.25).abs() < 1e-4);
+        assert!((record.position - Vec3::new(2.0, 0.5, 0.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn hits_inner_side_after_crossing_tube() {
+        // From inside the tube, the next hit is its far side.
+        let ray = Ray::new(Vec3::new(-2.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let record = torus().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 0.5).abs() < 1e-4);
+        assert!(!record.front_face);
+    }
+
+    #[test]
+    fn misses_through_hole() {
+        let ray = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(torus().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn grazes_top_of_tube() {
+        // Tangent to the top of the ring, a double root of the quartic.
+        let ray = Ray::new(Vec3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let record = torus().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 3.0).abs() < 1e-2);
+    }
+}
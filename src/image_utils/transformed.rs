@@ -0,0 +1,346 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{HitInterval, HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
+use crate::utils::animated_transform::AnimatedTransform;
+use crate::utils::transform::Transform;
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+pub struct Transformed {
+    pub object: Arc<dyn Hittable + Send + Sync>,
+    pub transform: Transform,
+}
+
+impl Transformed {
+    pub fn new(object: Arc<dyn Hittable + Send + Sync>, transform: Transform) -> Transformed {
+        Transformed { object, transform }
+    }
+}
+
+fn record_to_world(mut record: HitRecord, transform: &Transform, ray: &Ray) -> HitRecord {
+    let outward_normal = if record.front_face {
+        record.normal
+    } else {
+        -record.normal
+    };
+    record.position = transform.point(record.position);
+    record.tangent = transform.vector(record.tangent);
+    record.set_face_normal(ray, transform.normal(outward_normal).unit_vector());
+    record
+}
+
+fn hit_transformed(
+    object: &(dyn Hittable + Send + Sync),
+    transform: &Transform,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord> {
+    let local_ray = transform.inverted().ray(ray);
+    let record = object.hit(&local_ray, t_min, t_max)?;
+    Some(record_to_world(record, transform, ray))
+}
+
+fn intervals_transformed(
+    object: &(dyn Hittable + Send + Sync),
+    transform: &Transform,
+    ray: &Ray,
+) -> Vec<HitInterval> {
+    let local_ray = transform.inverted().ray(ray);
+    object
+        .intervals(&local_ray)
+        .into_iter()
+        .map(|interval| {
+            HitInterval::new(
+                record_to_world(interval.enter, transform, ray),
+                record_to_world(interval.exit, transform, ray),
+            )
+        })
+        .collect()
+}
+
+// The object's density over local directions, carried to world directions by
+// the solid angle Jacobian |det A| / |Aω|^3 of the inverse linear map A.
+fn pdf_value_transformed(
+    object: &(dyn Hittable + Send + Sync),
+    transform: &Transform,
+    origin: Vec3,
+    direction: Vec3,
+    time: f32,
+) -> f32 {
+    let inverse = transform.inverted();
+    let local = inverse.vector(direction.unit_vector());
+    let length = local.length();
+    if length == 0.0 {
+        return 0.0;
+    }
+    let determinant = inverse.vector(Vec3::new(1.0, 0.0, 0.0)).dot(
+        inverse
+            .vector(Vec3::new(0.0, 1.0, 0.0))
+            .cross(inverse.vector(Vec3::new(0.0, 0.0, 1.0))),
+    );
+    object.pdf_value(inverse.point(origin), local / length, time) * determinant.abs()
+        / (length * length * length)
+}
+
+// The object's bounds carried into world space. A surface element of normal
+// n grows in area by |det A| |A^-T n| under the linear map A, which is exact
+// for flat emitters; elsewhere the largest stretch of A^-T bounds it, and the
+// normal cone is only kept by maps preserving angles.
+fn light_bounds_transformed(
+    object: &(dyn Hittable + Send + Sync),
+    transform: &Transform,
+    time0: f32,
+    time1: f32,
+) -> Option<LightBounds> {
+    let local = object.light_bounds(time0, time1)?;
+    let columns = [
+        transform.vector(Vec3::new(1.0, 0.0, 0.0)),
+        transform.vector(Vec3::new(0.0, 1.0, 0.0)),
+        transform.vector(Vec3::new(0.0, 0.0, 1.0)),
+    ];
+    let determinant = columns[0].dot(columns[1].cross(columns[2])).abs();
+    let w = transform.normal(local.w);
+    let (cos_theta_o, stretch) = if local.cos_theta_o >= 1.0 {
+        (1.0, w.length())
+    } else {
+        let scale = columns[0].length();
+        let similarity = columns
+            .iter()
+            .all(|c| (c.length() - scale).abs() < 1e-4 * scale)
+            && columns[0].dot(columns[1]).abs() < 1e-4 * scale * scale
+            && columns[1].dot(columns[2]).abs() < 1e-4 * scale * scale
+            && columns[2].dot(columns[0]).abs() < 1e-4 * scale * scale;
+        if similarity {
+            (local.cos_theta_o, 1.0 / scale)
+        } else {
+            (-1.0, largest_stretch(transform))
+        }
+    };
+    Some(LightBounds::new(
+        transform.aabb(local.bounds),
+        w,
+        local.phi * determinant * stretch,
+        cos_theta_o,
+        local.cos_theta_e,
+        local.two_sided,
+    ))
+}
+
+// Largest factor the normal matrix stretches a unit vector by, from power
+// iteration on M^T M.
+fn largest_stretch(transform: &Transform) -> f32 {
+    let normal_matrix = transform.normal_matrix();
+    let transpose = normal_matrix.transpose();
+    let mut v = Vec3::new(1.0, 0.7, 0.3).unit_vector();
+    for _ in 0..32 {
+        let next = transpose.transform_vector(normal_matrix.transform_vector(v));
+        if next.near_zero() {
+            return 0.0;
+        }
+        v = next.unit_vector();
+    }
+    normal_matrix.transform_vector(v).length()
+}
+
+fn random_transformed(
+    object: &(dyn Hittable + Send + Sync),
+    transform: &Transform,
+    origin: Vec3,
+    time: f32,
+) -> Vec3 {
+    let local_origin = transform.inverted().point(origin);
+    transform
+        .vector(object.random(local_origin, time))
+        .unit_vector()
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        hit_transformed(self.object.as_ref(), &self.transform, ray, t_min, t_max)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<HitInterval> {
+        intervals_transformed(self.object.as_ref(), &self.transform, ray)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+        self.object
+            .bounding_box(time0, time1)
+            .map(|output_box| self.transform.aabb(output_box))
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        pdf_value_transformed(
+            self.object.as_ref(),
+            &self.transform,
+            origin,
+            direction,
+            time,
+        )
+    }
+
+    fn random(&self, origin: Vec3, time: f32) -> Vec3 {
+        random_transformed(self.object.as_ref(), &self.transform, origin, time)
+    }
+
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        light_bounds_transformed(self.object.as_ref(), &self.transform, time0, time1)
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Transformed::new(self.object.clone_dyn(), self.transform))
+    }
+}
+
+// Applies the transform of `animation` at each ray's time, so any object can
+// be motion blurred.
+pub struct AnimatedTransformed {
+    pub object: Arc<dyn Hittable + Send + Sync>,
+    pub animation: AnimatedTransform,
+}
+
+impl AnimatedTransformed {
+    pub fn new(
+        object: Arc<dyn Hittable + Send + Sync>,
+        animation: AnimatedTransform,
+    ) -> AnimatedTransformed {
+        AnimatedTransformed { object, animation }
+    }
+}
+
+impl Hittable for AnimatedTransformed {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let transform = self.animation.at(ray.time);
+        hit_transformed(self.object.as_ref(), &transform, ray, t_min, t_max)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<HitInterval> {
+        let transform = self.animation.at(ray.time);
+        intervals_transformed(self.object.as_ref(), &transform, ray)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<AABB> {
+        self.object
+            .bounding_box(time0, time1)
+            .map(|output_box| self.animation.motion_bounds(output_box, time0, time1))
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        let transform = self.animation.at(time);
+        pdf_value_transformed(self.object.as_ref(), &transform, origin, direction, time)
+    }
+
+    fn random(&self, origin: Vec3, time: f32) -> Vec3 {
+        let transform = self.animation.at(time);
+        random_transformed(self.object.as_ref(), &transform, origin, time)
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(AnimatedTransformed::new(
+            self.object.clone_dyn(),
+            self.animation.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_utils::quad::Quad;
+    use crate::image_utils::sphere::Sphere;
+    use crate::materials::materials::{DiffuseLight, Lambertian};
+    use std::f32::consts::PI;
+
+    fn ellipsoid() -> Transformed {
+        let sphere = Sphere::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        );
+        Transformed::new(
+            Arc::new(sphere),
+            Transform::scale(Vec3::new(2.0, 0.5, 1.0))
+                .then(Transform::rotate_y(30.0))
+                .then(Transform::translate(Vec3::new(0.0, 1.0, -4.0))),
+        )
+    }
+
+    #[test]
+    fn pdf_value_integrates_to_one_over_directions() {
+        // Uniform directions have density 1/4π, so the mean of pdf·4π
+        // estimates ∫pdf dω, which is only 1 if the Jacobian is right.
+        let object = ellipsoid();
+        let origin = Vec3::new(0.5, 0.0, 2.0);
+        let samples = 400_000;
+        let total: f32 = (0..samples)
+            .map(|_| object.pdf_value(origin, Vec3::random_unit_vector(), 0.0))
+            .sum();
+        let integral = total * 4.0 * PI / samples as f32;
+        assert!((integral - 1.0).abs() < 0.05, "∫pdf = {}", integral);
+    }
+
+    #[test]
+    fn random_directions_hit_the_object() {
+        let object = ellipsoid();
+        let origin = Vec3::new(0.5, 0.0, 2.0);
+        for _ in 0..1000 {
+            let direction = object.random(origin, 0.0);
+            assert!(object.pdf_value(origin, direction, 0.0) > 0.0);
+        }
+    }
+
+    fn emitter() -> Arc<DiffuseLight> {
+        Arc::new(DiffuseLight::new_one_sided(Vec3::new(2.0, 3.0, 1.0)))
+    }
+
+    #[test]
+    fn light_bounds_follow_a_stretched_quad() {
+        let quad = Quad::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            emitter(),
+        );
+        let transform = Transform::scale(Vec3::new(3.0, 0.5, 7.0))
+            .then(Transform::rotate_x(90.0))
+            .then(Transform::translate(Vec3::new(1.0, 2.0, 3.0)));
+        let transformed = Transformed::new(Arc::new(quad), transform)
+            .light_bounds(0.0, 1.0)
+            .unwrap();
+        // The same quad built in world space.
+        let world = Quad::new(
+            transform.point(Vec3::new(0.0, 0.0, 0.0)),
+            transform.vector(Vec3::new(1.0, 0.0, 0.0)),
+            transform.vector(Vec3::new(0.0, 1.0, 0.0)),
+            emitter(),
+        )
+        .light_bounds(0.0, 1.0)
+        .unwrap();
+        assert!((transformed.phi - world.phi).abs() < 1e-3 * world.phi);
+        assert!((transformed.w - world.w).length() < 1e-4);
+        assert_eq!(transformed.cos_theta_o, 1.0);
+    }
+
+    #[test]
+    fn light_bounds_of_a_scaled_sphere() {
+        let sphere = || Arc::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, emitter()));
+        let local = sphere().light_bounds(0.0, 1.0).unwrap();
+        let rotated = Transformed::new(
+            sphere(),
+            Transform::uniform_scale(2.0).then(Transform::rotate(Vec3::new(1.0, 1.0, 0.0), 40.0)),
+        )
+        .light_bounds(0.0, 1.0)
+        .unwrap();
+        assert!((rotated.phi - 4.0 * local.phi).abs() < 1e-3 * local.phi);
+        assert_eq!(rotated.cos_theta_o, -1.0);
+        // Stretched to an ellipsoid of semi-axes 2, 1 and 1, whose area is
+        // about 21.48 against the sphere's 12.57.
+        let stretched = Transformed::new(sphere(), Transform::scale(Vec3::new(2.0, 1.0, 1.0)))
+            .light_bounds(0.0, 1.0)
+            .unwrap();
+        assert!(stretched.phi >= 21.48 / (4.0 * PI) * local.phi);
+        assert!(stretched.phi <= 2.0 * local.phi * (1.0 + 1e-4));
+    }
+}
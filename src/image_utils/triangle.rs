@@ -0,0 +1,188 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::hittable::{area_pdf, HitRecord, Hittable};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::LightBounds;
+use crate::materials::materials::Material;
+use crate::utils::random_double;
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    // Per-vertex shading normals and texture coordinates, interpolated over
+    // the face when present.
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: Option<[(f32, f32); 3]>,
+    pub material: Arc<dyn Material + Send + Sync>,
+}
+
+impl Triangle {
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, material: Arc<dyn Material + Send + Sync>) -> Triangle {
+        Triangle {
+            vertices: [a, b, c],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    pub fn with_vertex_attributes(
+        vertices: [Vec3; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[(f32, f32); 3]>,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Triangle {
+        Triangle {
+            vertices,
+            normals,
+            uvs,
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    // Möller–Trumbore; without vertex texture coordinates the barycentric
+    // coordinates of `b` and `c` are used instead.
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let [a, b, c] = self.vertices;
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = ray.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let inv_determinant = 1.0 / determinant;
+        let s = ray.origin - a;
+        let u = s.dot(p) * inv_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = ray.direction.dot(q) * inv_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inv_determinant;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let w = 1.0 - u - v;
+        let mut record = HitRecord::new(ray.at(t), self.material.clone(), t);
+        (record.u, record.v) = match self.uvs {
+            Some([uv0, uv1, uv2]) => (
+                w * uv0.0 + u * uv1.0 + v * uv2.0,
+                w * uv0.1 + u * uv1.1 + v * uv2.1,
+            ),
+            None => (u, v),
+        };
+        let normal = match self.normals {
+            Some([n0, n1, n2]) => (w * n0 + u * n1 + v * n2).unit_vector(),
+            None => edge1.cross(edge2).unit_vector(),
+        };
+        record.set_face_normal(ray, normal);
+        Some(record)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<AABB> {
+        let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+        let bbox = AABB::from_points(&self.vertices);
+        Some(AABB::new(bbox.min - pad, bbox.max + pad))
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        match self.hit(&Ray::new(origin, direction, time), 0.001, f32::INFINITY) {
+            Some(record) => {
+                let [a, b, c] = self.vertices;
+                area_pdf(&record, direction, 0.5 * (b - a).cross(c - a).length())
+            }
+            None => 0.0,
+        }
+    }
+
+    // Uniform over the area by folding the unit square onto the triangle.
+    fn random(&self, origin: Vec3, _time: f32) -> Vec3 {
+        let [a, b, c] = self.vertices;
+        let (mut u, mut v) = (random_double(), random_double());
+        if u + v > 1.0 {
+            (u, v) = (1.0 - u, 1.0 - v);
+        }
+        a + u * (b - a) + v * (c - a) - origin
+    }
+
+    // With vertex normals the outward normal is interpolated between them,
+    // so it stays in any cone narrower than a hemisphere holding all three.
+    fn light_bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        let emission = self.material.emission_bounds()?;
+        let [a, b, c] = self.vertices;
+        let n = (b - a).cross(c - a);
+        let (w, cos_theta_o) = match self.normals {
+            Some(normals) => {
+                let w = (normals[0] + normals[1] + normals[2]).unit_vector();
+                let cos_theta_o = normals
+                    .iter()
+                    .fold(1.0f32, |cos, normal| cos.min(normal.unit_vector().dot(w)));
+                if cos_theta_o > 0.0 {
+                    (w, cos_theta_o)
+                } else {
+                    (w, -1.0)
+                }
+            }
+            None => (n.unit_vector(), 1.0),
+        };
+        Some(LightBounds::from_emission(
+            self.bounding_box(time0, time1)?,
+            w,
+            cos_theta_o,
+            0.5 * n.length(),
+            emission,
+        ))
+    }
+
+    fn clone_dyn(&self) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Triangle::with_vertex_attributes(
+            self.vertices,
+            self.normals,
+            self.uvs,
+            self.material.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::Lambertian;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn hits_inside() {
+        let ray = Ray::new(Vec3::new(0.25, 0.25, 2.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let record = triangle().hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((record.t - 2.0).abs() < 1e-4);
+        assert!((record.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-4);
+        assert!(record.front_face);
+        assert!((record.u - 0.25).abs() < 1e-4 && (record.v - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn misses_past_hypotenuse() {
+        let ray = Ray::new(Vec3::new(0.75, 0.75, 2.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(triangle().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn misses_behind_origin() {
+        let ray = Ray::new(Vec3::new(0.25, 0.25, 2.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(triangle().hit(&ray, 0.001, f32::INFINITY).is_none());
+    }
+}
@@ -0,0 +1 @@
+pub mod path_tracer;
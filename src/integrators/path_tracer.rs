@@ -0,0 +1,522 @@
+use crate::image_utils::hittable::{HitRecord, Hittable, HittableList};
+use crate::image_utils::ray::Ray;
+use crate::lights::area::AreaLight;
+use crate::lights::light::Light;
+use crate::lights::light_bvh::LightBVH;
+use crate::lights::linking::LightSet;
+use crate::materials::materials::LobeFlags;
+use crate::utils::onb::ONB;
+use crate::utils::vec3::Vec3;
+use crate::utils::{power_heuristic, random_double};
+use std::sync::Arc;
+
+// Longest number of bounces of each kind a path may take.
+#[derive(Debug, Copy, Clone)]
+pub struct BounceLimits {
+    pub diffuse: u32,
+    pub specular: u32,
+    pub transmission: u32,
+    pub volume: u32,
+}
+
+impl BounceLimits {
+    pub fn new(diffuse: u32, specular: u32, transmission: u32, volume: u32) -> BounceLimits {
+        BounceLimits {
+            diffuse,
+            specular,
+            transmission,
+            volume,
+        }
+    }
+
+    pub fn uniform(limit: u32) -> BounceLimits {
+        BounceLimits::new(limit, limit, limit, limit)
+    }
+
+    fn limit(&self, kind: BounceKind) -> u32 {
+        match kind {
+            BounceKind::Diffuse => self.diffuse,
+            BounceKind::Specular => self.specular,
+            BounceKind::Transmission => self.transmission,
+            BounceKind::Volume => self.volume,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BounceKind {
+    Diffuse,
+    // Glossy and mirror reflections.
+    Specular,
+    Transmission,
+    Volume,
+}
+
+impl BounceKind {
+    fn from_flags(flags: LobeFlags) -> BounceKind {
+        if flags.contains(LobeFlags::VOLUME) {
+            BounceKind::Volume
+        } else if flags.contains(LobeFlags::TRANSMISSION) {
+            BounceKind::Transmission
+        } else if flags.contains(LobeFlags::DIFFUSE) {
+            BounceKind::Diffuse
+        } else {
+            BounceKind::Specular
+        }
+    }
+}
+
+// Unidirectional path tracer following a single path per camera ray in a
+// loop. Paths end on a miss, a non scattering hit, the total or per-kind
+// bounce limit, or by Russian roulette once `russian_roulette_depth`
+// bounces have been made.
+//
+// At every vertex with a non specular lobe, one of `lights` is picked from
+// a light BVH by its estimated contribution there, a direction towards it
+// sampled and a shadow ray cast. Emission found that way and by the next
+// bounce is combined with multiple importance sampling, so emissive objects
+// missing from `lights` are still found by bounces. Delta lights are only
+// found by sampling them.
+//
+// Rays leaving the scene see the lights at infinity, and the constant
+// background passed to `color` unless one of them is an environment.
+//
+// The light BVH bounds moving lights over the shutter interval [`time0`,
+// `time1`] the constructors take, which should be the camera's.
+//
+// Light linking (`ObjectLinking`) is honoured everywhere: emission reaching
+// a vertex counts only if the object there is lit by its light group,
+// shadow rays pass objects casting no shadows for the light, and camera
+// rays pass objects hidden from the camera.
+#[derive(Clone)]
+pub struct PathTracer {
+    pub max_depth: u32,
+    pub limits: BounceLimits,
+    pub russian_roulette_depth: u32,
+    pub lights: Vec<Arc<dyn Light + Send + Sync>>,
+    light_bvh: LightBVH,
+}
+
+impl PathTracer {
+    // Area lights from `lights`, one per object.
+    pub fn new(
+        max_depth: u32,
+        russian_roulette_depth: u32,
+        lights: HittableList,
+        time0: f32,
+        time1: f32,
+    ) -> PathTracer {
+        PathTracer::new_with_limits(
+            max_depth,
+            BounceLimits::uniform(max_depth),
+            russian_roulette_depth,
+            lights,
+            time0,
+            time1,
+        )
+    }
+
+    pub fn new_with_limits(
+        max_depth: u32,
+        limits: BounceLimits,
+        russian_roulette_depth: u32,
+        lights: HittableList,
+        time0: f32,
+        time1: f32,
+    ) -> PathTracer {
+        let lights = AreaLight::from_list(&lights);
+        PathTracer {
+            max_depth,
+            limits,
+            russian_roulette_depth,
+            light_bvh: LightBVH::new(lights.clone(), time0, time1),
+            lights,
+        }
+    }
+
+    pub fn new_with_environment(
+        max_depth: u32,
+        russian_roulette_depth: u32,
+        lights: HittableList,
+        environment: Arc<dyn Light + Send + Sync>,
+        time0: f32,
+        time1: f32,
+    ) -> PathTracer {
+        let mut lights = AreaLight::from_list(&lights);
+        lights.push(environment);
+        PathTracer::new_with_lights(max_depth, russian_roulette_depth, lights, time0, time1)
+    }
+
+    pub fn new_with_lights(
+        max_depth: u32,
+        russian_roulette_depth: u32,
+        lights: Vec<Arc<dyn Light + Send + Sync>>,
+        time0: f32,
+        time1: f32,
+    ) -> PathTracer {
+        PathTracer {
+            max_depth,
+            limits: BounceLimits::uniform(max_depth),
+            russian_roulette_depth,
+            light_bvh: LightBVH::new(lights.clone(), time0, time1),
+            lights,
+        }
+    }
+
+    pub fn color(&self, ray: &Ray, background: Vec3, world: &(dyn Hittable + Send + Sync)) -> Vec3 {
+        let mut radiance = Vec3::new(0.0, 0.0, 0.0);
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+        let mut bounces = [0; 4];
+        let mut ray = Ray::new(ray.origin, ray.direction, ray.time);
+        // Position, normal and BSDF sampling density of the last vertex, if
+        // it could also have reached the next one by light sampling.
+        let mut previous: Option<(Vec3, Vec3, f32)> = None;
+        // Lights the last vertex is lit by.
+        let mut lit_by = LightSet::ALL;
+
+        for depth in 0..self.max_depth {
+            let hit = if depth == 0 {
+                camera_hit(&ray, world)
+            } else {
+                world.hit(&ray, 0.001, f32::INFINITY)
+            };
+            let record = match hit {
+                Some(record) => record,
+                None => {
+                    let mut escaped = self.emission_weight(previous, &ray, true)
+                        * self.infinite_radiance(ray.direction, lit_by);
+                    if !self.lights.iter().any(|light| light.is_environment()) {
+                        escaped = escaped + background;
+                    }
+                    radiance = radiance + throughput * escaped;
+                    break;
+                }
+            };
+            let frame = record.shading_frame();
+            let wo = frame.world_to_local(-ray.direction.unit_vector());
+            if lit_by.contains(record.linking.light_group) {
+                let emitted = record.material.emitted(&record, wo);
+                let weight = self.emission_weight(previous, &ray, false);
+                radiance = radiance + weight * throughput * emitted;
+            }
+            let lobes = record.material.lobes();
+            let light_sampled = !self.light_bvh.is_empty() && lobes.is_non_specular();
+            // Light picking ignores the incident cosine in media.
+            let normal = if lobes.contains(LobeFlags::VOLUME) {
+                Vec3::new(0.0, 0.0, 0.0)
+            } else {
+                record.normal
+            };
+            if light_sampled {
+                let direct = self.direct_lighting(&ray, &record, normal, &frame, wo, world);
+                radiance = radiance + throughput * direct;
+            }
+
+            let sample = match record.material.sample(
+                &record,
+                wo,
+                random_double(),
+                (random_double(), random_double()),
+            ) {
+                Some(sample) => sample,
+                None => break,
+            };
+            previous = if light_sampled && !sample.flags.contains(LobeFlags::SPECULAR) {
+                Some((record.position, normal, sample.pdf))
+            } else {
+                None
+            };
+            lit_by = record.linking.lit_by;
+
+            let kind = BounceKind::from_flags(sample.flags);
+            bounces[kind as usize] += 1;
+            if bounces[kind as usize] > self.limits.limit(kind) {
+                break;
+            }
+            throughput = throughput * sample.weight;
+
+            if depth + 1 >= self.russian_roulette_depth {
+                let survival = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+                if survival <= 0.0 || random_double() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+            ray = Ray::new(record.position, frame.local_to_world(sample.wi), ray.time);
+        }
+        radiance
+    }
+
+    fn infinite_radiance(&self, direction: Vec3, groups: LightSet) -> Vec3 {
+        self.lights
+            .iter()
+            .filter(|light| light.is_infinite() && groups.contains(light.group()))
+            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, light| {
+                sum + light.radiance(direction)
+            })
+    }
+
+    // MIS weight of emission reached by a bounce from `previous`.
+    fn emission_weight(
+        &self,
+        previous: Option<(Vec3, Vec3, f32)>,
+        ray: &Ray,
+        infinite: bool,
+    ) -> f32 {
+        match previous {
+            // Only the lights at infinity or only the others count, since a
+            // direction only ever sees one kind.
+            Some((origin, normal, bsdf_pdf)) => power_heuristic(
+                bsdf_pdf,
+                self.light_bvh
+                    .pdf(origin, normal, ray.direction, ray.time, infinite),
+            ),
+            None => 1.0,
+        }
+    }
+
+    // Light arriving from one sampled light direction, weighted by the BSDF
+    // and, unless the light is a delta light, the MIS weight against BSDF
+    // sampling.
+    fn direct_lighting(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        normal: Vec3,
+        frame: &ONB,
+        wo: Vec3,
+        world: &(dyn Hittable + Send + Sync),
+    ) -> Vec3 {
+        let (index, pmf) = match self
+            .light_bvh
+            .sample(record.position, normal, random_double())
+        {
+            Some(picked) => picked,
+            None => return Vec3::new(0.0, 0.0, 0.0),
+        };
+        let light = &self.lights[index];
+        let sample = match light.sample(
+            record.position,
+            ray.time,
+            (random_double(), random_double()),
+        ) {
+            Some(sample) if sample.pdf > 0.0 => sample,
+            _ => return Vec3::new(0.0, 0.0, 0.0),
+        };
+        let wi = frame.world_to_local(sample.direction);
+        let value = record.material.eval(record, wo, wi);
+        if value.near_zero() {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let shadow_ray = Ray::new(record.position, sample.direction, ray.time);
+        let lit_by = record.linking.lit_by;
+
+        if light.is_delta() {
+            let (passed, _) = shadow_transmittance(&shadow_ray, sample.distance - 0.001, world);
+            if !lit_by.intersection(passed).contains(light.group()) {
+                return Vec3::new(0.0, 0.0, 0.0);
+            }
+            return value * sample.radiance / (pmf * sample.pdf);
+        }
+
+        // Whatever the shadow ray finds, other lights included, is weighted
+        // against every light of that kind.
+        let infinite = light.is_infinite();
+        let (emitted, passed_through) = if infinite {
+            let (passed, passed_through) = shadow_transmittance(&shadow_ray, f32::INFINITY, world);
+            let emitted = self.infinite_radiance(sample.direction, lit_by.intersection(passed));
+            (emitted, passed_through)
+        } else {
+            match shadow_emitter(&shadow_ray, world) {
+                Some((group, emitted, passed, passed_through))
+                    if lit_by.intersection(passed).contains(group) =>
+                {
+                    (emitted, passed_through)
+                }
+                _ => return Vec3::new(0.0, 0.0, 0.0),
+            }
+        };
+        let pdf = self.light_bvh.pdf(
+            record.position,
+            normal,
+            sample.direction,
+            ray.time,
+            infinite,
+        );
+        if pdf <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        // Light shining through objects that cast no shadow for it cannot
+        // be found by BSDF sampling, whose rays stop at them.
+        let weight = if passed_through {
+            1.0
+        } else {
+            power_heuristic(pdf, record.material.pdf(record, wo, wi))
+        };
+        weight * value * emitted / pdf
+    }
+}
+
+// Parameter just past a hit at `t`, relative since a fixed offset vanishes
+// against large t.
+fn past(t: f32) -> f32 {
+    t + (t.abs() * 1e-5).max(0.0001)
+}
+
+// First hit of a camera ray, looking through objects hidden from the
+// camera.
+fn camera_hit(ray: &Ray, world: &(dyn Hittable + Send + Sync)) -> Option<HitRecord> {
+    let mut t_min = 0.001;
+    loop {
+        let record = world.hit(ray, t_min, f32::INFINITY)?;
+        if record.linking.camera_visible {
+            return Some(record);
+        }
+        t_min = past(record.t);
+    }
+}
+
+// Light groups whose light gets along `ray` up to `t_max`, through the
+// objects casting no shadows for them, and whether any object was passed.
+fn shadow_transmittance(
+    ray: &Ray,
+    t_max: f32,
+    world: &(dyn Hittable + Send + Sync),
+) -> (LightSet, bool) {
+    let mut passed = LightSet::ALL;
+    let mut passed_through = false;
+    let mut t_min = 0.001;
+    while let Some(hit) = world.hit(ray, t_min, t_max) {
+        passed = passed.difference(hit.linking.shadows_for);
+        if passed.is_empty() {
+            break;
+        }
+        passed_through = true;
+        t_min = past(hit.t);
+    }
+    (passed, passed_through)
+}
+
+// First emission found along `ray`, as its light group and radiance, with
+// the groups let through by the objects passed on the way as in
+// `shadow_transmittance`.
+fn shadow_emitter(
+    ray: &Ray,
+    world: &(dyn Hittable + Send + Sync),
+) -> Option<(u32, Vec3, LightSet, bool)> {
+    let mut passed = LightSet::ALL;
+    let mut passed_through = false;
+    let mut t_min = 0.001;
+    loop {
+        let hit = world.hit(ray, t_min, f32::INFINITY)?;
+        let wo = hit.shading_frame().world_to_local(-ray.direction);
+        let emitted = hit.material.emitted(&hit, wo);
+        if !emitted.near_zero() {
+            return Some((hit.linking.light_group, emitted, passed, passed_through));
+        }
+        passed = passed.difference(hit.linking.shadows_for);
+        if passed.is_empty() {
+            return None;
+        }
+        passed_through = true;
+        t_min = past(hit.t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_utils::sphere::Sphere;
+    use crate::lights::environment::EnvironmentLight;
+    use crate::materials::materials::{Lambertian, Metal};
+
+    fn mirror() -> HittableList {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Metal::new(Vec3::new(0.8, 0.8, 0.8), 0.0)),
+        )));
+        world
+    }
+
+    #[test]
+    fn specular_limit_ends_paths_at_mirrors() {
+        let world = mirror();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let background = Vec3::new(1.0, 1.0, 1.0);
+
+        let no_specular = PathTracer::new_with_limits(
+            50,
+            BounceLimits::new(50, 0, 50, 50),
+            50,
+            HittableList::new(),
+            0.0,
+            1.0,
+        );
+        assert!(no_specular.color(&ray, background, &world).near_zero());
+
+        // The mirror sends the ray straight back out to the background.
+        let one_specular = PathTracer::new_with_limits(
+            50,
+            BounceLimits::new(0, 1, 0, 0),
+            50,
+            HittableList::new(),
+            0.0,
+            1.0,
+        );
+        let color = one_specular.color(&ray, background, &world);
+        assert!(
+            (color - Vec3::new(0.8, 0.8, 0.8)).length() < 0.02,
+            "{:?}",
+            color
+        );
+    }
+
+    #[test]
+    fn white_furnace() {
+        // Surfaces reflecting everything under a uniform sky of radiance one
+        // look exactly as bright as the sky, however often light bounces
+        // between them on the way.
+        let white = Arc::new(Lambertian::new(Vec3::new(1.0, 1.0, 1.0)));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            white.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            white.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(Vec3::new(1.6, 0.6, 0.0), 0.6, white)));
+        let sky = Arc::new(EnvironmentLight::from_pixels(
+            vec![Vec3::new(1.0, 1.0, 1.0)],
+            1,
+            1,
+            0.0,
+            1.0,
+        ));
+        let integrator =
+            PathTracer::new_with_environment(1000, 1, HittableList::new(), sky, 0.0, 1.0);
+
+        let samples = 40_000;
+        let mut total = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            // Aimed around where the spheres touch the ground and each other.
+            let target = Vec3::new(
+                1.0 + 1.5 * (random_double() - 0.5),
+                0.5 * random_double(),
+                0.0,
+            );
+            let origin = Vec3::new(0.8, 1.0, 6.0);
+            let ray = Ray::new(origin, target - origin, 0.0);
+            total = total + integrator.color(&ray, Vec3::new(0.0, 0.0, 0.0), &world);
+        }
+        let mean = total / samples as f32;
+        for c in 0..3 {
+            assert!((mean[c] - 1.0).abs() < 0.03, "{:?}", mean);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::image_utils::hittable::{Hittable, HittableList};
+use crate::image_utils::ray::Ray;
+use crate::lights::light::{Light, LightBounds, LightSample};
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Rays per side of the grid shot at each face of an object's bounding box
+// to estimate its light bounds.
+const PROBE_GRID: usize = 16;
+
+// Emitting geometry, sampled through its `Hittable::random` and
+// `Hittable::pdf_value` and bounded by its `Hittable::light_bounds`. The
+// same object has to be in the scene to be seen and to cast shadows.
+pub struct AreaLight {
+    pub object: Arc<dyn Hittable + Send + Sync>,
+}
+
+impl AreaLight {
+    pub fn new(object: Arc<dyn Hittable + Send + Sync>) -> AreaLight {
+        AreaLight { object }
+    }
+
+    // One light per object of `list`.
+    pub fn from_list(list: &HittableList) -> Vec<Arc<dyn Light + Send + Sync>> {
+        list.objects
+            .iter()
+            .map(|object| Arc::new(AreaLight::new(object.clone())) as Arc<dyn Light + Send + Sync>)
+            .collect()
+    }
+}
+
+impl Light for AreaLight {
+    // `Hittable::random` draws its own random numbers.
+    fn sample(&self, origin: Vec3, time: f32, _u: (f32, f32)) -> Option<LightSample> {
+        let direction = self.object.random(origin, time);
+        let pdf = self.object.pdf_value(origin, direction, time);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let record = self
+            .object
+            .hit(&Ray::new(origin, direction, time), 0.001, f32::INFINITY)?;
+        let wo = record
+            .shading_frame()
+            .world_to_local(-direction.unit_vector());
+        let radiance = record.material.emitted(&record, wo);
+        Some(LightSample::new(
+            direction.unit_vector(),
+            record.t * direction.length(),
+            radiance,
+            pdf,
+        ))
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        self.object.pdf_value(origin, direction, time)
+    }
+
+    fn bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        self.object.light_bounds(time0, time1)
+    }
+}
+
+// Light bounds of objects that cannot work out their own, estimated from
+// rays shot both ways along each axis through a grid over the bounding box.
+// The area is estimated from the fraction of rays hitting, the power from
+// the brightest emission seen. Flat emitters get a narrow normal cone,
+// anything else shines every way. Moving emitters are probed at times
+// spread over [`time0`, `time1`].
+pub fn probe_bounds<H: Hittable + ?Sized>(
+    object: &H,
+    time0: f32,
+    time1: f32,
+) -> Option<LightBounds> {
+    let bounds = object.bounding_box(time0, time1)?;
+    let diagonal = bounds.diagonal();
+    let mut projected_area = 0.0;
+    let mut max_radiance = 0.0f32;
+    let mut normals = Vec::new();
+    let mut two_sided = false;
+    for axis in 0..3 {
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let face_area = diagonal[a] * diagonal[b];
+        for sign in [1.0, -1.0] {
+            let mut hits = 0;
+            for i in 0..PROBE_GRID {
+                for j in 0..PROBE_GRID {
+                    let mut origin = bounds.min;
+                    origin[a] += (i as f32 + 0.5) / PROBE_GRID as f32 * diagonal[a];
+                    origin[b] += (j as f32 + 0.5) / PROBE_GRID as f32 * diagonal[b];
+                    let mut direction = Vec3::new(0.0, 0.0, 0.0);
+                    direction[axis] = sign;
+                    origin[axis] = if sign > 0.0 {
+                        bounds.min[axis]
+                    } else {
+                        bounds.max[axis]
+                    } - sign;
+                    let time = time0
+                        + (time1 - time0) * (i * PROBE_GRID + j) as f32
+                            / (PROBE_GRID * PROBE_GRID) as f32;
+                    let ray = Ray::new(origin, direction, time);
+                    let record = match object.hit(&ray, 0.001, f32::INFINITY) {
+                        Some(record) => record,
+                        None => continue,
+                    };
+                    hits += 1;
+                    let wo = record.shading_frame().world_to_local(-direction);
+                    let emitted = record.material.emitted(&record, wo);
+                    let radiance = emitted.x.max(emitted.y).max(emitted.z);
+                    if radiance <= 0.0 {
+                        continue;
+                    }
+                    max_radiance = max_radiance.max(radiance);
+                    let outward = if record.front_face {
+                        record.normal
+                    } else {
+                        two_sided = true;
+                        -record.normal
+                    };
+                    normals.push(outward);
+                }
+            }
+            // Both directions see the same projection; average them.
+            projected_area += 0.5 * face_area * hits as f32 / (PROBE_GRID * PROBE_GRID) as f32;
+        }
+    }
+    if normals.is_empty() {
+        return None;
+    }
+
+    let sum = normals
+        .iter()
+        .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &normal| sum + normal);
+    let (w, cos_theta_o) = if sum.length() < 0.5 * normals.len() as f32 {
+        (Vec3::new(0.0, 0.0, 1.0), -1.0)
+    } else {
+        let w = sum.unit_vector();
+        let cos_theta_o = normals
+            .iter()
+            .fold(1.0f32, |cos, normal| cos.min(normal.dot(w)));
+        (w, cos_theta_o)
+    };
+    // For a flat emitter the projections add up to its area; a convex
+    // object's mean projection is a quarter of its surface (Cauchy).
+    let area = if cos_theta_o > 0.0 {
+        projected_area
+    } else {
+        4.0 / 3.0 * projected_area
+    };
+    let sides = if two_sided { 2.0 } else { 1.0 };
+    Some(LightBounds::new(
+        bounds,
+        w,
+        PI * sides * area * max_radiance,
+        cos_theta_o,
+        0.0,
+        two_sided,
+    ))
+}
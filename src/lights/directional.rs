@@ -0,0 +1,84 @@
+use crate::lights::light::{Light, LightSample};
+use crate::utils::onb::ONB;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+
+// Light from infinitely far away arriving from `direction`, with
+// `irradiance` on a surface facing it. A nonzero `angular_radius` in
+// degrees turns it from a delta light into a disk of constant radiance, like
+// the sun, which gives soft shadow edges and is seen by rays leaving the
+// scene.
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub irradiance: Vec3,
+    cos_radius: f32,
+}
+
+impl DirectionalLight {
+    pub fn new_with_radius(
+        direction: Vec3,
+        irradiance: Vec3,
+        angular_radius: f32,
+    ) -> DirectionalLight {
+        DirectionalLight {
+            direction: direction.unit_vector(),
+            irradiance,
+            cos_radius: angular_radius.to_radians().cos(),
+        }
+    }
+
+    fn solid_angle(&self) -> f32 {
+        2.0 * PI * (1.0 - self.cos_radius)
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _origin: Vec3, _time: f32, u: (f32, f32)) -> Option<LightSample> {
+        if self.is_delta() {
+            return Some(LightSample::new(
+                self.direction,
+                f32::INFINITY,
+                self.irradiance,
+                1.0,
+            ));
+        }
+        let cos_theta = 1.0 - u.0 * (1.0 - self.cos_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let direction = ONB::build_from_w(self.direction).local_to_world(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        Some(LightSample::new(
+            direction,
+            f32::INFINITY,
+            self.irradiance / self.solid_angle(),
+            1.0 / self.solid_angle(),
+        ))
+    }
+
+    fn pdf(&self, _origin: Vec3, direction: Vec3, _time: f32) -> f32 {
+        if self.is_delta() || direction.unit_vector().dot(self.direction) < self.cos_radius {
+            0.0
+        } else {
+            1.0 / self.solid_angle()
+        }
+    }
+
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        if self.is_delta() || direction.unit_vector().dot(self.direction) < self.cos_radius {
+            Vec3::new(0.0, 0.0, 0.0)
+        } else {
+            self.irradiance / self.solid_angle()
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        self.cos_radius >= 1.0
+    }
+
+    fn is_infinite(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,166 @@
+use crate::lights::light::{Light, LightSample};
+use crate::utils::distribution::Distribution2D;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+
+// Infinitely far light given by an equirectangular (latitude-longitude)
+// image, laid out like the texture coordinates of `Sphere` with +y up.
+// `rotation` turns the map about the y axis, in degrees. Directions are
+// importance sampled by pixel luminance, weighted by the solid angle each
+// row covers.
+pub struct EnvironmentLight {
+    pub pixels: Vec<Vec3>,
+    pub width: u32,
+    pub height: u32,
+    pub rotation: f32,
+    pub intensity: f32,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentLight {
+    // Any format the image crate reads, `.hdr` and `.exr` keeping their
+    // full range.
+    pub fn new(filename: &str, rotation: f32, intensity: f32) -> Result<EnvironmentLight, String> {
+        let image = image::open(filename)
+            .map_err(|e| format!("cannot read environment map {filename}: {e}"))?
+            .into_rgb32f();
+        let pixels = image
+            .pixels()
+            .map(|p| Vec3::new(p[0], p[1], p[2]))
+            .collect();
+        Ok(EnvironmentLight::from_pixels(
+            pixels,
+            image.width(),
+            image.height(),
+            rotation,
+            intensity,
+        ))
+    }
+
+    // Rows from the top (+y) down.
+    pub fn from_pixels(
+        pixels: Vec<Vec3>,
+        width: u32,
+        height: u32,
+        rotation: f32,
+        intensity: f32,
+    ) -> EnvironmentLight {
+        let mut function = Vec::with_capacity(pixels.len());
+        for j in 0..height {
+            let sin_theta = (PI * (j as f32 + 0.5) / height as f32).sin();
+            for i in 0..width {
+                function.push(pixels[(j * width + i) as usize].luminance() * sin_theta);
+            }
+        }
+        EnvironmentLight {
+            distribution: Distribution2D::new(&function, width as usize, height as usize),
+            pixels,
+            width,
+            height,
+            rotation,
+            intensity,
+        }
+    }
+
+    fn lookup(&self, u: f32, v: f32) -> Vec3 {
+        let i = ((u * self.width as f32) as u32).min(self.width - 1);
+        let j = ((v * self.height as f32) as u32).min(self.height - 1);
+        self.intensity * self.pixels[(j * self.width + i) as usize]
+    }
+
+    // `v` runs from the top row down.
+    fn direction_to_uv(&self, direction: Vec3) -> (f32, f32) {
+        let d = rotate_y(direction.unit_vector(), -self.rotation.to_radians());
+        let phi = d.z.atan2(d.x);
+        let u = 1.0 - (phi + PI) / (2.0 * PI);
+        let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+        (u.clamp(0.0, 1.0), v.clamp(0.0, 1.0))
+    }
+
+    fn uv_to_direction(&self, u: f32, v: f32) -> Vec3 {
+        let phi = PI - 2.0 * PI * u;
+        let theta = v * PI;
+        let d = Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        );
+        rotate_y(d, self.rotation.to_radians())
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample(&self, _origin: Vec3, _time: f32, u: (f32, f32)) -> Option<LightSample> {
+        let ((u, v), map_pdf) = self.distribution.sample_continuous(u);
+        let sin_theta = (v * PI).sin();
+        if map_pdf == 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+        let pdf = map_pdf / (2.0 * PI * PI * sin_theta);
+        Some(LightSample::new(
+            self.uv_to_direction(u, v),
+            f32::INFINITY,
+            self.lookup(u, v),
+            pdf,
+        ))
+    }
+
+    fn pdf(&self, _origin: Vec3, direction: Vec3, _time: f32) -> f32 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf((u, v)) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        let (u, v) = self.direction_to_uv(direction);
+        self.lookup(u, v)
+    }
+
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    fn is_environment(&self) -> bool {
+        true
+    }
+}
+
+fn rotate_y(d: Vec3, angle: f32) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    Vec3::new(cos * d.x + sin * d.z, d.y, -sin * d.x + cos * d.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_map_is_an_error() {
+        assert!(EnvironmentLight::new("no_such_environment.hdr", 0.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn sampled_directions_match_their_pdf_and_radiance() {
+        // A bright pixel in the upper half of an otherwise dim map.
+        let mut pixels = vec![Vec3::new(0.1, 0.1, 0.1); 8 * 4];
+        pixels[8 + 3] = Vec3::new(50.0, 40.0, 30.0);
+        let light = EnvironmentLight::from_pixels(pixels, 8, 4, 30.0, 2.0);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        for i in 0..64 {
+            let u = ((i % 8) as f32 + 0.5) / 8.0;
+            let v = ((i / 8) as f32 + 0.5) / 8.0;
+            let sample = light.sample(origin, 0.0, (u, v)).unwrap();
+            let pdf = light.pdf(origin, sample.direction, 0.0);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-3 * sample.pdf,
+                "{} {}",
+                pdf,
+                sample.pdf
+            );
+            assert!((light.radiance(sample.direction) - sample.radiance).length() < 1e-4);
+        }
+    }
+}
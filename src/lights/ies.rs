@@ -0,0 +1,256 @@
+use crate::utils::vec3::Vec3;
+
+// Goniometric distribution of a light fixture read from an IES LM-63 file,
+// with type C photometry: vertical angles from the nadir (the direction
+// the fixture points at) and horizontal angles around it, in degrees.
+// Files giving only part of the horizontal range (0-90, 0-180 or 90-270)
+// are mirrored as LM-63 describes.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    pub vertical_angles: Vec<f32>,
+    pub horizontal_angles: Vec<f32>,
+    // Candela for each horizontal angle, along the vertical angles.
+    pub candela: Vec<Vec<f32>>,
+    pub max_candela: f32,
+}
+
+impl IesProfile {
+    pub fn from_file(filename: &str) -> Result<IesProfile, String> {
+        let source = std::fs::read_to_string(filename)
+            .map_err(|e| format!("cannot read IES file {filename}: {e}"))?;
+        IesProfile::parse(&source)
+    }
+
+    pub fn parse(source: &str) -> Result<IesProfile, String> {
+        let mut lines = source.lines();
+        let tilt = lines
+            .by_ref()
+            .map(str::trim)
+            .find(|line| line.starts_with("TILT="))
+            .ok_or("IES file without a TILT line")?;
+        // Tilt data kept in another file is not read.
+        if tilt != "TILT=NONE" && tilt != "TILT=INCLUDE" {
+            return Err(format!(
+                "IES {tilt} is not supported, only TILT=NONE and TILT=INCLUDE"
+            ));
+        }
+        let mut tokens = lines.flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','));
+        let mut next = || -> Result<f32, String> {
+            let token = tokens
+                .by_ref()
+                .find(|token| !token.is_empty())
+                .ok_or("truncated IES file")?;
+            token
+                .parse()
+                .map_err(|_| format!("invalid IES number {token}"))
+        };
+        if tilt == "TILT=INCLUDE" {
+            // Lamp to luminaire geometry, then the angles and factors.
+            next()?;
+            let count = next()? as usize;
+            for _ in 0..2 * count {
+                next()?;
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()? as u32;
+        if photometric_type != 1 {
+            return Err(format!(
+                "IES photometric type {photometric_type} is not supported, only type C (1)"
+            ));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err("IES file without angles".to_string());
+        }
+        // Units and luminous opening dimensions.
+        for _ in 0..4 {
+            next()?;
+        }
+        // The second factor is 1 in files newer than LM-63-1995.
+        let ballast_factor = next()? * next()?;
+        let _input_watts = next()?;
+        let vertical_angles = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<f32>, String>>()?;
+        let horizontal_angles = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<f32>, String>>()?;
+        let candela = (0..horizontal_count)
+            .map(|_| {
+                (0..vertical_count)
+                    .map(|_| Ok(next()? * multiplier * ballast_factor))
+                    .collect()
+            })
+            .collect::<Result<Vec<Vec<f32>>, String>>()?;
+        let max_candela = candela.iter().flatten().fold(0.0f32, |a, &b| a.max(b));
+        Ok(IesProfile {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+            max_candela,
+        })
+    }
+
+    // Candela towards `direction`, in the fixture's frame where z is the
+    // nadir and x the horizontal angle zero.
+    pub fn candela(&self, direction: Vec3) -> f32 {
+        let direction = direction.unit_vector();
+        let vertical = direction.z.clamp(-1.0, 1.0).acos().to_degrees();
+        let mut horizontal = direction.y.atan2(direction.x).to_degrees();
+        if horizontal < 0.0 {
+            horizontal += 360.0;
+        }
+        let first = self.horizontal_angles[0];
+        let last = *self.horizontal_angles.last().unwrap();
+        if self.horizontal_angles.len() == 1 {
+            horizontal = self.horizontal_angles[0];
+        } else if last <= 90.0 {
+            horizontal = fold(fold(horizontal, 180.0), 90.0);
+        } else if last <= 180.0 {
+            horizontal = fold(horizontal, 180.0);
+        } else if first >= 90.0 && last <= 270.0 {
+            // Symmetric about the 90-270 plane.
+            horizontal = if horizontal < 90.0 {
+                180.0 - horizontal
+            } else if horizontal > 270.0 {
+                540.0 - horizontal
+            } else {
+                horizontal
+            };
+        }
+
+        let (h0, h1, th) = bracket(&self.horizontal_angles, horizontal);
+        let (v0, v1, tv) = match bracket_within(&self.vertical_angles, vertical) {
+            Some(bracket) => bracket,
+            None => return 0.0,
+        };
+        let row = |h: usize| {
+            let values = &self.candela[h];
+            values[v0] + tv * (values[v1] - values[v0])
+        };
+        row(h0) + th * (row(h1) - row(h0))
+    }
+
+    // Candela towards `direction` relative to the brightest direction.
+    pub fn relative(&self, direction: Vec3) -> f32 {
+        if self.max_candela > 0.0 {
+            self.candela(direction) / self.max_candela
+        } else {
+            0.0
+        }
+    }
+}
+
+// Mirrors `angle` in [0, 2 * half) about `half`.
+fn fold(angle: f32, half: f32) -> f32 {
+    if angle > half {
+        2.0 * half - angle
+    } else {
+        angle
+    }
+}
+
+// Indices around `x` in the sorted `angles` and the weight of the second,
+// clamping outside of them.
+fn bracket(angles: &[f32], x: f32) -> (usize, usize, f32) {
+    if angles.len() == 1 || x <= angles[0] {
+        return (0, 0, 0.0);
+    }
+    let last = angles.len() - 1;
+    if x >= angles[last] {
+        return (last, last, 0.0);
+    }
+    let i = angles.partition_point(|&a| a <= x) - 1;
+    let t = (x - angles[i]) / (angles[i + 1] - angles[i]);
+    (i, i + 1, t)
+}
+
+// As `bracket`, but `None` outside of the angles: no light is given there.
+fn bracket_within(angles: &[f32], x: f32) -> Option<(usize, usize, f32)> {
+    let (first, last) = (angles[0], angles[angles.len() - 1]);
+    if x < first - 1e-3 || x > last + 1e-3 {
+        return None;
+    }
+    Some(bracket(angles, x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Type C, bilaterally symmetric, 1000 cd at the nadir down to nothing at
+    // the horizon, brighter towards the 90 degree plane.
+    const BILATERAL: &str = "IESNA:LM-63-2002
+[TEST] inline
+TILT=NONE
+1 1000 2 3 3 1 1 0 0 0
+1 1 100
+0 45 90
+0 90 180
+500 250 0
+500 300 0
+500 250 0
+";
+
+    fn direction(vertical: f32, horizontal: f32) -> Vec3 {
+        let (v, h) = (vertical.to_radians(), horizontal.to_radians());
+        Vec3::new(v.sin() * h.cos(), v.sin() * h.sin(), v.cos())
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-2,
+            "expected {expected}, found {actual}"
+        );
+    }
+
+    #[test]
+    fn candela_at_known_angles() {
+        let profile = IesProfile::parse(BILATERAL).unwrap();
+        assert_eq!(profile.max_candela, 1000.0);
+        assert_close(profile.candela(direction(0.0, 0.0)), 1000.0);
+        assert_close(profile.candela(direction(45.0, 0.0)), 500.0);
+        assert_close(profile.candela(direction(45.0, 90.0)), 600.0);
+        assert_close(profile.candela(direction(22.5, 0.0)), 750.0);
+        assert_close(profile.candela(direction(45.0, 45.0)), 550.0);
+        assert_close(profile.candela(direction(90.0, 0.0)), 0.0);
+        // Mirrored from 90 degrees.
+        assert_close(profile.candela(direction(45.0, 270.0)), 600.0);
+        // No light above the horizon.
+        assert_close(profile.candela(direction(135.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn candela_mirrored_about_90_270_plane() {
+        let source = "TILT=NONE
+1 1000 1 2 3 1 1 0 0 0
+1 1 100
+0 90
+90 180 270
+100 100
+200 200
+300 300
+";
+        let profile = IesProfile::parse(source).unwrap();
+        assert_close(profile.candela(direction(45.0, 180.0)), 200.0);
+        assert_close(profile.candela(direction(45.0, 0.0)), 200.0);
+        assert_close(profile.candela(direction(45.0, 90.0)), 100.0);
+        assert_close(profile.candela(direction(45.0, 315.0)), 250.0);
+        assert_close(profile.candela(direction(45.0, 45.0)), 150.0);
+    }
+
+    #[test]
+    fn rejects_unsupported_files() {
+        let tilt_file = BILATERAL.replace("TILT=NONE", "TILT=lamp.tlt");
+        assert!(IesProfile::parse(&tilt_file).is_err());
+        let type_b = BILATERAL.replace("1 1000 2 3 3 1", "1 1000 2 3 3 2");
+        assert!(IesProfile::parse(&type_b).is_err());
+        assert!(IesProfile::parse("TILT=NONE\n1 1000 2 3").is_err());
+        assert!(IesProfile::from_file("missing.ies").is_err());
+    }
+}
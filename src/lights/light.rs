@@ -0,0 +1,239 @@
+use crate::image_utils::aabb::AABB;
+use crate::materials::materials::EmissionBounds;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+
+// Direction from a point towards a light, `distance` along it to the light
+// (infinite for lights at infinity), the radiance arriving and the solid
+// angle density of the direction. For delta lights `pdf` is 1 and
+// `radiance` the light arriving from the single possible direction.
+#[derive(Debug, Copy, Clone)]
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f32,
+    pub radiance: Vec3,
+    pub pdf: f32,
+}
+
+impl LightSample {
+    pub fn new(direction: Vec3, distance: f32, radiance: Vec3, pdf: f32) -> LightSample {
+        LightSample {
+            direction,
+            distance,
+            radiance,
+            pdf,
+        }
+    }
+}
+
+// Where a light is and where it shines, for picking lights by their
+// contribution. `phi` bounds the emitted power, the normals of the emitting
+// surfaces lie within `cos_theta_o` of `w` and each of them emits within
+// `cos_theta_e` of itself. Two sided emitters also shine along -`w`.
+#[derive(Debug, Copy, Clone)]
+pub struct LightBounds {
+    pub bounds: AABB,
+    pub w: Vec3,
+    pub phi: f32,
+    pub cos_theta_o: f32,
+    pub cos_theta_e: f32,
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    pub fn new(
+        bounds: AABB,
+        w: Vec3,
+        phi: f32,
+        cos_theta_o: f32,
+        cos_theta_e: f32,
+        two_sided: bool,
+    ) -> LightBounds {
+        LightBounds {
+            bounds,
+            w: w.unit_vector(),
+            phi,
+            cos_theta_o,
+            cos_theta_e,
+            two_sided,
+        }
+    }
+
+    // Surface of `area` inside `bounds` whose outward normals lie within
+    // `cos_theta_o` of `w`, emitting as `emission` allows. Each side
+    // emits at most pi times its radiance bound per unit area.
+    pub fn from_emission(
+        bounds: AABB,
+        w: Vec3,
+        cos_theta_o: f32,
+        area: f32,
+        emission: EmissionBounds,
+    ) -> LightBounds {
+        let sides = if emission.two_sided { 2.0 } else { 1.0 };
+        LightBounds::new(
+            bounds,
+            w,
+            PI * sides * area * emission.radiance,
+            cos_theta_o,
+            emission.cos_theta_e,
+            emission.two_sided,
+        )
+    }
+
+    // Light shining the same way in every direction from within `bounds`.
+    pub fn isotropic(bounds: AABB, phi: f32) -> LightBounds {
+        LightBounds::new(bounds, Vec3::new(0.0, 0.0, 1.0), phi, -1.0, 0.0, false)
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        self.bounds.centroid()
+    }
+
+    pub fn union(&self, other: &LightBounds) -> LightBounds {
+        if self.phi == 0.0 {
+            return *other;
+        }
+        if other.phi == 0.0 {
+            return *self;
+        }
+        let (w, cos_theta_o) = cone_union(self.w, self.cos_theta_o, other.w, other.cos_theta_o);
+        LightBounds {
+            bounds: self.bounds.surrounding_box(other.bounds),
+            w,
+            phi: self.phi + other.phi,
+            cos_theta_o,
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+            two_sided: self.two_sided || other.two_sided,
+        }
+    }
+
+    // Conservative estimate of the light reaching `point` from within the
+    // bounds, following Conty Estevez and Kulla's "Importance Sampling of
+    // Many Lights with Adaptive Tree Splitting" as pbrt-v4 does. The
+    // incident cosine is only accounted for with a nonzero `normal`.
+    pub fn importance(&self, point: Vec3, normal: Vec3) -> f32 {
+        let center = self.centroid();
+        let diagonal = self.bounds.diagonal().length();
+        let distance_squared = (point - center).length_squared().max(0.5 * diagonal);
+        // Angles subtended by the bounds from `point`, as its bounding
+        // sphere.
+        let radius_squared = 0.25 * diagonal * diagonal;
+        let (sin_theta_b, cos_theta_b) = if (point - center).length_squared() < radius_squared {
+            (0.0, -1.0)
+        } else {
+            let sin_squared = radius_squared / (point - center).length_squared();
+            (sin_squared.sqrt(), (1.0 - sin_squared).max(0.0).sqrt())
+        };
+
+        let wi = (point - center).unit_vector();
+        let mut cos_theta_w = self.w.dot(wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        if wi.near_zero() {
+            cos_theta_w = 1.0;
+        }
+        let sin_theta_w = (1.0 - cos_theta_w * cos_theta_w).max(0.0).sqrt();
+        let sin_theta_o = (1.0 - self.cos_theta_o * self.cos_theta_o).max(0.0).sqrt();
+        // Angle from `point` to the closest normal in the cone, then to the
+        // closest direction the bounds allow.
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        let mut importance = self.phi * cos_theta_p / distance_squared;
+        if !normal.near_zero() && !wi.near_zero() {
+            let cos_theta_i = wi.dot(normal).abs();
+            let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+        importance.max(0.0)
+    }
+}
+
+// Cosine of the angle a - b, or one when b is the wider angle.
+fn cos_sub_clamped(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> f32 {
+    if cos_a > cos_b {
+        1.0
+    } else {
+        cos_a * cos_b + sin_a * sin_b
+    }
+}
+
+fn sin_sub_clamped(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> f32 {
+    if cos_a > cos_b {
+        0.0
+    } else {
+        sin_a * cos_b - cos_a * sin_b
+    }
+}
+
+// Smallest cone around both cones given by an axis and the cosine of their
+// spread.
+fn cone_union(wa: Vec3, cos_a: f32, wb: Vec3, cos_b: f32) -> (Vec3, f32) {
+    let theta_a = cos_a.clamp(-1.0, 1.0).acos();
+    let theta_b = cos_b.clamp(-1.0, 1.0).acos();
+    let theta_d = wa.dot(wb).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (wa, cos_a);
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return (wb, cos_b);
+    }
+    let theta_o = 0.5 * (theta_a + theta_d + theta_b);
+    let axis = wa.cross(wb);
+    if theta_o >= PI || axis.near_zero() {
+        return (Vec3::new(0.0, 0.0, 1.0), -1.0);
+    }
+    // Rotate `wa` towards `wb` about their common normal.
+    let theta_r = theta_o - theta_a;
+    let axis = axis.unit_vector();
+    let w = wa * theta_r.cos() + axis.cross(wa) * theta_r.sin();
+    (w.unit_vector(), theta_o.cos())
+}
+
+// Anything sampled directly by the integrator. Emitting geometry is found
+// by rays as usual; lights at infinity give their radiance to rays leaving
+// the scene; delta lights can only be reached by sampling them.
+pub trait Light {
+    // Samples the light as it is at `time`, the time of the shading ray.
+    fn sample(&self, origin: Vec3, time: f32, u: (f32, f32)) -> Option<LightSample>;
+
+    // Density of `sample` choosing `direction` from `origin`, zero for delta
+    // lights.
+    fn pdf(&self, origin: Vec3, direction: Vec3, time: f32) -> f32;
+
+    // Radiance seen by a ray leaving the scene along `direction`.
+    fn radiance(&self, _direction: Vec3) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    fn is_infinite(&self) -> bool {
+        false
+    }
+
+    // Bounds for picking the light among others over the shutter interval
+    // [`time0`, `time1`], `None` for lights at infinity and others picked
+    // apart from the light BVH.
+    fn bounds(&self, _time0: f32, _time1: f32) -> Option<LightBounds> {
+        None
+    }
+
+    // Light group for light linking. Emitting objects take theirs from
+    // the hit instead (`ObjectLinking::light_group`).
+    fn group(&self) -> u32 {
+        0
+    }
+
+    // Whether the light fills the whole sky, replacing the background.
+    fn is_environment(&self) -> bool {
+        false
+    }
+}
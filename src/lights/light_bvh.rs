@@ -0,0 +1,388 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::ray::Ray;
+use crate::lights::light::{Light, LightBounds};
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Buckets along each axis the split cost is evaluated at.
+const SPLIT_BUCKETS: usize = 12;
+
+#[derive(Debug, Copy, Clone)]
+struct LightNode {
+    bounds: LightBounds,
+    // The light of a leaf, or the second child of an interior node, the
+    // first one following the node itself.
+    index: usize,
+    is_leaf: bool,
+}
+
+// Hierarchy of light bounds for picking a light in proportion to its
+// estimated contribution at a point, as in pbrt-v4. Each level down the
+// tree chooses between two children by `LightBounds::importance`, so
+// picking is logarithmic in the number of lights while bright, near and
+// facing lights are preferred.
+//
+// Lights without bounds, those at infinity among them, are picked
+// uniformly apart from the tree, the tree itself counting as one more of
+// them. Lights with no power are never picked. Bounds cover the shutter
+// interval [`time0`, `time1`] the tree is built for.
+#[derive(Clone)]
+pub struct LightBVH {
+    pub lights: Vec<Arc<dyn Light + Send + Sync>>,
+    nodes: Vec<LightNode>,
+    unbounded: Vec<usize>,
+}
+
+impl LightBVH {
+    pub fn new(lights: Vec<Arc<dyn Light + Send + Sync>>, time0: f32, time1: f32) -> LightBVH {
+        let mut unbounded = Vec::new();
+        let mut bounded = Vec::new();
+        for (index, light) in lights.iter().enumerate() {
+            match light.bounds(time0, time1) {
+                Some(bounds) if bounds.phi > 0.0 => bounded.push((index, bounds)),
+                Some(_) => {}
+                None => unbounded.push(index),
+            }
+        }
+        let mut tree = LightBVH {
+            lights,
+            nodes: Vec::new(),
+            unbounded,
+        };
+        if !bounded.is_empty() {
+            tree.build(&mut bounded);
+        }
+        tree
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.unbounded.is_empty()
+    }
+
+    // Chance of picking among the unbounded lights rather than the tree.
+    fn unbounded_probability(&self) -> f32 {
+        let trees = if self.nodes.is_empty() { 0 } else { 1 };
+        self.unbounded.len() as f32 / (self.unbounded.len() + trees) as f32
+    }
+
+    // Picks a light for shading `point`, giving its index and probability.
+    // `normal` is zero where the incident cosine does not matter, as in
+    // media.
+    pub fn sample(&self, point: Vec3, normal: Vec3, u: f32) -> Option<(usize, f32)> {
+        let p_unbounded = self.unbounded_probability();
+        if u < p_unbounded {
+            let count = self.unbounded.len();
+            let index = ((u / p_unbounded * count as f32) as usize).min(count - 1);
+            return Some((self.unbounded[index], p_unbounded / count as f32));
+        }
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut u = ((u - p_unbounded) / (1.0 - p_unbounded)).min(1.0 - f32::EPSILON);
+        let mut pmf = 1.0 - p_unbounded;
+        let mut node = 0;
+        loop {
+            if self.nodes[node].is_leaf {
+                return Some((self.nodes[node].index, pmf));
+            }
+            let children = [node + 1, self.nodes[node].index];
+            let first = self.nodes[children[0]].bounds.importance(point, normal);
+            let second = self.nodes[children[1]].bounds.importance(point, normal);
+            if first == 0.0 && second == 0.0 {
+                return None;
+            }
+            let p_first = first / (first + second);
+            if u < p_first {
+                u = (u / p_first).min(1.0 - f32::EPSILON);
+                pmf *= p_first;
+                node = children[0];
+            } else {
+                u = ((u - p_first) / (1.0 - p_first)).min(1.0 - f32::EPSILON);
+                pmf *= 1.0 - p_first;
+                node = children[1];
+            }
+        }
+    }
+
+    // Density of picking a light with `sample` and then sampling
+    // `direction` from it, summed over the lights at infinity or over the
+    // others. Only the branches of the tree the ray from `point` passes
+    // through are visited, since no other light can be found along it.
+    pub fn pdf(
+        &self,
+        point: Vec3,
+        normal: Vec3,
+        direction: Vec3,
+        time: f32,
+        infinite: bool,
+    ) -> f32 {
+        let mut pdf = 0.0;
+        for &index in &self.unbounded {
+            let light = &self.lights[index];
+            if light.is_infinite() == infinite && !light.is_delta() {
+                pdf += light.pdf(point, direction, time);
+            }
+        }
+        if !self.unbounded.is_empty() {
+            pdf *= self.unbounded_probability() / self.unbounded.len() as f32;
+        }
+        if infinite || self.nodes.is_empty() {
+            return pdf;
+        }
+
+        let ray = Ray::new(point, direction, time);
+        let mut stack = vec![(0, 1.0 - self.unbounded_probability())];
+        while let Some((node, pmf)) = stack.pop() {
+            if !self.nodes[node].bounds.bounds.hit(&ray, 0.0, f32::INFINITY) {
+                continue;
+            }
+            if self.nodes[node].is_leaf {
+                let light = &self.lights[self.nodes[node].index];
+                if !light.is_delta() {
+                    pdf += pmf * light.pdf(point, direction, time);
+                }
+                continue;
+            }
+            let children = [node + 1, self.nodes[node].index];
+            let first = self.nodes[children[0]].bounds.importance(point, normal);
+            let second = self.nodes[children[1]].bounds.importance(point, normal);
+            if first == 0.0 && second == 0.0 {
+                continue;
+            }
+            stack.push((children[0], pmf * first / (first + second)));
+            stack.push((children[1], pmf * second / (first + second)));
+        }
+        pdf
+    }
+
+    // Adds the subtree over `lights` at the end of `nodes`, splitting where
+    // the surface area orientation heuristic is lowest.
+    fn build(&mut self, lights: &mut [(usize, LightBounds)]) -> usize {
+        let node = self.nodes.len();
+        if lights.len() == 1 {
+            let (index, bounds) = lights[0];
+            self.nodes.push(LightNode {
+                bounds,
+                index,
+                is_leaf: true,
+            });
+            return node;
+        }
+
+        let bounds = lights
+            .iter()
+            .skip(1)
+            .fold(lights[0].1, |bounds, (_, other)| bounds.union(other));
+        let mid = split(lights, &bounds).unwrap_or_else(|| median_split(lights));
+        self.nodes.push(LightNode {
+            bounds,
+            index: 0,
+            is_leaf: false,
+        });
+        let (first, second) = lights.split_at_mut(mid);
+        self.build(first);
+        let second = self.build(second);
+        self.nodes[node].index = second;
+        node
+    }
+}
+
+// Reorders `lights` around the cheapest split of their centroids into
+// buckets, giving the size of the first half, or `None` when the centroids
+// cannot be told apart.
+fn split(lights: &mut [(usize, LightBounds)], bounds: &LightBounds) -> Option<usize> {
+    let centroids = lights
+        .iter()
+        .map(|(_, bounds)| bounds.centroid())
+        .collect::<Vec<Vec3>>();
+    let extent = AABB::from_points(&centroids);
+    let (min, max) = (extent.min, extent.max);
+
+    let bucket = |centroid: Vec3, axis: usize| {
+        let offset = (centroid[axis] - min[axis]) / (max[axis] - min[axis]);
+        ((offset * SPLIT_BUCKETS as f32) as usize).min(SPLIT_BUCKETS - 1)
+    };
+    let mut best: Option<(f32, usize, usize)> = None;
+    for axis in 0..3 {
+        if max[axis] <= min[axis] {
+            continue;
+        }
+        let mut buckets: [Option<LightBounds>; SPLIT_BUCKETS] = [None; SPLIT_BUCKETS];
+        for (i, (_, light)) in lights.iter().enumerate() {
+            let b = &mut buckets[bucket(centroids[i], axis)];
+            *b = Some(b.map_or(*light, |bounds| bounds.union(light)));
+        }
+        for boundary in 1..SPLIT_BUCKETS {
+            let union = |buckets: &[Option<LightBounds>]| {
+                buckets
+                    .iter()
+                    .flatten()
+                    .fold(None, |sum: Option<LightBounds>, b| {
+                        Some(sum.map_or(*b, |sum| sum.union(b)))
+                    })
+            };
+            let (below, above) = match (union(&buckets[..boundary]), union(&buckets[boundary..])) {
+                (Some(below), Some(above)) => (below, above),
+                _ => continue,
+            };
+            let cost = split_cost(&below, bounds, axis) + split_cost(&above, bounds, axis);
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, boundary));
+            }
+        }
+    }
+
+    let (cost, axis, boundary) = best?;
+    if cost <= 0.0 || !cost.is_finite() {
+        return None;
+    }
+    let mut keyed = lights
+        .iter()
+        .zip(&centroids)
+        .map(|(light, &centroid)| (bucket(centroid, axis) < boundary, *light))
+        .collect::<Vec<_>>();
+    keyed.sort_by_key(|(below, _)| !below);
+    let mid = keyed.iter().filter(|(below, _)| *below).count();
+    for (slot, (_, light)) in lights.iter_mut().zip(keyed) {
+        *slot = light;
+    }
+    Some(mid)
+}
+
+// Sorts `lights` along the widest spread of their centroids, giving half
+// of them.
+fn median_split(lights: &mut [(usize, LightBounds)]) -> usize {
+    let centroids = lights
+        .iter()
+        .map(|(_, bounds)| bounds.centroid())
+        .collect::<Vec<Vec3>>();
+    let spread = AABB::from_points(&centroids).diagonal();
+    let axis = (0..3)
+        .max_by(|&a, &b| spread[a].total_cmp(&spread[b]))
+        .unwrap();
+    lights.sort_by(|(_, a), (_, b)| a.centroid()[axis].total_cmp(&b.centroid()[axis]));
+    lights.len() / 2
+}
+
+// Cost of a child with `bounds` under a node spanning `parent`, its power
+// times the solid angle its emission covers and the surface of its box,
+// the latter stretched along `axis` for thin nodes.
+fn split_cost(bounds: &LightBounds, parent: &LightBounds, axis: usize) -> f32 {
+    let theta_o = bounds.cos_theta_o.clamp(-1.0, 1.0).acos();
+    let theta_e = bounds.cos_theta_e.clamp(-1.0, 1.0).acos();
+    let theta_w = (theta_o + theta_e).min(PI);
+    let sin_theta_o = (1.0 - bounds.cos_theta_o * bounds.cos_theta_o)
+        .max(0.0)
+        .sqrt();
+    let solid_angle = 2.0 * PI * (1.0 - bounds.cos_theta_o)
+        + PI / 2.0
+            * (2.0 * theta_w * sin_theta_o
+                - (theta_o - 2.0 * theta_w).cos()
+                - 2.0 * theta_o * sin_theta_o
+                + bounds.cos_theta_o);
+    let diagonal = parent.bounds.diagonal();
+    let stretch = if diagonal[axis] > 0.0 {
+        diagonal.x.max(diagonal.y).max(diagonal.z) / diagonal[axis]
+    } else {
+        1.0
+    };
+    bounds.phi * solid_angle * stretch * bounds.bounds.surface_area()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_utils::quad::Quad;
+    use crate::image_utils::sphere::Sphere;
+    use crate::lights::area::AreaLight;
+    use crate::lights::point::PointLight;
+    use crate::materials::materials::DiffuseLight;
+
+    fn lights() -> Vec<Arc<dyn Light + Send + Sync>> {
+        let mut lights: Vec<Arc<dyn Light + Send + Sync>> = Vec::new();
+        for i in 0..6 {
+            let x = 3.0 * i as f32 - 7.5;
+            let brightness = 1.0 + i as f32;
+            lights.push(Arc::new(AreaLight::new(Arc::new(Quad::new(
+                Vec3::new(x, 4.0, -1.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 1.0 + 0.5 * i as f32),
+                Arc::new(DiffuseLight::new(Vec3::new(
+                    brightness, brightness, brightness,
+                ))),
+            )))));
+        }
+        lights.push(Arc::new(AreaLight::new(Arc::new(Sphere::new(
+            Vec3::new(2.0, 1.0, 5.0),
+            0.5,
+            Arc::new(DiffuseLight::new(Vec3::new(20.0, 10.0, 5.0))),
+        )))));
+        lights.push(Arc::new(PointLight::new(
+            Vec3::new(-4.0, 2.0, 3.0),
+            Vec3::new(5.0, 5.0, 5.0),
+        )));
+        lights
+    }
+
+    // Probability of `sample` picking each light, and the fraction of
+    // stratified numbers that picked it.
+    fn pick_rates(tree: &LightBVH, point: Vec3, normal: Vec3) -> Vec<(f32, f32)> {
+        let samples = 100_000;
+        let mut rates = vec![(0.0, 0.0); tree.lights.len()];
+        for i in 0..samples {
+            let u = (i as f32 + 0.5) / samples as f32;
+            let (index, pmf) = tree.sample(point, normal, u).unwrap();
+            if rates[index].0 > 0.0 {
+                assert!((rates[index].0 - pmf).abs() < 1e-5);
+            }
+            rates[index].0 = pmf;
+            rates[index].1 += 1.0 / samples as f32;
+        }
+        rates
+    }
+
+    #[test]
+    fn pmf_sums_to_one_and_matches_picking() {
+        let tree = LightBVH::new(lights(), 0.0, 1.0);
+        let rates = pick_rates(&tree, Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let total: f32 = rates.iter().map(|(pmf, _)| pmf).sum();
+        assert!((total - 1.0).abs() < 1e-4, "{}", total);
+        for (pmf, rate) in rates {
+            assert!((pmf - rate).abs() < 1e-3, "{} {}", pmf, rate);
+        }
+    }
+
+    #[test]
+    fn pdf_matches_sampling() {
+        let tree = LightBVH::new(lights(), 0.0, 1.0);
+        let (point, normal) = (Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rates = pick_rates(&tree, point, normal);
+        for i in 0..200 {
+            let u = (i as f32 + 0.5) / 200.0;
+            let (index, _) = tree.sample(point, normal, u).unwrap();
+            let light = &tree.lights[index];
+            if light.is_delta() {
+                continue;
+            }
+            let sample = light.sample(point, 0.0, (0.5, 0.5)).unwrap();
+            // The emitters do not overlap, but a direction may still
+            // reach several of them.
+            let expected: f32 = tree
+                .lights
+                .iter()
+                .zip(&rates)
+                .filter(|(light, _)| !light.is_delta())
+                .map(|(light, (pmf, _))| pmf * light.pdf(point, sample.direction, 0.0))
+                .sum();
+            let pdf = tree.pdf(point, normal, sample.direction, 0.0, false);
+            assert!(
+                (pdf - expected).abs() < 1e-3 * expected,
+                "{} {}",
+                pdf,
+                expected
+            );
+        }
+    }
+}
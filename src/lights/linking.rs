@@ -0,0 +1,113 @@
+use crate::lights::light::{Light, LightBounds, LightSample};
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+// Set of light groups, numbered from 0 to 63. Lights are in group 0 unless
+// put in another one, emitting objects through their `ObjectLinking` and
+// other lights with `GroupedLight`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LightSet(u64);
+
+impl LightSet {
+    pub const ALL: LightSet = LightSet(u64::MAX);
+    pub const NONE: LightSet = LightSet(0);
+
+    pub fn only(groups: &[u32]) -> LightSet {
+        LightSet(groups.iter().fold(0, |bits, &group| bits | bit(group)))
+    }
+
+    pub fn except(groups: &[u32]) -> LightSet {
+        LightSet(!LightSet::only(groups).0)
+    }
+
+    pub fn contains(self, group: u32) -> bool {
+        self.0 & bit(group) != 0
+    }
+
+    pub fn intersection(self, other: LightSet) -> LightSet {
+        LightSet(self.0 & other.0)
+    }
+
+    pub fn difference(self, other: LightSet) -> LightSet {
+        LightSet(self.0 & !other.0)
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+fn bit(group: u32) -> u64 {
+    assert!(group < 64, "light groups go from 0 to 63");
+    1 << group
+}
+
+// How an object takes part in lighting: the group its own emission is in,
+// the lights illuminating it, the lights it casts shadows for and whether
+// camera rays see it. Objects hidden from the camera still show in
+// reflections and cast shadows.
+#[derive(Debug, Copy, Clone)]
+pub struct ObjectLinking {
+    pub light_group: u32,
+    pub lit_by: LightSet,
+    pub shadows_for: LightSet,
+    pub camera_visible: bool,
+}
+
+impl ObjectLinking {
+    // Lit by every light, shadowing every light and seen by the camera.
+    pub fn new() -> ObjectLinking {
+        ObjectLinking {
+            light_group: 0,
+            lit_by: LightSet::ALL,
+            shadows_for: LightSet::ALL,
+            camera_visible: true,
+        }
+    }
+}
+
+// Puts `light` in another light group than 0.
+pub struct GroupedLight {
+    pub light: Arc<dyn Light + Send + Sync>,
+    pub group: u32,
+}
+
+impl GroupedLight {
+    pub fn new(light: Arc<dyn Light + Send + Sync>, group: u32) -> GroupedLight {
+        GroupedLight { light, group }
+    }
+}
+
+impl Light for GroupedLight {
+    fn sample(&self, origin: Vec3, time: f32, u: (f32, f32)) -> Option<LightSample> {
+        self.light.sample(origin, time, u)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        self.light.pdf(origin, direction, time)
+    }
+
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        self.light.radiance(direction)
+    }
+
+    fn is_delta(&self) -> bool {
+        self.light.is_delta()
+    }
+
+    fn is_infinite(&self) -> bool {
+        self.light.is_infinite()
+    }
+
+    fn is_environment(&self) -> bool {
+        self.light.is_environment()
+    }
+
+    fn bounds(&self, time0: f32, time1: f32) -> Option<LightBounds> {
+        self.light.bounds(time0, time1)
+    }
+
+    fn group(&self) -> u32 {
+        self.group
+    }
+}
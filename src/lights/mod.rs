@@ -0,0 +1,9 @@
+pub mod area;
+pub mod directional;
+pub mod environment;
+pub mod ies;
+pub mod light;
+pub mod light_bvh;
+pub mod linking;
+pub mod point;
+pub mod sky;
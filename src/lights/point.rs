@@ -0,0 +1,190 @@
+use crate::image_utils::aabb::AABB;
+use crate::lights::ies::IesProfile;
+use crate::lights::light::{Light, LightBounds, LightSample};
+use crate::utils::onb::ONB;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Light leaving a single point, `intensity` being the radiant intensity so
+// that the irradiance at distance d is intensity / d². With a profile, the
+// intensity is that of its brightest direction, the profile's nadir along
+// `frame.w`.
+pub struct PointLight {
+    pub position: Vec3,
+    pub intensity: Vec3,
+    pub profile: Option<Arc<IesProfile>>,
+    pub frame: ONB,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Vec3) -> PointLight {
+        PointLight {
+            position,
+            intensity,
+            profile: None,
+            frame: ONB::build_from_w(Vec3::new(0.0, -1.0, 0.0)),
+        }
+    }
+
+    pub fn new_with_profile(
+        position: Vec3,
+        intensity: Vec3,
+        profile: Arc<IesProfile>,
+        nadir: Vec3,
+    ) -> PointLight {
+        PointLight {
+            position,
+            intensity,
+            profile: Some(profile),
+            frame: ONB::build_from_w(nadir),
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, origin: Vec3, _time: f32, _u: (f32, f32)) -> Option<LightSample> {
+        let to_light = self.position - origin;
+        let distance_squared = to_light.length_squared();
+        if distance_squared == 0.0 {
+            return None;
+        }
+        let direction = to_light.unit_vector();
+        let intensity = match &self.profile {
+            Some(profile) => {
+                profile.relative(self.frame.world_to_local(-direction)) * self.intensity
+            }
+            None => self.intensity,
+        };
+        Some(LightSample::new(
+            direction,
+            distance_squared.sqrt(),
+            intensity / distance_squared,
+            1.0,
+        ))
+    }
+
+    fn pdf(&self, _origin: Vec3, _direction: Vec3, _time: f32) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self, _time0: f32, _time1: f32) -> Option<LightBounds> {
+        let i = &self.intensity;
+        Some(LightBounds::isotropic(
+            AABB::new(self.position, self.position),
+            4.0 * PI * i.x.max(i.y).max(i.z),
+        ))
+    }
+}
+
+// Point light shining down a cone around `direction`. The intensity is full
+// within `falloff_start` degrees of the axis and goes smoothly to zero at
+// `total_width` degrees. A profile further shapes the beam, with its nadir
+// along the axis.
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub intensity: Vec3,
+    pub cos_total_width: f32,
+    pub cos_falloff_start: f32,
+    pub profile: Option<Arc<IesProfile>>,
+    pub frame: ONB,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vec3,
+        look_at: Vec3,
+        intensity: Vec3,
+        total_width: f32,
+        falloff_start: f32,
+    ) -> SpotLight {
+        let direction = (look_at - position).unit_vector();
+        SpotLight {
+            position,
+            direction,
+            intensity,
+            cos_total_width: total_width.to_radians().cos(),
+            cos_falloff_start: falloff_start.min(total_width).to_radians().cos(),
+            profile: None,
+            frame: ONB::build_from_w(direction),
+        }
+    }
+
+    pub fn new_with_profile(
+        position: Vec3,
+        look_at: Vec3,
+        intensity: Vec3,
+        total_width: f32,
+        falloff_start: f32,
+        profile: Arc<IesProfile>,
+    ) -> SpotLight {
+        SpotLight {
+            profile: Some(profile),
+            ..SpotLight::new(position, look_at, intensity, total_width, falloff_start)
+        }
+    }
+
+    fn falloff(&self, cos_theta: f32) -> f32 {
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_total_width {
+            return 0.0;
+        }
+        let t =
+            (cos_theta - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, origin: Vec3, _time: f32, _u: (f32, f32)) -> Option<LightSample> {
+        let to_light = self.position - origin;
+        let distance_squared = to_light.length_squared();
+        if distance_squared == 0.0 {
+            return None;
+        }
+        let direction = to_light.unit_vector();
+        let mut falloff = self.falloff(-direction.dot(self.direction));
+        if let Some(profile) = &self.profile {
+            falloff *= profile.relative(self.frame.world_to_local(-direction));
+        }
+        if falloff == 0.0 {
+            return None;
+        }
+        Some(LightSample::new(
+            direction,
+            distance_squared.sqrt(),
+            falloff * self.intensity / distance_squared,
+            1.0,
+        ))
+    }
+
+    fn pdf(&self, _origin: Vec3, _direction: Vec3, _time: f32) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    // The power is that of a point light of the same intensity, so that
+    // spots are not picked less often for shining a narrower beam.
+    fn bounds(&self, _time0: f32, _time1: f32) -> Option<LightBounds> {
+        let i = &self.intensity;
+        let cos_theta_e = (self.cos_total_width.acos() - self.cos_falloff_start.acos()).cos();
+        Some(LightBounds::new(
+            AABB::new(self.position, self.position),
+            self.direction,
+            4.0 * PI * i.x.max(i.y).max(i.z),
+            self.cos_falloff_start,
+            cos_theta_e,
+            false,
+        ))
+    }
+}
@@ -0,0 +1,273 @@
+use crate::lights::environment::EnvironmentLight;
+use crate::lights::light::{Light, LightSample};
+use crate::utils::color::xyz_to_rgb;
+use crate::utils::onb::ONB;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+
+// Angular radius of the sun seen from the ground, in degrees.
+const SUN_ANGULAR_RADIUS: f32 = 0.2665;
+// Illuminance of the sun outside the atmosphere, in klx like the sky.
+const SUN_ILLUMINANCE: f32 = 128.0;
+// Brings the sky, in kcd/m², and the sun to values of about one.
+const SKY_SCALE: f32 = 0.1;
+// Resolution of the table the sky is importance sampled with.
+const TABLE_WIDTH: u32 = 128;
+const TABLE_HEIGHT: u32 = 64;
+
+// Clear sky of Preetham, Shirley and Smits, "A Practical Analytic Model
+// for Daylight", with +y up. The sun is a disk whose color comes from the
+// Rayleigh and aerosol extinction of the paper's appendix. Below the horizon
+// is a diffuse ground of `ground_albedo` lit by the sky and the sun.
+//
+// Elevation and azimuth are in degrees, the azimuth going from +z towards
+// +x. The sun and the sky are sampled separately, in proportion to their
+// power, the sky through a tabulated copy of itself.
+pub struct SkyLight {
+    pub sun_direction: Vec3,
+    pub intensity: f32,
+    sky: Preetham,
+    sun_radiance: Vec3,
+    cos_sun_radius: f32,
+    ground_radiance: Vec3,
+    table: EnvironmentLight,
+    sun_probability: f32,
+}
+
+// Sky alone, as luminance and chromaticity at the zenith divided by the
+// Perez function there, with the Perez coefficients of each.
+struct Preetham {
+    sun_direction: Vec3,
+    zenith: [f32; 3],
+    perez: [[f32; 5]; 3],
+}
+
+impl SkyLight {
+    pub fn new(
+        sun_elevation: f32,
+        sun_azimuth: f32,
+        turbidity: f32,
+        ground_albedo: Vec3,
+        intensity: f32,
+    ) -> SkyLight {
+        let (elevation, azimuth) = (sun_elevation.to_radians(), sun_azimuth.to_radians());
+        let sun_direction = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            elevation.cos() * azimuth.cos(),
+        );
+        // The model only holds for the sun above the horizon.
+        let theta_s = (PI / 2.0 - elevation).clamp(0.0, PI / 2.0);
+        let t = turbidity;
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (t2, s1, s2, s3) = (
+            t * t,
+            theta_s,
+            theta_s * theta_s,
+            theta_s * theta_s * theta_s,
+        );
+        let zenith_x = t2 * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s1)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s1 + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s1 + 0.25886);
+        let zenith_y = t2 * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s1)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s1 + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s1 + 0.26688);
+        let zenith = [zenith_luminance.max(0.0), zenith_x, zenith_y];
+        let sky = Preetham {
+            sun_direction,
+            zenith: [0, 1, 2].map(|i| zenith[i] / perez_function(&perez[i], 0.0, theta_s)),
+            perez,
+        };
+
+        let cos_sun_radius = SUN_ANGULAR_RADIUS.to_radians().cos();
+        let sun_solid_angle = 2.0 * PI * (1.0 - cos_sun_radius);
+        let sun_radiance = if sun_elevation > 0.0 {
+            SKY_SCALE * SUN_ILLUMINANCE / sun_solid_angle * sun_transmittance(theta_s, t)
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        };
+
+        // Rows of the table from the top down, the ground filled in once the
+        // light falling on it is known.
+        let mut pixels = Vec::with_capacity((TABLE_WIDTH * TABLE_HEIGHT) as usize);
+        let mut sky_irradiance = Vec3::new(0.0, 0.0, 0.0);
+        for j in 0..TABLE_HEIGHT {
+            let theta = PI * (j as f32 + 0.5) / TABLE_HEIGHT as f32;
+            let solid_angle = pixel_solid_angle(theta);
+            for i in 0..TABLE_WIDTH {
+                let phi = PI - 2.0 * PI * (i as f32 + 0.5) / TABLE_WIDTH as f32;
+                let direction = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let radiance = if direction.y >= 0.0 {
+                    sky.radiance(direction)
+                } else {
+                    Vec3::new(0.0, 0.0, 0.0)
+                };
+                sky_irradiance = sky_irradiance + radiance * direction.y.max(0.0) * solid_angle;
+                pixels.push(radiance);
+            }
+        }
+        let sun_irradiance = sun_radiance * sun_solid_angle * sun_direction.y.max(0.0);
+        let ground_radiance = ground_albedo * (sky_irradiance + sun_irradiance) / PI;
+        let mut sky_power = 0.0;
+        for (index, pixel) in pixels.iter_mut().enumerate() {
+            let theta = PI * ((index as u32 / TABLE_WIDTH) as f32 + 0.5) / TABLE_HEIGHT as f32;
+            if theta > PI / 2.0 {
+                *pixel = ground_radiance;
+            }
+            sky_power += pixel.luminance() * pixel_solid_angle(theta);
+        }
+        let sun_power = sun_radiance.luminance() * sun_solid_angle;
+
+        SkyLight {
+            sun_direction,
+            intensity,
+            sky,
+            sun_radiance,
+            cos_sun_radius,
+            ground_radiance,
+            table: EnvironmentLight::from_pixels(pixels, TABLE_WIDTH, TABLE_HEIGHT, 0.0, 1.0),
+            sun_probability: sun_power / (sun_power + sky_power),
+        }
+    }
+}
+
+impl Preetham {
+    // Directions below the horizon see the horizon.
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        let theta = direction.y.max(0.0).acos();
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * perez_function(&self.perez[i], theta, gamma));
+        SKY_SCALE * xyy_to_rgb(x, y, luminance)
+    }
+}
+
+impl Light for SkyLight {
+    fn sample(&self, origin: Vec3, time: f32, u: (f32, f32)) -> Option<LightSample> {
+        let direction = if u.0 < self.sun_probability {
+            // Uniformly within the cone of the sun.
+            let u0 = u.0 / self.sun_probability;
+            let cos_theta = 1.0 - u0 * (1.0 - self.cos_sun_radius);
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * u.1;
+            ONB::build_from_w(self.sun_direction).local_to_world(Vec3::new(
+                sin_theta * phi.cos(),
+                sin_theta * phi.sin(),
+                cos_theta,
+            ))
+        } else {
+            let u0 = (u.0 - self.sun_probability) / (1.0 - self.sun_probability);
+            self.table
+                .sample(origin, time, (u0.min(1.0 - f32::EPSILON), u.1))?
+                .direction
+        };
+        Some(LightSample::new(
+            direction,
+            f32::INFINITY,
+            self.radiance(direction),
+            self.pdf(origin, direction, time),
+        ))
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3, time: f32) -> f32 {
+        let direction = direction.unit_vector();
+        let sun_pdf = if direction.dot(self.sun_direction) >= self.cos_sun_radius {
+            1.0 / (2.0 * PI * (1.0 - self.cos_sun_radius))
+        } else {
+            0.0
+        };
+        self.sun_probability * sun_pdf
+            + (1.0 - self.sun_probability) * self.table.pdf(origin, direction, time)
+    }
+
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        let direction = direction.unit_vector();
+        if direction.y < 0.0 {
+            return self.intensity * self.ground_radiance;
+        }
+        let mut radiance = self.sky.radiance(direction);
+        if direction.dot(self.sun_direction) >= self.cos_sun_radius {
+            radiance = radiance + self.sun_radiance;
+        }
+        self.intensity * radiance
+    }
+
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    fn is_environment(&self) -> bool {
+        true
+    }
+}
+
+// Solid angle of a table pixel in the row at `theta`.
+fn pixel_solid_angle(theta: f32) -> f32 {
+    2.0 * PI * PI * theta.sin() / (TABLE_WIDTH * TABLE_HEIGHT) as f32
+}
+
+// Perez et al. luminance distribution for a view `theta` from the zenith and
+// `gamma` from the sun.
+fn perez_function(coefficients: &[f32; 5], theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+    let cos_gamma = gamma.cos();
+    (1.0 + a * (b / theta.cos().max(0.001)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+// CIE xyY to linear sRGB.
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Vec3 {
+    if y <= 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    xyz_to_rgb(Vec3::new(
+        x / y * luminance,
+        luminance,
+        (1.0 - x - y) / y * luminance,
+    ))
+}
+
+// Fraction of sunlight reaching the ground through Rayleigh scattering and
+// aerosols, at wavelengths standing for red, green and blue.
+fn sun_transmittance(theta_s: f32, turbidity: f32) -> Vec3 {
+    let relative_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let transmittance = |lambda: f32| {
+        let rayleigh = 0.008735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        (-relative_mass * (rayleigh + aerosol)).exp()
+    };
+    Vec3::new(
+        transmittance(0.65),
+        transmittance(0.55),
+        transmittance(0.45),
+    )
+}
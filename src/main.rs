@@ -1,26 +1,64 @@
+use crate::image_utils::aabb::AABB;
 use crate::image_utils::bvh::BVHNode;
 use crate::image_utils::camera::Camera;
+use crate::image_utils::cone::Cone;
 use crate::image_utils::constant_medium::ConstantMedium;
+use crate::image_utils::csg::Csg;
+use crate::image_utils::curve::{Curve, CurveType};
+use crate::image_utils::cylinder::Cylinder;
+use crate::image_utils::disk::Disk;
+use crate::image_utils::heightfield::Heightfield;
 use crate::image_utils::hittable::{Hittable, HittableList};
 use crate::image_utils::image::{Image, Pixel};
+use crate::image_utils::linked::Linked;
+use crate::image_utils::mesh::{Mesh, Subdivision};
+use crate::image_utils::quad::Quad;
 use crate::image_utils::rectangle::{XYRect, XZRect, YZRect};
-use crate::image_utils::rotate::RotateY;
+use crate::image_utils::sdf::{
+    Sdf, SdfBox, SdfCapsule, SdfDifference, SdfDisplace, SdfIntersection, SdfObject, SdfRepeat,
+    SdfSmoothUnion, SdfSphere, SdfTorus, SdfTranslate, SdfTwist, SdfUnion,
+};
 use crate::image_utils::sphere::{MovingSphere, Sphere};
-use crate::image_utils::texture::{CheckerTexture, ImageTexture, NoiseTexture};
-use crate::image_utils::translate::Translate;
-use crate::materials::materials::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::image_utils::subsurface::Subsurface;
+use crate::image_utils::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor};
+use crate::image_utils::torus::Torus;
+use crate::image_utils::transformed::{AnimatedTransformed, Transformed};
+use crate::image_utils::triangle::Triangle;
+use crate::integrators::path_tracer::{BounceLimits, PathTracer};
+use crate::lights::area::AreaLight;
+use crate::lights::directional::DirectionalLight;
+use crate::lights::environment::EnvironmentLight;
+use crate::lights::ies::IesProfile;
+use crate::lights::light::Light;
+use crate::lights::linking::{GroupedLight, LightSet, ObjectLinking};
+use crate::lights::point::{PointLight, SpotLight};
+use crate::lights::sky::SkyLight;
+use crate::materials::coated::Coated;
+use crate::materials::hair::Hair;
+use crate::materials::materials::{
+    Dielectric, DiffuseLight, Lambertian, Material, Metal, OrenNayar, Sheen,
+};
+use crate::materials::mix::MixMaterial;
+use crate::materials::principled::{scalar, Principled};
+use crate::utils::animated_transform::{AnimatedTransform, Keyframe};
+use crate::utils::color::blackbody;
+use crate::utils::mat4::Mat4;
+use crate::utils::perlin::Perlin;
+use crate::utils::quaternion::Quaternion;
+use crate::utils::transform::Transform;
 use crate::utils::vec3::Vec3;
 use crate::utils::{random_double, random_double_range};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
 mod image_utils;
+mod integrators;
+mod lights;
 mod materials;
 mod utils;
 
 const NUMBER_OF_THREADS: usize = 7;
 
-#[allow(dead_code)]
 fn random_scene() -> HittableList {
     let mut list = HittableList::new();
     // let material_ground = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
@@ -137,8 +175,10 @@ fn pepega() -> HittableList {
     list
 }
 
-fn cornell_box() -> HittableList {
+// Scene objects and the emissive ones among them, sampled as lights.
+fn cornell_box() -> (HittableList, HittableList) {
     let mut list = HittableList::new();
+    let mut lights = HittableList::new();
 
     let red = Arc::new(Lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
     let white = Arc::new(Lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
@@ -147,39 +187,532 @@ fn cornell_box() -> HittableList {
 
     let rect1 = YZRect::new(green, 0.0, 555.0, 0.0, 555.0, 555.0);
     let rect2 = YZRect::new(red, 0.0, 555.0, 0.0, 555.0, 0.0);
-    let rect3 = XZRect::new(light, 213.0, 343.0, 227.0, 332.0, 554.0);
+    let rect3 = Arc::new(XZRect::new(light, 213.0, 343.0, 227.0, 332.0, 554.0));
     let rect4 = XZRect::new(white.clone(), 0.0, 555.0, 0.0, 555.0, 0.0);
     let rect5 = XZRect::new(white.clone(), 0.0, 555.0, 0.0, 555.0, 555.0);
     let rect6 = XYRect::new(white.clone(), 0.0, 555.0, 0.0, 555.0, 555.0);
 
-    let mut box1: Arc<dyn Hittable + Send + Sync> = Arc::new(crate::image_utils::r#box::Box::new(
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(165.0, 330.0, 165.0),
-        white.clone(),
-    ));
-    box1 = Arc::new(RotateY::new(box1, 15.0));
-    box1 = Arc::new(Translate::new(box1, Vec3::new(265.0, 0.0, 295.0)));
-    let mut box2: Arc<dyn Hittable + Send + Sync> = Arc::new(crate::image_utils::r#box::Box::new(
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(165.0, 165.0, 165.0),
-        white.clone(),
-    ));
-    box2 = Arc::new(RotateY::new(box2, -18.0));
-    box2 = Arc::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
+    let box1 = Transformed::new(
+        Arc::new(crate::image_utils::r#box::Box::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(165.0, 330.0, 165.0),
+            white.clone(),
+        )),
+        Transform::rotate_y(15.0).then(Transform::translate(Vec3::new(265.0, 0.0, 295.0))),
+    );
+    let box2 = Transformed::new(
+        Arc::new(crate::image_utils::r#box::Box::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(165.0, 165.0, 165.0),
+            white.clone(),
+        )),
+        Transform::rotate_y(-18.0).then(Transform::translate(Vec3::new(130.0, 0.0, 65.0))),
+    );
 
     list.add(Arc::new(rect1));
     list.add(Arc::new(rect2));
-    list.add(Arc::new(rect3));
+    list.add(rect3.clone());
+    lights.add(rect3);
     list.add(Arc::new(rect4));
     list.add(Arc::new(rect5));
     list.add(Arc::new(rect6));
-    list.add(box1);
-    list.add(box2);
+    list.add(Arc::new(box1));
+    list.add(Arc::new(box2));
+    (list, lights)
+}
+
+// An ellipsoid, a tumbled box, a sheared box and a shrunken sphere, each
+// made by transforming a plain primitive, under a ceiling light.
+fn affine_transforms() -> HittableList {
+    let mut list = HittableList::new();
+    let white = Arc::new(Lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
+    let light = Arc::new(DiffuseLight::new(Vec3::new(7.0, 7.0, 7.0)));
+    list.add(Arc::new(XZRect::new(
+        white.clone(),
+        -1000.0,
+        1000.0,
+        -1000.0,
+        1000.0,
+        0.0,
+    )));
+    list.add(Arc::new(XZRect::new(
+        light, 113.0, 443.0, 127.0, 432.0, 554.0,
+    )));
+
+    let unit_sphere = Arc::new(Sphere::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Vec3::new(0.8, 0.3, 0.2))),
+    ));
+    list.add(Arc::new(Transformed::new(
+        unit_sphere.clone(),
+        Transform::scale(Vec3::new(90.0, 40.0, 60.0))
+            .then(Transform::rotate(Vec3::new(1.0, 1.0, 0.0), 30.0))
+            .then(Transform::translate(Vec3::new(150.0, 90.0, 200.0))),
+    )));
+    list.add(Arc::new(Transformed::new(
+        unit_sphere,
+        Transform::uniform_scale(50.0).then(Transform::translate(Vec3::new(420.0, 50.0, 150.0))),
+    )));
+
+    let unit_box = Arc::new(crate::image_utils::r#box::Box::new(
+        Vec3::new(-1.0, -1.0, -1.0),
+        Vec3::new(1.0, 1.0, 1.0),
+        white,
+    ));
+    list.add(Arc::new(Transformed::new(
+        unit_box.clone(),
+        Transform::uniform_scale(60.0)
+            .then(Transform::rotate_x(30.0))
+            .then(Transform::rotate_z(20.0))
+            .then(Transform::translate(Vec3::new(300.0, 100.0, 300.0))),
+    )));
+    // Slanted along x as y grows.
+    let shear = Mat4::new([
+        [1.0, 0.6, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    list.add(Arc::new(Transformed::new(
+        unit_box,
+        Transform::scale(Vec3::new(40.0, 120.0, 40.0))
+            .then(Transform::from_matrix(shear))
+            .then(Transform::translate(Vec3::new(230.0, 120.0, 450.0))),
+    )));
+    list
+}
+
+fn spinning_boxes() -> HittableList {
+    let mut list = HittableList::new();
+    let ground = Arc::new(CheckerTexture::from_color(
+        Vec3::new(0.2, 0.3, 0.1),
+        Vec3::new(0.9, 0.9, 0.9),
+    ));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::from_texture(ground)),
+    )));
+
+    let unit_box: Arc<dyn Hittable + Send + Sync> = Arc::new(crate::image_utils::r#box::Box::new(
+        Vec3::new(-0.5, -0.5, -0.5),
+        Vec3::new(0.5, 0.5, 0.5),
+        Arc::new(Lambertian::new(Vec3::new(0.7, 0.3, 0.1))),
+    ));
+    let axis = Vec3::new(0.0, 1.0, 0.0);
+    let spinning = AnimatedTransform::new(vec![
+        Keyframe::new(
+            0.0,
+            Vec3::new(-2.0, 1.0, 0.0),
+            Quaternion::identity(),
+            Vec3::new(1.0, 1.0, 1.0),
+        ),
+        Keyframe::new(
+            0.5,
+            Vec3::new(-2.0, 1.0, 0.0),
+            Quaternion::from_axis_angle(axis, 45.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ),
+        Keyframe::new(
+            1.0,
+            Vec3::new(-2.0, 1.0, 0.0),
+            Quaternion::from_axis_angle(axis, 90.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ),
+    ]);
+    list.add(Arc::new(AnimatedTransformed::new(
+        unit_box.clone(),
+        spinning,
+    )));
+
+    let tumbling = AnimatedTransform::between(
+        Keyframe::new(
+            0.0,
+            Vec3::new(2.0, 1.0, 0.0),
+            Quaternion::identity(),
+            Vec3::new(1.0, 1.0, 1.0),
+        ),
+        Keyframe::new(
+            1.0,
+            Vec3::new(2.5, 1.5, 0.0),
+            Quaternion::from_axis_angle(Vec3::new(1.0, 0.0, 1.0), 60.0),
+            Vec3::new(1.5, 1.5, 1.5),
+        ),
+    );
+    list.add(Arc::new(AnimatedTransformed::new(unit_box, tumbling)));
+    list
+}
+
+fn primitives() -> HittableList {
+    let mut list = HittableList::new();
+    let checker = Arc::new(CheckerTexture::from_color(
+        Vec3::new(0.2, 0.3, 0.1),
+        Vec3::new(0.9, 0.9, 0.9),
+    ));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::from_texture(checker)),
+    )));
+
+    let red = Arc::new(Lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
+    let green = Arc::new(Lambertian::new(Vec3::new(0.12, 0.45, 0.15)));
+    let blue = Arc::new(Lambertian::new(Vec3::new(0.1, 0.2, 0.6)));
+    let gold = Arc::new(Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.1));
+    let earth = Arc::new(Lambertian::from_texture(Arc::new(ImageTexture::new(
+        "earthmap.jpg",
+    ))));
+
+    list.add(Arc::new(Disk::new(
+        Vec3::new(-4.0, 1.0, 0.0),
+        Vec3::new(1.0, 0.5, 1.0),
+        1.0,
+        earth.clone(),
+    )));
+    list.add(Arc::new(Cylinder::new(
+        Vec3::new(-1.5, 0.0, 0.0),
+        Vec3::new(-1.5, 2.0, 0.0),
+        0.7,
+        red,
+    )));
+    list.add(Arc::new(Cone::new(
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(1.0, 2.0, 0.0),
+        0.8,
+        green,
+    )));
+    list.add(Arc::new(Torus::new(
+        Vec3::new(3.5, 1.0, 0.0),
+        Vec3::new(0.3, 1.0, -0.5),
+        0.8,
+        0.25,
+        gold,
+    )));
+    list.add(Arc::new(Quad::new(
+        Vec3::new(-3.0, 0.5, -3.0),
+        Vec3::new(2.5, 0.0, -0.5),
+        Vec3::new(0.0, 2.0, 0.0),
+        earth,
+    )));
+    list.add(Arc::new(Triangle::new(
+        Vec3::new(0.5, 0.5, -3.0),
+        Vec3::new(3.0, 0.5, -3.5),
+        Vec3::new(1.5, 2.5, -3.2),
+        blue,
+    )));
+    list
+}
+
+fn machined_parts() -> HittableList {
+    let mut list = HittableList::new();
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+    )));
+
+    let steel = Arc::new(Metal::new(Vec3::new(0.7, 0.7, 0.75), 0.2));
+    let brass = Arc::new(Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.1));
+    let cutter = Arc::new(Lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
+
+    // A cube with a spherical pocket milled into its top face.
+    let block = Arc::new(crate::image_utils::r#box::Box::new(
+        Vec3::new(-3.0, 0.0, -1.0),
+        Vec3::new(-1.0, 2.0, 1.0),
+        steel.clone(),
+    ));
+    let pocket = Arc::new(Sphere::new(Vec3::new(-2.0, 2.0, 0.0), 0.9, cutter));
+    list.add(Arc::new(Csg::difference(block, pocket)));
+
+    // A cube with its edges rounded off by a sphere.
+    let cube = Arc::new(crate::image_utils::r#box::Box::new(
+        Vec3::new(-0.8, 0.0, -0.8),
+        Vec3::new(0.8, 1.6, 0.8),
+        brass.clone(),
+    ));
+    let rounding = Arc::new(Sphere::new(Vec3::new(0.0, 0.8, 0.0), 1.05, brass));
+    list.add(Arc::new(Csg::intersection(cube, rounding)));
+
+    // Two fused spheres with a bore through them.
+    let fused = Arc::new(Csg::union(
+        Arc::new(Sphere::new(Vec3::new(2.0, 1.0, 0.0), 0.8, steel.clone())),
+        Arc::new(Sphere::new(Vec3::new(2.8, 1.0, 0.0), 0.8, steel.clone())),
+    ));
+    let bore = Arc::new(crate::image_utils::r#box::Box::new(
+        Vec3::new(1.0, 0.8, -1.0),
+        Vec3::new(3.8, 1.2, 1.0),
+        steel,
+    ));
+    list.add(Arc::new(Csg::difference(fused, bore)));
+    list
+}
+
+fn sdf_shapes() -> HittableList {
+    let mut list = HittableList::new();
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+    )));
+
+    let blob: Arc<dyn Sdf + Send + Sync> = Arc::new(SdfSmoothUnion::new(
+        Arc::new(SdfSphere::new(Vec3::new(-3.0, 1.0, 0.0), 0.8)),
+        Arc::new(SdfBox::new_rounded(
+            Vec3::new(-3.0, 0.5, 0.0),
+            Vec3::new(1.0, 0.3, 0.6),
+            0.1,
+        )),
+        0.4,
+    ));
+    list.add(Arc::new(SdfObject::new(
+        blob,
+        AABB::new(Vec3::new(-4.5, -0.5, -1.5), Vec3::new(-1.5, 2.5, 1.5)),
+        Arc::new(Lambertian::new(Vec3::new(0.7, 0.3, 0.3))),
+    )));
+
+    let twisted = Arc::new(SdfTranslate::new(
+        Arc::new(SdfTwist::new(
+            Arc::new(SdfBox::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.4, 1.0, 0.4),
+            )),
+            1.5,
+        )),
+        Vec3::new(0.0, 1.0, 0.0),
+    ));
+    list.add(Arc::new(SdfObject::new_with_step_scale(
+        twisted,
+        AABB::new(Vec3::new(-0.6, -0.1, -0.6), Vec3::new(0.6, 2.1, 0.6)),
+        0.5,
+        Arc::new(Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.1)),
+    )));
+
+    let pillars = Arc::new(SdfRepeat::new(
+        Arc::new(SdfCapsule::new(
+            Vec3::new(0.0, 0.2, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.15,
+        )),
+        Vec3::new(0.5, 0.0, 0.5),
+        Vec3::new(1.0, 0.0, 1.0),
+    ));
+    list.add(Arc::new(Transformed::new(
+        Arc::new(SdfObject::new(
+            pillars,
+            AABB::new(Vec3::new(-0.7, 0.0, -0.7), Vec3::new(0.7, 1.2, 0.7)),
+            Arc::new(Lambertian::new(Vec3::new(0.2, 0.4, 0.8))),
+        )),
+        Transform::translate(Vec3::new(2.5, 0.0, -1.0)),
+    )));
+
+    let rock = Arc::new(SdfDisplace::new(
+        Arc::new(SdfSphere::new(Vec3::new(2.5, 0.8, 1.5), 0.7)),
+        4.0,
+        0.15,
+    ));
+    list.add(Arc::new(SdfObject::new_with_step_scale(
+        rock,
+        AABB::new(Vec3::new(1.6, -0.1, 0.6), Vec3::new(3.4, 1.7, 2.4)),
+        0.6,
+        Arc::new(Lambertian::new(Vec3::new(0.45, 0.4, 0.35))),
+    )));
+
+    // A rounded cube drilled through both ways, beside a ring lying flat.
+    let drilled = Arc::new(SdfDifference::new(
+        Arc::new(SdfIntersection::new(
+            Arc::new(SdfBox::new(
+                Vec3::new(0.0, 0.5, 2.5),
+                Vec3::new(0.5, 0.5, 0.5),
+            )),
+            Arc::new(SdfSphere::new(Vec3::new(0.0, 0.5, 2.5), 0.65)),
+        )),
+        Arc::new(SdfUnion::new(
+            Arc::new(SdfCapsule::new(
+                Vec3::new(-1.0, 0.5, 2.5),
+                Vec3::new(1.0, 0.5, 2.5),
+                0.25,
+            )),
+            Arc::new(SdfCapsule::new(
+                Vec3::new(0.0, 0.5, 1.5),
+                Vec3::new(0.0, 0.5, 3.5),
+                0.25,
+            )),
+        )),
+    ));
+    let ring = Arc::new(SdfTorus::new(Vec3::new(-1.5, 0.2, 2.5), 0.6, 0.2));
+    list.add(Arc::new(SdfObject::new(
+        Arc::new(SdfUnion::new(drilled, ring)),
+        AABB::new(Vec3::new(-2.4, -0.1, 1.6), Vec3::new(0.7, 1.1, 3.4)),
+        Arc::new(Metal::new(Vec3::new(0.7, 0.7, 0.75), 0.2)),
+    )));
+    list
+}
+
+fn terrain() -> HittableList {
+    let mut list = HittableList::new();
+    let grass = Arc::new(Lambertian::new(Vec3::new(0.3, 0.45, 0.2)));
+    list.add(Arc::new(Heightfield::from_noise(
+        &Perlin::new(),
+        512,
+        6.0,
+        7,
+        Vec3::new(-50.0, 0.0, -50.0),
+        Vec3::new(100.0, 12.0, 100.0),
+        grass,
+    )));
+    let earth_texture = Arc::new(ImageTexture::new("earthmap.jpg"));
+    let relief = Heightfield::from_image(
+        "earthmap.jpg",
+        Vec3::new(-10.0, 12.0, -5.0),
+        Vec3::new(20.0, 2.0, 10.0),
+        Arc::new(Lambertian::from_texture(earth_texture)),
+    )
+    .unwrap();
+    list.add(Arc::new(relief));
+    list
+}
+
+fn grass_and_fur() -> HittableList {
+    let mut list = HittableList::new();
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Vec3::new(0.35, 0.25, 0.15))),
+    )));
+
+    let mut blades = HittableList::new();
+    let grass = Arc::new(Lambertian::new(Vec3::new(0.2, 0.5, 0.1)));
+    for _ in 0..4000 {
+        let root = Vec3::new(
+            random_double_range(-6.0, 6.0),
+            0.0,
+            random_double_range(-6.0, 2.0),
+        );
+        let height = random_double_range(0.4, 1.0);
+        let bend = Vec3::new(
+            random_double_range(-0.3, 0.3),
+            0.0,
+            random_double_range(-0.3, 0.3),
+        );
+        let points = [
+            root,
+            root + Vec3::new(0.0, 0.4 * height, 0.0) + 0.1 * bend,
+            root + Vec3::new(0.0, 0.8 * height, 0.0) + 0.5 * bend,
+            root + Vec3::new(0.0, height, 0.0) + bend,
+        ];
+        blades.add(Arc::new(Curve::bspline_strand(
+            &points,
+            0.04,
+            0.002,
+            CurveType::Ribbon,
+            grass.clone(),
+        )));
+    }
+    list.add(BVHNode::from_list(blades, 0.0, 1.0));
+
+    let mut fur = HittableList::new();
+    let hair = Arc::new(Hair::from_melanin(1.3, 0.2, 0.25, 0.3));
+    // Dyed blue on top.
+    let dyed = Arc::new(Hair::from_color(Vec3::new(0.2, 0.35, 0.8), 0.25, 0.3));
+    let center = Vec3::new(0.0, 1.0, 0.0);
+    for _ in 0..20000 {
+        let normal = Vec3::random_unit_vector();
+        let material = if normal.y > 0.6 {
+            dyed.clone()
+        } else {
+            hair.clone()
+        };
+        let root = center + 0.8 * normal;
+        let droop = Vec3::new(0.0, -0.15, 0.0);
+        let points = [
+            root,
+            root + 0.1 * normal,
+            root + 0.2 * normal + 0.3 * droop,
+            root + 0.3 * normal + droop,
+        ];
+        fur.add(Arc::new(Curve::bspline_strand(
+            &points,
+            0.006,
+            0.001,
+            CurveType::Round,
+            material,
+        )));
+    }
+    list.add(BVHNode::from_list(fur, 0.0, 1.0));
+    list.add(Arc::new(Sphere::new(
+        center,
+        0.8,
+        Arc::new(Lambertian::new(Vec3::new(0.3, 0.2, 0.1))),
+    )));
+    list
+}
+
+fn subdivision_surfaces() -> HittableList {
+    let mut list = HittableList::new();
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+    )));
+
+    let cube = Mesh::new(
+        vec![
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(1.0, 1.0, -1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(1.0, -1.0, 1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(-1.0, 1.0, 1.0),
+        ],
+        vec![
+            vec![0, 3, 2, 1],
+            vec![4, 5, 6, 7],
+            vec![0, 1, 5, 4],
+            vec![1, 2, 6, 5],
+            vec![2, 3, 7, 6],
+            vec![3, 0, 4, 7],
+        ],
+    );
+    let smooth = cube.subdivide(Subdivision::CatmullClark, 4);
+    list.add(Arc::new(Transformed::new(
+        BVHNode::from_list(
+            smooth.triangles(Arc::new(Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.1))),
+            0.0,
+            1.0,
+        ),
+        Transform::translate(Vec3::new(-2.5, 1.0, 0.0)),
+    )));
+    let rocky = cube
+        .subdivide(Subdivision::Loop, 5)
+        .displace(&NoiseTexture::new_with_scale(4.0), 0.15);
+    list.add(Arc::new(Transformed::new(
+        BVHNode::from_list(
+            rocky.triangles(Arc::new(Lambertian::new(Vec3::new(0.4, 0.35, 0.3)))),
+            0.0,
+            1.0,
+        ),
+        Transform::translate(Vec3::new(2.5, 1.0, 0.0)),
+    )));
+    let pyramid = Mesh::from_obj("pyramid.obj")
+        .unwrap()
+        .subdivide(Subdivision::CatmullClark, 3);
+    list.add(Arc::new(Transformed::new(
+        BVHNode::from_list(
+            pyramid.triangles(Arc::new(Lambertian::new(Vec3::new(0.2, 0.4, 0.7)))),
+            0.0,
+            1.0,
+        ),
+        Transform::translate(Vec3::new(0.0, 0.0, -2.5)),
+    )));
     list
 }
 
-fn final_scene() -> HittableList {
+fn final_scene() -> (HittableList, HittableList) {
     let mut list = HittableList::new();
+    let mut lights = HittableList::new();
     let mut boxes = HittableList::new();
 
     let ground = Arc::new(Lambertian::new(Vec3::new(0.48, 0.83, 0.53)));
@@ -203,9 +736,9 @@ fn final_scene() -> HittableList {
     list.add(BVHNode::from_list(boxes, 0.0, 1.0));
 
     let light = Arc::new(DiffuseLight::new(Vec3::new(7.0, 7.0, 7.0)));
-    list.add(Arc::new(XZRect::new(
-        light, 123.0, 423.0, 147.0, 412.0, 554.0,
-    )));
+    let light = Arc::new(XZRect::new(light, 123.0, 423.0, 147.0, 412.0, 554.0));
+    list.add(light.clone());
+    lights.add(light);
 
     let center1 = Vec3::new(400.0, 400.0, 200.0);
     let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
@@ -281,16 +814,17 @@ fn final_scene() -> HittableList {
         )));
     }
 
-    list.add(Arc::new(Translate::new(
-        Arc::new(RotateY::new(BVHNode::from_list(boxes2, 0.0, 1.0), 15.0)),
-        Vec3::new(-100.0, 270.0, 395.0),
+    list.add(Arc::new(Transformed::new(
+        BVHNode::from_list(boxes2, 0.0, 1.0),
+        Transform::rotate_y(15.0).then(Transform::translate(Vec3::new(-100.0, 270.0, 395.0))),
     )));
 
-    list
+    (list, lights)
 }
 
-fn simple_light() -> HittableList {
+fn simple_light() -> (HittableList, HittableList) {
     let mut list = HittableList::new();
+    let mut lights = HittableList::new();
     let pertext = Arc::new(NoiseTexture::new_with_scale(4.0));
     let sphere = Sphere::new(
         Vec3::new(0.0, -1000.0, 0.0),
@@ -303,16 +837,669 @@ fn simple_light() -> HittableList {
         Arc::new(Lambertian::from_texture(pertext)),
     );
     let difflight = Arc::new(DiffuseLight::new(Vec3::new(4.0, 4.0, 4.0)));
-    let rect = XYRect::new(difflight, 3.0, 5.0, 1.0, 3.0, -2.0);
+    let rect = Arc::new(XYRect::new(difflight, 3.0, 5.0, 1.0, 3.0, -2.0));
     list.add(Arc::new(sphere));
     list.add(Arc::new(sphere2));
-    list.add(Arc::new(rect));
+    list.add(rect.clone());
+    lights.add(rect);
+    (list, lights)
+}
+
+// Spheres on a ground plane, for scenes lit only from far away.
+fn outdoor_spheres() -> HittableList {
+    let mut list = HittableList::new();
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(-2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Vec3::new(0.8, 0.3, 0.2))),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Vec3::new(0.9, 0.9, 0.9), 0.05)),
+    )));
     list
 }
 
-fn run(id: usize, width: u32, height: u32, camera: &Camera, list: &HittableList) -> Vec<Pixel> {
-    let samples_per_pixel = 10000;
-    let max_depth = 50;
+fn environment_lit() -> (HittableList, Arc<EnvironmentLight>) {
+    let environment = Arc::new(EnvironmentLight::new("environment.hdr", 90.0, 1.0).unwrap());
+    (outdoor_spheres(), environment)
+}
+
+// Late afternoon sun, low enough for long shadows.
+fn sun_and_sky() -> (HittableList, Arc<SkyLight>) {
+    let sky = Arc::new(SkyLight::new(
+        20.0,
+        60.0,
+        3.0,
+        Vec3::new(0.3, 0.3, 0.3),
+        1.0,
+    ));
+    (outdoor_spheres(), sky)
+}
+
+// The outdoor spheres at night: a warm spot light, a dim blue point light
+// and faint moonlight.
+fn light_types() -> (HittableList, Vec<Arc<dyn Light + Send + Sync>>) {
+    let lights: Vec<Arc<dyn Light + Send + Sync>> = vec![
+        Arc::new(SpotLight::new(
+            Vec3::new(0.0, 6.0, 2.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(60.0, 45.0, 30.0),
+            25.0,
+            15.0,
+        )),
+        Arc::new(PointLight::new(
+            Vec3::new(-4.0, 1.5, 2.0),
+            Vec3::new(2.0, 3.0, 6.0),
+        )),
+        Arc::new(DirectionalLight::new_with_radius(
+            Vec3::new(-1.0, 2.0, -1.0),
+            Vec3::new(0.05, 0.05, 0.08),
+            0.5,
+        )),
+    ];
+    (outdoor_spheres(), lights)
+}
+
+// Gallery wall washed by measured fixtures: a profiled ceiling panel, a
+// profiled point light aimed at the wall and a profiled spot grazing it.
+fn ies_fixtures() -> (HittableList, Vec<Arc<dyn Light + Send + Sync>>) {
+    let mut list = HittableList::new();
+    let white = Arc::new(Lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
+    let profile = Arc::new(IesProfile::from_file("fixture.ies").unwrap());
+
+    list.add(Arc::new(XZRect::new(
+        white.clone(),
+        -500.0,
+        500.0,
+        -500.0,
+        500.0,
+        0.0,
+    )));
+    list.add(Arc::new(XYRect::new(
+        white, -500.0, 500.0, 0.0, 400.0, 200.0,
+    )));
+    let panel = Arc::new(XZRect::new(
+        Arc::new(DiffuseLight::with_profile(
+            Vec3::new(10.0, 10.0, 10.0),
+            profile.clone(),
+        )),
+        -150.0,
+        -50.0,
+        100.0,
+        150.0,
+        399.0,
+    ));
+    list.add(panel.clone());
+
+    let lights: Vec<Arc<dyn Light + Send + Sync>> = vec![
+        Arc::new(AreaLight::new(panel)),
+        Arc::new(PointLight::new_with_profile(
+            Vec3::new(150.0, 380.0, 100.0),
+            Vec3::new(40000.0, 36000.0, 30000.0),
+            profile.clone(),
+            Vec3::new(0.0, -1.0, 0.6),
+        )),
+        Arc::new(SpotLight::new_with_profile(
+            Vec3::new(-300.0, 390.0, 150.0),
+            Vec3::new(-300.0, 150.0, 200.0),
+            Vec3::new(30000.0, 30000.0, 30000.0),
+            40.0,
+            30.0,
+            profile,
+        )),
+    ];
+    (list, lights)
+}
+
+// Product shot with a one sided daylight softbox given in watts and narrowed
+// by its spread, a dimmer tungsten fill given in lumens and a one sided
+// rim strip behind the subject.
+fn softbox_studio() -> (HittableList, HittableList) {
+    let mut list = HittableList::new();
+    let mut lights = HittableList::new();
+    list.add(Arc::new(XZRect::new(
+        Arc::new(Lambertian::new(Vec3::new(0.6, 0.6, 0.6))),
+        -20.0,
+        20.0,
+        -20.0,
+        20.0,
+        0.0,
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Vec3::new(0.8, 0.7, 0.5), 0.2)),
+    )));
+
+    // XYRect faces +z, towards the subject.
+    let softbox = Arc::new(XYRect::new(
+        Arc::new(DiffuseLight::from_watts(
+            blackbody(5600.0),
+            400.0,
+            4.0,
+            60.0,
+            false,
+        )),
+        -1.0,
+        1.0,
+        0.5,
+        2.5,
+        -4.0,
+    ));
+    let fill = Arc::new(YZRect::new(
+        Arc::new(DiffuseLight::from_lumens(
+            blackbody(3200.0),
+            40000.0,
+            1.0,
+            180.0,
+            true,
+        )),
+        0.5,
+        1.5,
+        -0.5,
+        0.5,
+        4.0,
+    ));
+    let rim = Arc::new(XYRect::new(
+        Arc::new(DiffuseLight::new_one_sided(Vec3::new(6.0, 6.0, 6.0))),
+        1.4,
+        1.6,
+        0.0,
+        3.0,
+        -2.5,
+    ));
+    list.add(softbox.clone());
+    list.add(fill.clone());
+    list.add(rim.clone());
+    lights.add(softbox);
+    lights.add(fill);
+    lights.add(rim);
+    (list, lights)
+}
+
+// Hundreds of small lamps over a ground plane, with the light BVH picking
+// the nearby ones.
+fn city_lights() -> (HittableList, HittableList) {
+    let mut list = HittableList::new();
+    let mut lights = HittableList::new();
+    list.add(Arc::new(XZRect::new(
+        Arc::new(Lambertian::new(Vec3::new(0.4, 0.4, 0.4))),
+        -100.0,
+        100.0,
+        -100.0,
+        100.0,
+        0.0,
+    )));
+    for i in 0..20 {
+        for j in 0..20 {
+            let x = -50.0 + 5.0 * i as f32 + random_double_range(-1.0, 1.0);
+            let z = -50.0 + 5.0 * j as f32 + random_double_range(-1.0, 1.0);
+            let color = 20.0 * blackbody(random_double_range(2200.0, 6500.0));
+            let lamp: Arc<dyn Hittable + Send + Sync> = if random_double() < 0.5 {
+                Arc::new(Sphere::new(
+                    Vec3::new(x, 3.0, z),
+                    0.2,
+                    Arc::new(DiffuseLight::new(color)),
+                ))
+            } else {
+                Arc::new(XZRect::new(
+                    Arc::new(DiffuseLight::new(color)),
+                    x - 0.3,
+                    x + 0.3,
+                    z - 0.3,
+                    z + 0.3,
+                    4.0,
+                ))
+            };
+            list.add(lamp.clone());
+            lights.add(lamp);
+        }
+    }
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Vec3::new(0.9, 0.9, 0.9), 0.05)),
+    )));
+    (list, lights)
+}
+
+// A rim light and a warm point light in group 1 only lighting the sphere, a
+// pillar that casts no shadow and a bounce card the camera does not see.
+fn light_linking() -> (HittableList, Vec<Arc<dyn Light + Send + Sync>>) {
+    let mut list = HittableList::new();
+    let away_from_rim = ObjectLinking {
+        lit_by: LightSet::except(&[1]),
+        ..ObjectLinking::new()
+    };
+    list.add(Arc::new(Linked::new(
+        Arc::new(XZRect::new(
+            Arc::new(Lambertian::new(Vec3::new(0.6, 0.6, 0.6))),
+            -20.0,
+            20.0,
+            -20.0,
+            20.0,
+            0.0,
+        )),
+        away_from_rim,
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Vec3::new(0.7, 0.3, 0.2))),
+    )));
+    list.add(Arc::new(Linked::new(
+        Arc::new(Cylinder::new(
+            Vec3::new(-2.0, 0.0, -1.0),
+            Vec3::new(-2.0, 3.0, -1.0),
+            0.3,
+            Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.8))),
+        )),
+        ObjectLinking {
+            shadows_for: LightSet::NONE,
+            ..away_from_rim
+        },
+    )));
+    list.add(Arc::new(Linked::new(
+        Arc::new(YZRect::new(
+            Arc::new(Lambertian::new(Vec3::new(0.9, 0.9, 0.9))),
+            0.0,
+            3.0,
+            -2.0,
+            2.0,
+            3.0,
+        )),
+        ObjectLinking {
+            camera_visible: false,
+            ..away_from_rim
+        },
+    )));
+
+    let key = Arc::new(XZRect::new(
+        Arc::new(DiffuseLight::new(Vec3::new(8.0, 8.0, 8.0))),
+        -1.0,
+        1.0,
+        -1.0,
+        1.0,
+        6.0,
+    ));
+    let rim: Arc<dyn Hittable + Send + Sync> = Arc::new(Linked::new(
+        Arc::new(XYRect::new(
+            Arc::new(DiffuseLight::new(Vec3::new(2.0, 4.0, 12.0))),
+            -1.0,
+            1.0,
+            0.5,
+            2.5,
+            -4.0,
+        )),
+        ObjectLinking {
+            light_group: 1,
+            ..ObjectLinking::new()
+        },
+    ));
+    list.add(key.clone());
+    list.add(rim.clone());
+    let lights: Vec<Arc<dyn Light + Send + Sync>> = vec![
+        Arc::new(AreaLight::new(key)),
+        Arc::new(AreaLight::new(rim)),
+        Arc::new(GroupedLight::new(
+            Arc::new(PointLight::new(
+                Vec3::new(2.5, 2.5, -2.0),
+                Vec3::new(6.0, 4.0, 2.0),
+            )),
+            1,
+        )),
+    ];
+    (list, lights)
+}
+
+// The conductor presets left to right, rough at the back and polished at
+// the front, with a brushed aluminium floor under the sky.
+fn metal_presets() -> (HittableList, HittableList) {
+    let mut list = HittableList::new();
+    list.add(Arc::new(XZRect::new(
+        Arc::new(Metal::from_ior(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+            0.4,
+            0.8,
+        )),
+        -10.0,
+        10.0,
+        -10.0,
+        10.0,
+        0.0,
+    )));
+    let presets: [fn(f32) -> Metal; 4] =
+        [Metal::gold, Metal::copper, Metal::aluminium, Metal::silver];
+    for (i, preset) in presets.iter().enumerate() {
+        let x = -3.0 + 2.0 * i as f32;
+        list.add(Arc::new(Sphere::new(
+            Vec3::new(x, 0.8, 1.0),
+            0.8,
+            Arc::new(preset(0.05)),
+        )));
+        list.add(Arc::new(Sphere::new(
+            Vec3::new(x, 0.8, -1.0),
+            0.8,
+            Arc::new(preset(0.5)),
+        )));
+    }
+    (list, HittableList::new())
+}
+
+// Frosted glass, a green glass paperweight and a window pane in front of
+// a lamp.
+fn glass_materials() -> (HittableList, HittableList) {
+    let mut list = HittableList::new();
+    let mut lights = HittableList::new();
+    list.add(Arc::new(XZRect::new(
+        Arc::new(Lambertian::new(Vec3::new(0.7, 0.7, 0.7))),
+        -10.0,
+        10.0,
+        -10.0,
+        10.0,
+        0.0,
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(-1.5, 1.0, 0.0),
+        1.0,
+        Arc::new(Dielectric::new_rough(1.5, 0.3)),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(1.5, 1.0, 0.0),
+        1.0,
+        Arc::new(Dielectric::new_absorbing(
+            1.5,
+            0.0,
+            Vec3::new(0.2, 0.7, 0.3),
+            1.0,
+        )),
+    )));
+    list.add(Arc::new(XYRect::new(
+        Arc::new(Dielectric::new_thin(1.5)),
+        -3.0,
+        3.0,
+        0.0,
+        3.0,
+        -2.0,
+    )));
+    let lamp = Arc::new(XYRect::new(
+        Arc::new(DiffuseLight::new(Vec3::new(6.0, 6.0, 6.0))),
+        -1.0,
+        1.0,
+        1.0,
+        3.0,
+        -5.0,
+    ));
+    list.add(lamp.clone());
+    lights.add(lamp);
+    (list, lights)
+}
+
+// A row of principled spheres: red car paint, gold, velvet, frosted glass,
+// wax and a glowing one, in front of a sphere whose checker texture
+// switches it between metal and plastic.
+fn principled_materials() -> (HittableList, HittableList) {
+    let mut list = HittableList::new();
+    list.add(Arc::new(XZRect::new(
+        Arc::new(Principled::new(Vec3::new(0.6, 0.6, 0.6))),
+        -10.0,
+        10.0,
+        -10.0,
+        10.0,
+        0.0,
+    )));
+    let materials = [
+        Principled {
+            roughness: scalar(0.4),
+            clearcoat: scalar(1.0),
+            ..Principled::new(Vec3::new(0.7, 0.05, 0.05))
+        },
+        Principled {
+            metallic: scalar(1.0),
+            roughness: scalar(0.25),
+            ..Principled::new(Vec3::new(1.0, 0.78, 0.34))
+        },
+        Principled {
+            roughness: scalar(1.0),
+            sheen: scalar(1.0),
+            ..Principled::new(Vec3::new(0.3, 0.05, 0.4))
+        },
+        Principled {
+            roughness: scalar(0.3),
+            transmission: scalar(1.0),
+            ..Principled::new(Vec3::new(0.9, 0.95, 1.0))
+        },
+        Principled {
+            roughness: scalar(0.6),
+            subsurface: scalar(1.0),
+            ..Principled::new(Vec3::new(0.9, 0.8, 0.6))
+        },
+        Principled {
+            emission: Arc::new(SolidColor::new(Vec3::new(4.0, 2.5, 1.0))),
+            ..Principled::new(Vec3::new(0.1, 0.1, 0.1))
+        },
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        list.add(Arc::new(Sphere::new(
+            Vec3::new(-5.0 + 2.0 * i as f32, 0.8, 1.0),
+            0.8,
+            Arc::new(material),
+        )));
+    }
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, 1.5, -2.0),
+        1.5,
+        Arc::new(Principled {
+            metallic: Arc::new(CheckerTexture::from_color(
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 1.0, 1.0),
+            )),
+            roughness: scalar(0.2),
+            ..Principled::new(Vec3::new(0.2, 0.4, 0.8))
+        }),
+    )));
+    (list, HittableList::new())
+}
+
+// Skin, marble and a wax ring lit from behind, so light shows through
+// their thin parts. Random walks take many bounces; render with a high
+// depth.
+fn subsurface_materials() -> (HittableList, HittableList) {
+    let mut list = HittableList::new();
+    let mut lights = HittableList::new();
+    list.add(Arc::new(XZRect::new(
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        -10.0,
+        10.0,
+        -10.0,
+        10.0,
+        0.0,
+    )));
+    let boundary = |center: Vec3| {
+        Arc::new(Sphere::new(
+            center,
+            1.0,
+            Arc::new(Lambertian::new(Vec3::new(1.0, 1.0, 1.0))),
+        ))
+    };
+    list.add(Arc::new(Subsurface::new(
+        boundary(Vec3::new(-2.5, 1.0, 0.0)),
+        1.4,
+        0.3,
+        Vec3::new(0.85, 0.55, 0.45),
+        Vec3::new(0.3, 0.15, 0.1),
+        0.0,
+    )));
+    // Jensen et al. 2001, a scene unit being 2 mm.
+    list.add(Arc::new(Subsurface::from_coefficients(
+        boundary(Vec3::new(0.0, 1.0, 0.0)),
+        1.5,
+        0.0,
+        Vec3::new(0.0021, 0.0041, 0.0071) * 2.0,
+        Vec3::new(2.19, 2.62, 3.0) * 2.0,
+        0.0,
+    )));
+    list.add(Arc::new(Subsurface::new(
+        Arc::new(Torus::new(
+            Vec3::new(2.5, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            0.8,
+            0.25,
+            Arc::new(Lambertian::new(Vec3::new(1.0, 1.0, 1.0))),
+        )),
+        1.45,
+        0.2,
+        Vec3::new(0.95, 0.85, 0.6),
+        Vec3::new(0.5, 0.4, 0.25),
+        0.3,
+    )));
+    let lamp = Arc::new(XYRect::new(
+        Arc::new(DiffuseLight::new(Vec3::new(8.0, 8.0, 8.0))),
+        -4.0,
+        4.0,
+        0.5,
+        3.5,
+        -3.0,
+    ));
+    list.add(lamp.clone());
+    lights.add(lamp);
+    (list, lights)
+}
+
+// Car paint, varnished wood and dirty copper: a clearcoat over red
+// metallic paint, amber varnish over a grained diffuse base, and diffuse
+// dirt masked by noise over copper.
+fn layered_materials() -> (HittableList, HittableList) {
+    let mut list = HittableList::new();
+    let mut lights = HittableList::new();
+    list.add(Arc::new(XZRect::new(
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        -10.0,
+        10.0,
+        -10.0,
+        10.0,
+        0.0,
+    )));
+    let paint = Arc::new(Principled {
+        metallic: scalar(0.6),
+        roughness: scalar(0.4),
+        ..Principled::new(Vec3::new(0.6, 0.02, 0.03))
+    });
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(-2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Coated::new(paint, 1.5, 0.0)),
+    )));
+    let grain = Arc::new(Lambertian::from_texture(Arc::new(
+        NoiseTexture::new_with_scale(8.0),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Coated::new_absorbing(
+            grain,
+            1.5,
+            0.05,
+            Vec3::new(0.9, 0.6, 0.3),
+        )),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(MixMaterial::from_texture(
+            Arc::new(Metal::copper(0.2)),
+            Arc::new(Lambertian::new(Vec3::new(0.3, 0.22, 0.15))),
+            Arc::new(NoiseTexture::new_with_scale(2.0)),
+        )),
+    )));
+    let lamp = Arc::new(XZRect::new(
+        Arc::new(DiffuseLight::new(Vec3::new(6.0, 6.0, 6.0))),
+        -2.0,
+        2.0,
+        -2.0,
+        2.0,
+        5.0,
+    ));
+    list.add(lamp.clone());
+    lights.add(lamp);
+    (list, lights)
+}
+
+// Clay, concrete and velvet: smooth and rough Oren-Nayar terracotta, noisy
+// rough concrete, and a sheen over dark red cloth.
+fn cloth_and_clay_materials() -> (HittableList, HittableList) {
+    let mut list = HittableList::new();
+    let mut lights = HittableList::new();
+    list.add(Arc::new(XZRect::new(
+        Arc::new(OrenNayar::from_texture(
+            Arc::new(NoiseTexture::new_with_scale(4.0)),
+            60.0,
+        )),
+        -10.0,
+        10.0,
+        -10.0,
+        10.0,
+        0.0,
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(-3.3, 1.0, 0.0),
+        1.0,
+        Arc::new(OrenNayar::new(Vec3::new(0.7, 0.35, 0.2), 0.0)),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(-1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(OrenNayar::new(Vec3::new(0.7, 0.35, 0.2), 40.0)),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(OrenNayar::new(Vec3::new(0.55, 0.55, 0.52), 80.0)),
+    )));
+    list.add(Arc::new(Sphere::new(
+        Vec3::new(3.3, 1.0, 0.0),
+        1.0,
+        Arc::new(MixMaterial::new(
+            Arc::new(Lambertian::new(Vec3::new(0.3, 0.02, 0.04))),
+            Arc::new(Sheen::new(Vec3::new(1.0, 0.3, 0.35), 0.3)),
+            0.5,
+        )),
+    )));
+    let lamp = Arc::new(XZRect::new(
+        Arc::new(DiffuseLight::new(Vec3::new(6.0, 6.0, 6.0))),
+        -2.0,
+        2.0,
+        -2.0,
+        2.0,
+        5.0,
+    ));
+    list.add(lamp.clone());
+    lights.add(lamp);
+    (list, lights)
+}
+
+fn run(
+    id: usize,
+    width: u32,
+    height: u32,
+    camera: &Camera,
+    list: &HittableList,
+    integrator: &PathTracer,
+    samples_per_pixel: u32,
+) -> Vec<Pixel> {
     let scale = 1.0 / samples_per_pixel as f32;
     let mut index = id * width as usize;
     let total_lines = (height as f32 / NUMBER_OF_THREADS as f32).ceil();
@@ -328,7 +1515,8 @@ fn run(id: usize, width: u32, height: u32, camera: &Camera, list: &HittableList)
         total_lines as u32 + 1
     );
     let mut pixel = Pixel::new(0, 0, 0);
-    for _ in index..max_index as usize {
+    let start = index;
+    for _ in start..max_index as usize {
         let x = index as u32 % width;
         let y = height - (index as u32 / width) - 1;
 
@@ -337,14 +1525,14 @@ fn run(id: usize, width: u32, height: u32, camera: &Camera, list: &HittableList)
             let u = (x as f32 + random_double()) / (width - 1) as f32;
             let v = (y as f32 + random_double()) / (height - 1) as f32;
             let ray = camera.get_ray(u, v);
-            pixel_color = pixel_color + ray.color(background, list, max_depth);
+            pixel_color = pixel_color + integrator.color(&ray, background, list);
         }
         pixel.r = ((pixel_color.x * scale).sqrt().clamp(0.0, 0.999) * 256.0) as u8;
         pixel.g = ((pixel_color.y * scale).sqrt().clamp(0.0, 0.999) * 256.0) as u8;
         pixel.b = ((pixel_color.z * scale).sqrt().clamp(0.0, 0.999) * 256.0) as u8;
         out.push(pixel);
         index += 1;
-        if index % width as usize == 0 {
+        if index.is_multiple_of(width as usize) {
             index += width as usize * (NUMBER_OF_THREADS - 1);
             current_line += 1;
             println!(
@@ -362,41 +1550,25 @@ fn main() {
     let width: f32 = 800.0;
 
     let mut image = Image::new(width as u32, (width / aspect_ratio) as u32, 255);
-    let list = Arc::new(final_scene());
-
-    let lookfrom = Vec3::new(478.0, 278.0, -600.0);
-    let lookat = Vec3::new(278.0, 278.0, -1.0);
-    let view_up = Vec3::new(0.0, 1.0, 0.0);
-    let distance_to_focus = 4.2;
-    let aperture = 0.1;
+    let name = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "final_scene".to_string());
+    let (list, integrator, view) = scene(&name);
+    let samples_per_pixel = view.samples_per_pixel;
+    let list = Arc::new(list);
+    let integrator = Arc::new(integrator);
 
     let camera = Arc::new(Camera::new(
-        lookfrom,
-        lookat,
-        view_up,
-        40.0,
+        view.look_from,
+        view.look_at,
+        Vec3::new(0.0, 1.0, 0.0),
+        view.vertical_fov,
         aspect_ratio,
-        aperture,
-        distance_to_focus,
-        0.0,
-        1.0,
+        view.aperture,
+        view.focus_distance,
+        view.time_1,
+        view.time_2,
     ));
-    // let lookfrom = Vec3::new(478.0, 278.0, -600.0);
-    // let lookat = Vec3::new(278.0, 278.0, 0.0);
-    // let view_up = Vec3::new(0.0, 1.0, 0.0);
-    // let distance_to_focus = 4.2;
-    // let aperture = 0.1;
-    // let camera = Arc::new(Camera::new(
-    //     lookfrom,
-    //     lookat,
-    //     view_up,
-    //     40.0,
-    //     aspect_ratio,
-    //     aperture,
-    //     distance_to_focus,
-    //     0.0,
-    //     1.0,
-    // ));
 
     let pixels_out: Arc<Mutex<Vec<Vec<Pixel>>>> =
         Arc::new(Mutex::new(vec![vec![]; NUMBER_OF_THREADS]));
@@ -406,8 +1578,17 @@ fn main() {
         let camera = camera.clone();
         let pixels_out = pixels_out.clone();
         let list = list.clone();
+        let integrator = integrator.clone();
         threads.push(thread::spawn(move || {
-            let out = run(i, image.width, image.height, &camera, &list);
+            let out = run(
+                i,
+                image.width,
+                image.height,
+                &camera,
+                &list,
+                &integrator,
+                samples_per_pixel,
+            );
             pixels_out.lock().unwrap()[i] = out;
             println!("Thread {i} finished.");
         }));
@@ -417,10 +1598,9 @@ fn main() {
     }
 
     let pixels_out = pixels_out.lock().unwrap();
-    let iterations = image.height / NUMBER_OF_THREADS as u32 + 1;
+    // let iterations = image.height / NUMBER_OF_THREADS as u32 + 1;
     // let iterations = pixels_out[NUMBER_OF_THREADS - 1].len() as u32 / image.width + 1;
     let number_of_lines = image.width.checked_div_euclid(NUMBER_OF_THREADS as u32);
-    let remaining_pixels = image.width.checked_rem_euclid(NUMBER_OF_THREADS as u32);
     for i in 0..number_of_lines.unwrap() {
         for j in 0..NUMBER_OF_THREADS {
             println!(
@@ -443,3 +1623,228 @@ fn main() {
     }
     image.write_to_file("test13.ppm");
 }
+
+// Where a scene is seen from, turned into a `Camera` by `main`, and how
+// many samples `run` takes for each pixel.
+struct View {
+    look_from: Vec3,
+    look_at: Vec3,
+    vertical_fov: f32,
+    aperture: f32,
+    focus_distance: f32,
+    time_1: f32,
+    time_2: f32,
+    samples_per_pixel: u32,
+}
+
+impl View {
+    // A pinhole looking from `look_from` to `look_at`, with the shutter open
+    // over [0, 1] and 500 samples per pixel.
+    fn new(look_from: Vec3, look_at: Vec3, vertical_fov: f32) -> View {
+        View {
+            look_from,
+            look_at,
+            vertical_fov,
+            aperture: 0.0,
+            focus_distance: (look_at - look_from).length(),
+            time_1: 0.0,
+            time_2: 1.0,
+            samples_per_pixel: 500,
+        }
+    }
+}
+
+// Scene picked by name on the command line.
+fn scene(name: &str) -> (HittableList, PathTracer, View) {
+    let unlit = |list: HittableList, view: View| {
+        (
+            list,
+            PathTracer::new(50, 3, HittableList::new(), view.time_1, view.time_2),
+            view,
+        )
+    };
+    let lit = |(list, lights): (HittableList, HittableList), view: View| {
+        (
+            list,
+            PathTracer::new(50, 3, lights, view.time_1, view.time_2),
+            view,
+        )
+    };
+    let lit_by = |(list, lights): (HittableList, Vec<Arc<dyn Light + Send + Sync>>), view: View| {
+        (
+            list,
+            PathTracer::new_with_lights(50, 3, lights, view.time_1, view.time_2),
+            view,
+        )
+    };
+    match name {
+        "random_scene" => unlit(
+            random_scene(),
+            View {
+                aperture: 0.1,
+                focus_distance: 10.0,
+                ..View::new(Vec3::new(13.0, 2.0, 3.0), Vec3::new(0.0, 1.0, 0.0), 30.0)
+            },
+        ),
+        "basic_scene" => unlit(
+            basic_scene(),
+            View::new(Vec3::new(-2.0, 2.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 40.0),
+        ),
+        "two_perlin_spheres" => unlit(
+            two_perlin_spheres(),
+            View::new(Vec3::new(13.0, 2.0, 3.0), Vec3::new(0.0, 1.5, 0.0), 25.0),
+        ),
+        "pepega" => unlit(
+            pepega(),
+            View::new(Vec3::new(0.0, 0.0, 12.0), Vec3::new(0.0, 0.0, 0.0), 25.0),
+        ),
+        "cornell_box" => lit(
+            cornell_box(),
+            View::new(
+                Vec3::new(278.0, 278.0, -800.0),
+                Vec3::new(278.0, 278.0, 0.0),
+                40.0,
+            ),
+        ),
+        "affine_transforms" => unlit(
+            affine_transforms(),
+            View::new(
+                Vec3::new(278.0, 200.0, -200.0),
+                Vec3::new(300.0, 100.0, 300.0),
+                45.0,
+            ),
+        ),
+        "spinning_boxes" => unlit(
+            spinning_boxes(),
+            View::new(Vec3::new(0.0, 3.0, 12.0), Vec3::new(0.0, 1.0, 0.0), 40.0),
+        ),
+        "primitives" => unlit(
+            primitives(),
+            View::new(Vec3::new(0.0, 3.0, 12.0), Vec3::new(0.0, 1.0, -1.0), 40.0),
+        ),
+        "machined_parts" => unlit(
+            machined_parts(),
+            View::new(Vec3::new(0.0, 3.0, 9.0), Vec3::new(0.0, 1.0, 0.0), 45.0),
+        ),
+        "sdf_shapes" => unlit(
+            sdf_shapes(),
+            View::new(Vec3::new(0.0, 4.0, 11.0), Vec3::new(0.0, 0.8, 1.0), 40.0),
+        ),
+        "terrain" => unlit(
+            terrain(),
+            View::new(Vec3::new(0.0, 25.0, 45.0), Vec3::new(0.0, 8.0, 0.0), 50.0),
+        ),
+        "grass_and_fur" => unlit(
+            grass_and_fur(),
+            View::new(Vec3::new(0.0, 3.0, 8.0), Vec3::new(0.0, 0.8, 0.0), 40.0),
+        ),
+        "subdivision_surfaces" => unlit(
+            subdivision_surfaces(),
+            View::new(Vec3::new(0.0, 3.0, 9.0), Vec3::new(0.0, 1.0, -0.5), 40.0),
+        ),
+        "final_scene" => lit(
+            final_scene(),
+            View::new(
+                Vec3::new(478.0, 278.0, -600.0),
+                Vec3::new(278.0, 278.0, 0.0),
+                40.0,
+            ),
+        ),
+        "simple_light" => lit(
+            simple_light(),
+            View::new(Vec3::new(26.0, 3.0, 6.0), Vec3::new(0.0, 2.0, 0.0), 20.0),
+        ),
+        "outdoor_spheres" => unlit(
+            outdoor_spheres(),
+            View::new(Vec3::new(0.0, 2.5, 12.0), Vec3::new(0.0, 1.0, 0.0), 35.0),
+        ),
+        "environment_lit" => {
+            let (list, environment) = environment_lit();
+            let view = View::new(Vec3::new(0.0, 2.5, 12.0), Vec3::new(0.0, 1.0, 0.0), 35.0);
+            let integrator = PathTracer::new_with_environment(
+                50,
+                3,
+                HittableList::new(),
+                environment,
+                view.time_1,
+                view.time_2,
+            );
+            (list, integrator, view)
+        }
+        "sun_and_sky" => {
+            let (list, sky) = sun_and_sky();
+            let view = View::new(Vec3::new(0.0, 2.5, 12.0), Vec3::new(0.0, 1.0, 0.0), 35.0);
+            let integrator = PathTracer::new_with_environment(
+                50,
+                3,
+                HittableList::new(),
+                sky,
+                view.time_1,
+                view.time_2,
+            );
+            (list, integrator, view)
+        }
+        "light_types" => lit_by(
+            light_types(),
+            View::new(Vec3::new(0.0, 3.0, 10.0), Vec3::new(0.0, 1.0, 0.0), 40.0),
+        ),
+        "ies_fixtures" => lit_by(
+            ies_fixtures(),
+            View::new(
+                Vec3::new(0.0, 200.0, -600.0),
+                Vec3::new(0.0, 200.0, 200.0),
+                45.0,
+            ),
+        ),
+        "softbox_studio" => lit(
+            softbox_studio(),
+            View::new(Vec3::new(0.0, 2.0, 8.0), Vec3::new(0.0, 1.0, 0.0), 30.0),
+        ),
+        "city_lights" => lit(
+            city_lights(),
+            View::new(Vec3::new(0.0, 25.0, 70.0), Vec3::new(0.0, 0.0, 0.0), 50.0),
+        ),
+        "light_linking" => lit_by(
+            light_linking(),
+            View::new(Vec3::new(0.0, 3.0, 10.0), Vec3::new(0.0, 1.0, 0.0), 40.0),
+        ),
+        "metal_presets" => lit(
+            metal_presets(),
+            View::new(Vec3::new(0.0, 3.0, 10.0), Vec3::new(0.0, 0.8, 0.0), 45.0),
+        ),
+        "glass_materials" => lit(
+            glass_materials(),
+            // Caustics are only found by bounces.
+            View {
+                samples_per_pixel: 2000,
+                ..View::new(Vec3::new(0.0, 2.0, 9.0), Vec3::new(0.0, 1.0, 0.0), 35.0)
+            },
+        ),
+        "principled_materials" => lit(
+            principled_materials(),
+            View::new(Vec3::new(0.0, 3.0, 12.0), Vec3::new(0.0, 1.0, 0.0), 40.0),
+        ),
+        "subsurface_materials" => {
+            let (list, lights) = subsurface_materials();
+            // Random walks take hundreds of steps inside the media, while
+            // paths between surfaces need no more than usual.
+            let limits = BounceLimits::new(8, 8, 16, 500);
+            let view = View {
+                samples_per_pixel: 2000,
+                ..View::new(Vec3::new(0.0, 2.5, 12.0), Vec3::new(0.0, 1.0, 0.0), 35.0)
+            };
+            let integrator =
+                PathTracer::new_with_limits(500, limits, 3, lights, view.time_1, view.time_2);
+            (list, integrator, view)
+        }
+        "layered_materials" => lit(
+            layered_materials(),
+            View::new(Vec3::new(0.0, 2.5, 12.0), Vec3::new(0.0, 1.0, 0.0), 35.0),
+        ),
+        "cloth_and_clay_materials" => lit(
+            cloth_and_clay_materials(),
+            View::new(Vec3::new(0.0, 2.5, 13.0), Vec3::new(0.0, 1.0, 0.0), 40.0),
+        ),
+        _ => panic!("Unknown scene {name}."),
+    }
+}
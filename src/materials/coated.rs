@@ -0,0 +1,441 @@
+use crate::image_utils::hittable::HitRecord;
+use crate::materials::materials::{
+    fresnel_dielectric, refract, BsdfSample, Dielectric, LobeFlags, Material,
+};
+use crate::utils::vec3::Vec3;
+use crate::utils::{power_heuristic, random_double};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Longest random walk between the coat and the base.
+const MAX_DEPTH: u32 = 10;
+// Random walks averaged by `eval` and `pdf`.
+const SAMPLES: u32 = 1;
+
+// Dielectric `coat` over a `base` material, for varnish, lacquer or the
+// clearcoat of car paint. Light bouncing between the two is followed by
+// random walks as in pbrt-v4's layered BSDFs, so `eval` and `pdf` give
+// unbiased estimates rather than exact values. The layer between them
+// absorbs following Beer-Lambert, `absorption` being its optical depth
+// straight across.
+//
+// Both faces of the surface are coated. The base is taken as opaque,
+// light it transmits being lost.
+pub struct Coated {
+    pub base: Arc<dyn Material + Send + Sync>,
+    pub coat: Dielectric,
+    pub absorption: Vec3,
+}
+
+impl Coated {
+    pub fn new(
+        base: Arc<dyn Material + Send + Sync>,
+        refraction_index: f32,
+        roughness: f32,
+    ) -> Coated {
+        Coated {
+            base,
+            coat: Dielectric::new_rough(refraction_index, roughness),
+            absorption: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Coat tinting light that crosses it straight down by `color`.
+    pub fn new_absorbing(
+        base: Arc<dyn Material + Send + Sync>,
+        refraction_index: f32,
+        roughness: f32,
+        color: Vec3,
+    ) -> Coated {
+        let depth = |c: f32| -c.clamp(1e-6, 1.0).ln();
+        Coated {
+            absorption: Vec3::new(depth(color.x), depth(color.y), depth(color.z)),
+            ..Coated::new(base, refraction_index, roughness)
+        }
+    }
+
+    // Transmittance of the layer crossed along `w`.
+    fn transmittance(&self, w: Vec3) -> Vec3 {
+        if w.z == 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let a = self.absorption / w.z.abs();
+        Vec3::new((-a.x).exp(), (-a.y).exp(), (-a.z).exp())
+    }
+}
+
+// The coat and the base at a hit. Directions below the coat have a
+// negative z, the coat being met from inside along them.
+struct Layers<'a> {
+    coated: &'a Coated,
+    hit_record: &'a HitRecord,
+    outside: HitRecord,
+    inside: HitRecord,
+}
+
+impl<'a> Layers<'a> {
+    fn new(coated: &'a Coated, hit_record: &'a HitRecord) -> Layers<'a> {
+        let mut outside = hit_record.clone();
+        outside.front_face = true;
+        let mut inside = hit_record.clone();
+        inside.front_face = false;
+        Layers {
+            coated,
+            hit_record,
+            outside,
+            inside,
+        }
+    }
+
+    fn is_smooth(&self) -> bool {
+        self.coated.coat.distribution.is_smooth()
+    }
+
+    fn coat_eval(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z >= 0.0 {
+            self.coated.coat.eval(&self.outside, wo, wi)
+        } else {
+            self.coated.coat.eval(&self.inside, flip(wo), flip(wi))
+        }
+    }
+
+    fn coat_sample(&self, wo: Vec3) -> Option<BsdfSample> {
+        let (uc, u) = (random_double(), (random_double(), random_double()));
+        if wo.z >= 0.0 {
+            return self.coated.coat.sample(&self.outside, wo, uc, u);
+        }
+        let mut sample = self.coated.coat.sample(&self.inside, flip(wo), uc, u)?;
+        sample.wi = flip(sample.wi);
+        Some(sample)
+    }
+
+    fn coat_pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        if wo.z >= 0.0 {
+            self.coated.coat.pdf(&self.outside, wo, wi)
+        } else {
+            self.coated.coat.pdf(&self.inside, flip(wo), flip(wi))
+        }
+    }
+
+    // Light entering the coat from `wi` outside, sampled from there.
+    fn coat_sample_in(&self, wi: Vec3) -> Option<BsdfSample> {
+        self.coat_sample(wi).filter(|sample| sample.wi.z < 0.0)
+    }
+
+    // Density of the walk `Coated::sample` takes from `wo` leaving towards
+    // `wi`, estimated by following one such walk and adding at each step
+    // the density of leaving towards `wi` at the next one.
+    fn walk_pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let coated = self.coated;
+        let mut pdf = self.coat_pdf(wo, wi);
+        let first = match self.coat_sample_in(wo) {
+            Some(first) => first,
+            None => return pdf,
+        };
+        let mut weight = first.weight;
+        let mut w = first.wi;
+        let mut at_base = false;
+        for depth in 0..MAX_DEPTH {
+            let survival = survival(depth, weight);
+            at_base = !at_base;
+            if !at_base {
+                pdf += survival * self.coat_pdf(-w, wi);
+            }
+            if random_double() >= survival {
+                break;
+            }
+            weight = weight / survival * coated.transmittance(w);
+
+            let sample = if at_base {
+                if self.is_smooth() && depth + 1 < MAX_DEPTH {
+                    pdf += self.smooth_exit_pdf(-w, wi, weight, depth + 1);
+                }
+                self.base_sample(-w)
+            } else {
+                self.coat_sample(-w).filter(|sample| sample.wi.z < 0.0)
+            };
+            match sample {
+                Some(sample) => {
+                    weight = weight * sample.weight;
+                    w = sample.wi;
+                }
+                None => break,
+            }
+        }
+        pdf
+    }
+
+    // Density of the base scattering light from `wo` into the one direction
+    // a smooth coat refracts into `wi`, and of the walk leaving through
+    // the coat at `depth`, `weight` being its weight before the base.
+    fn smooth_exit_pdf(&self, wo: Vec3, wi: Vec3, weight: Vec3, depth: u32) -> f32 {
+        let eta = self.coated.coat.refraction_index;
+        let w = match refract(wi, Vec3::new(0.0, 0.0, 1.0), eta) {
+            Some(w) => -w,
+            None => return 0.0,
+        };
+        let base = &self.coated.base;
+        let pdf = base.pdf(self.hit_record, wo, w);
+        if pdf <= 0.0 {
+            return 0.0;
+        }
+        let weight = weight * base.eval(self.hit_record, wo, w) / pdf;
+        let transmitted = 1.0 - fresnel_dielectric(-w.z, eta);
+        // Solid angles widen by eta² cos_inside / cos_outside on the way out.
+        let jacobian = wi.z / (eta * eta * w.z);
+        pdf * survival(depth, weight) * transmitted * jacobian
+    }
+
+    fn base_sample(&self, wo: Vec3) -> Option<BsdfSample> {
+        let (uc, u) = (random_double(), (random_double(), random_double()));
+        self.coated
+            .base
+            .sample(self.hit_record, wo, uc, u)
+            .filter(|sample| sample.wi.z > 0.0)
+    }
+}
+
+fn flip(w: Vec3) -> Vec3 {
+    Vec3::new(w.x, w.y, -w.z)
+}
+
+// Chance of a walk carrying `weight` going on at `depth`, Russian roulette
+// ending those carrying little light after a few steps.
+fn survival(depth: u32, weight: Vec3) -> f32 {
+    let max = weight.x.max(weight.y).max(weight.z);
+    if depth > 3 && max < 0.25 {
+        max.max(0.0)
+    } else {
+        1.0
+    }
+}
+
+impl Material for Coated {
+    fn eval(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let layers = Layers::new(self, hit_record);
+        let base = &self.base;
+        let base_non_specular = base.lobes().is_non_specular();
+        let eta = self.coat.refraction_index;
+        let mut value = SAMPLES as f32 * layers.coat_eval(wo, wi);
+
+        for _ in 0..SAMPLES {
+            let wos = match layers.coat_sample_in(wo) {
+                Some(wos) => wos,
+                None => continue,
+            };
+            // Light from `wi` crossing the coat, as if sampled from inside
+            // towards `wi`: radiance is not compressed the same way both
+            // ways through the interface.
+            let wis = layers.coat_sample_in(wi).map(|wis| {
+                let weight = wis.weight * eta * eta * wi.z / wis.wi.z.abs();
+                (wis, weight)
+            });
+            let mut beta = wos.weight;
+            let mut w = wos.wi;
+            let mut at_base = false;
+            for depth in 0..MAX_DEPTH {
+                let survival = survival(depth, beta);
+                if random_double() >= survival {
+                    break;
+                }
+                beta = beta / survival * self.transmittance(w);
+                at_base = !at_base;
+
+                if !at_base {
+                    // Light leaving through the coat was counted at the base.
+                    match layers.coat_sample(-w).filter(|sample| sample.wi.z < 0.0) {
+                        Some(sample) => {
+                            beta = beta * sample.weight;
+                            w = sample.wi;
+                        }
+                        None => break,
+                    }
+                    continue;
+                }
+
+                // Light from `wi` reaching the base through `wis`.
+                if let Some((wis, wis_weight)) = wis.as_ref().filter(|_| base_non_specular) {
+                    let reflected = base.eval(hit_record, -w, -wis.wi);
+                    if !reflected.near_zero() {
+                        let weight = if layers.is_smooth() {
+                            1.0
+                        } else {
+                            power_heuristic(wis.pdf, base.pdf(hit_record, -w, -wis.wi))
+                        };
+                        value = value
+                            + weight * beta * reflected * self.transmittance(wis.wi) * *wis_weight;
+                    }
+                }
+
+                let sample = match layers.base_sample(-w) {
+                    Some(sample) => sample,
+                    None => break,
+                };
+                beta = beta * sample.weight;
+                w = sample.wi;
+
+                // Light from `wi` reaching the base along the sampled
+                // direction.
+                if !layers.is_smooth() {
+                    let exit = layers.coat_eval(-w, wi);
+                    if !exit.near_zero() {
+                        let weight = if sample.flags.contains(LobeFlags::SPECULAR) {
+                            1.0
+                        } else {
+                            power_heuristic(sample.pdf, layers.coat_pdf(wi, -w))
+                        };
+                        value = value + weight * beta * self.transmittance(w) * exit;
+                    }
+                }
+            }
+        }
+        value / SAMPLES as f32
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let layers = Layers::new(self, hit_record);
+        let first = self.coat.sample(&layers.outside, wo, uc, u)?;
+        if first.wi.z > 0.0 {
+            if first.flags.contains(LobeFlags::SPECULAR) {
+                return Some(first);
+            }
+            // The weight follows the path taken, the density all of them.
+            let pdf = self.pdf(hit_record, wo, first.wi);
+            return Some(BsdfSample::new(first.wi, first.weight, pdf, first.flags));
+        }
+
+        let mut weight = first.weight;
+        let mut pdf = first.pdf;
+        let mut w = first.wi;
+        let mut specular = first.flags.contains(LobeFlags::SPECULAR);
+        let mut diffuse = false;
+        let mut at_base = false;
+        for depth in 0..MAX_DEPTH {
+            let survival = survival(depth, weight);
+            if random_double() >= survival {
+                return None;
+            }
+            weight = weight / survival * self.transmittance(w);
+            at_base = !at_base;
+
+            let sample = if at_base {
+                layers.base_sample(-w)?
+            } else {
+                layers.coat_sample(-w)?
+            };
+            weight = weight * sample.weight;
+            pdf *= sample.pdf;
+            specular &= sample.flags.contains(LobeFlags::SPECULAR);
+            diffuse |= sample.flags.contains(LobeFlags::DIFFUSE);
+            w = sample.wi;
+
+            if !at_base && w.z > 0.0 {
+                let (lobe, pdf) = if specular {
+                    (LobeFlags::SPECULAR, pdf)
+                } else {
+                    let lobe = if diffuse {
+                        LobeFlags::DIFFUSE
+                    } else {
+                        LobeFlags::GLOSSY
+                    };
+                    (lobe, self.pdf(hit_record, wo, w))
+                };
+                return Some(BsdfSample::new(
+                    w,
+                    weight,
+                    pdf,
+                    lobe | LobeFlags::REFLECTION,
+                ));
+            }
+        }
+        None
+    }
+
+    // Estimate of the density of `sample`, mixed with a uniform one since
+    // a single walk often misses `wi`.
+    fn pdf(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let layers = Layers::new(self, hit_record);
+        let sum: f32 = (0..SAMPLES).map(|_| layers.walk_pdf(wo, wi)).sum();
+        0.1 / (2.0 * PI) + 0.9 * sum / SAMPLES as f32
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        let coat = if self.coat.distribution.is_smooth() {
+            LobeFlags::SPECULAR
+        } else {
+            LobeFlags::GLOSSY
+        };
+        let base = self.base.lobes();
+        let mut lobes = LobeFlags::REFLECTION | coat;
+        if base.contains(LobeFlags::DIFFUSE) {
+            lobes = lobes | LobeFlags::DIFFUSE;
+        }
+        if base.contains(LobeFlags::GLOSSY) {
+            lobes = lobes | LobeFlags::GLOSSY;
+        }
+        // A rough coat blurs a mirror base.
+        if base.contains(LobeFlags::SPECULAR) {
+            lobes = lobes | coat;
+        }
+        lobes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::{Lambertian, Metal};
+    use crate::materials::testing::{assert_close, integrals, outgoing_directions};
+
+    // `eval` and `pdf` being estimates, only their integrals can be checked:
+    // samples average to the integral of `eval`, and `pdf` integrates to the
+    // chance of sampling it, mixed with the uniform density.
+    fn check_coated(coated: &Coated, tolerance: f32) {
+        for wo in outgoing_directions() {
+            let integrals = integrals(coated, wo);
+            assert_close(
+                integrals.sampled,
+                integrals.eval,
+                0.03,
+                &format!("sampled albedo from {wo:?}"),
+            );
+            let expected = 0.1 + 0.9 * integrals.non_specular;
+            assert!(
+                (integrals.pdf - expected).abs() < tolerance,
+                "pdf from {wo:?} integrates to {} instead of {expected}",
+                integrals.pdf
+            );
+        }
+    }
+
+    fn orange() -> Arc<dyn Material + Send + Sync> {
+        Arc::new(Lambertian::new(Vec3::new(0.8, 0.5, 0.2)))
+    }
+
+    #[test]
+    fn smooth_coat_samples_match_eval() {
+        check_coated(&Coated::new(orange(), 1.5, 0.0), 0.02);
+        check_coated(&Coated::new(Arc::new(Metal::gold(0.5)), 1.5, 0.0), 0.02);
+    }
+
+    #[test]
+    fn rough_coat_samples_match_eval() {
+        // Walks through a rough coat estimate the density more loosely.
+        check_coated(&Coated::new(orange(), 1.5, 0.6), 0.06);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::image_utils::hittable::HitRecord;
+use crate::materials::materials::{fresnel_dielectric, BsdfSample, LobeFlags, Material};
+use crate::utils::vec3::Vec3;
+use std::f32::consts::{LN_2, PI};
+
+// Number of explicitly modelled scattering lobes: R, TT and TRT. Higher
+// order paths are lumped together in one isotropic lobe.
+const P_MAX: usize = 3;
+
+const EUMELANIN_SIGMA_A: Vec3 = Vec3 {
+    x: 0.419,
+    y: 0.697,
+    z: 1.37,
+};
+const PHEOMELANIN_SIGMA_A: Vec3 = Vec3 {
+    x: 0.187,
+    y: 0.4,
+    z: 1.05,
+};
+
+// Hair fiber scattering from Chiang et al. 2016, as in pbrt-v3. Meant for
+// `Curve`s: the fiber axis is the hit tangent (x of the shading frame) and
+// `v` gives the offset across the fiber. `beta_m` and `beta_n` are the
+// longitudinal and azimuthal roughness in [0, 1], `alpha` the tilt of the
+// cuticle scales in degrees.
+pub struct Hair {
+    pub sigma_a: Vec3,
+    pub eta: f32,
+    // Longitudinal variance of each lobe.
+    v: [f32; P_MAX + 1],
+    // Azimuthal logistic scale.
+    s: f32,
+    sin_2k_alpha: [f32; 3],
+    cos_2k_alpha: [f32; 3],
+}
+
+impl Hair {
+    pub fn new(sigma_a: Vec3, eta: f32, beta_m: f32, beta_n: f32, alpha: f32) -> Hair {
+        let v0 = (0.726 * beta_m + 0.812 * beta_m * beta_m + 3.7 * beta_m.powi(20)).powi(2);
+        let v = [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0];
+        let s = (PI / 8.0).sqrt()
+            * (0.265 * beta_n + 1.194 * beta_n * beta_n + 5.372 * beta_n.powi(22));
+        let mut sin_2k_alpha = [alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [safe_sqrt(1.0 - sin_2k_alpha[0] * sin_2k_alpha[0]), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        Hair {
+            sigma_a,
+            eta,
+            v,
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    // Absorption from eumelanin (brown to black) and pheomelanin (red)
+    // concentrations, typically between 0 and 8.
+    pub fn from_melanin(eumelanin: f32, pheomelanin: f32, beta_m: f32, beta_n: f32) -> Hair {
+        let sigma_a = eumelanin * EUMELANIN_SIGMA_A + pheomelanin * PHEOMELANIN_SIGMA_A;
+        Hair::new(sigma_a, 1.55, beta_m, beta_n, 2.0)
+    }
+
+    // Absorption giving roughly `color` as the multiply scattered albedo.
+    pub fn from_color(color: Vec3, beta_m: f32, beta_n: f32) -> Hair {
+        let denominator = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4)
+            + 0.245 * beta_n.powi(5);
+        let sigma_a = |c: f32| (c.max(1e-4).ln() / denominator).powi(2);
+        Hair::new(
+            Vec3::new(sigma_a(color.x), sigma_a(color.y), sigma_a(color.z)),
+            1.55,
+            beta_m,
+            beta_n,
+            2.0,
+        )
+    }
+
+    // Longitudinal angles of `wo` tilted by the scales for lobe `p`.
+    fn tilt(&self, p: usize, sin_theta_o: f32, cos_theta_o: f32) -> (f32, f32) {
+        let (sin, cos) = match p {
+            0 => (
+                sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (sin, cos.abs())
+    }
+
+    // Attenuation of each lobe and the refracted azimuth `gamma_t` for a
+    // ray leaving along `sin_theta_o` at offset `h`.
+    fn attenuation(&self, sin_theta_o: f32, cos_theta_o: f32, h: f32) -> ([Vec3; P_MAX + 1], f32) {
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = h / etap;
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let gamma_t = safe_asin(sin_gamma_t);
+        let transmittance = exp(-(2.0 * cos_gamma_t / cos_theta_t) * self.sigma_a);
+        (ap(cos_theta_o, self.eta, h, transmittance), gamma_t)
+    }
+
+    // BSDF value times |cos theta_i| and the sampling density, in the fiber
+    // frame where x runs along the fiber.
+    fn evaluate(&self, wo: Vec3, wi: Vec3, h: f32) -> (Vec3, f32) {
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.y.atan2(wo.z);
+        let sin_theta_i = wi.x;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+        let phi_i = wi.y.atan2(wi.z);
+        let gamma_o = safe_asin(h);
+        let phi = phi_i - phi_o;
+
+        let (ap, gamma_t) = self.attenuation(sin_theta_o, cos_theta_o, h);
+        let ap_pdf = lobe_pdf(&ap);
+        let mut value = Vec3::new(0.0, 0.0, 0.0);
+        let mut pdf = 0.0;
+        for p in 0..P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+            let longitudinal = mp(
+                cos_theta_i,
+                cos_theta_op,
+                sin_theta_i,
+                sin_theta_op,
+                self.v[p],
+            );
+            let azimuthal = np(phi, p, self.s, gamma_o, gamma_t);
+            value = value + longitudinal * azimuthal * ap[p];
+            pdf += longitudinal * azimuthal * ap_pdf[p];
+        }
+        let longitudinal = mp(
+            cos_theta_i,
+            cos_theta_o,
+            sin_theta_i,
+            sin_theta_o,
+            self.v[P_MAX],
+        );
+        value = value + longitudinal / (2.0 * PI) * ap[P_MAX];
+        pdf += longitudinal / (2.0 * PI) * ap_pdf[P_MAX];
+        (value, pdf)
+    }
+
+    // `uc` picks the lobe, then is stretched back over [0, 1) for the
+    // azimuth within it, as in pbrt-v4.
+    fn sample_direction(&self, wo: Vec3, h: f32, uc: f32, u: (f32, f32)) -> Vec3 {
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.y.atan2(wo.z);
+        let gamma_o = safe_asin(h);
+
+        let (ap, gamma_t_o) = self.attenuation(sin_theta_o, cos_theta_o, h);
+        let ap_pdf = lobe_pdf(&ap);
+        let mut uc = uc;
+        let mut p = 0;
+        while p < P_MAX && uc >= ap_pdf[p] {
+            uc -= ap_pdf[p];
+            p += 1;
+        }
+        let uc = (uc / ap_pdf[p]).clamp(0.0, 1.0 - f32::EPSILON);
+
+        let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+        let u0 = u.0.max(1e-5);
+        let cos_theta = 1.0 + self.v[p] * (u0 + (1.0 - u0) * (-2.0 / self.v[p]).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (2.0 * PI * u.1).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        let delta_phi = if p < P_MAX {
+            phi(p, gamma_o, gamma_t_o) + sample_trimmed_logistic(uc, self.s, -PI, PI)
+        } else {
+            2.0 * PI * uc
+        };
+        let phi_i = phi_o + delta_phi;
+        Vec3::new(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        )
+    }
+}
+
+impl Material for Hair {
+    fn eval(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        self.evaluate(wo, wi, fiber_offset(hit_record)).0
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let h = fiber_offset(hit_record);
+        let wi = self.sample_direction(wo, h, uc, u);
+        let (value, pdf) = self.evaluate(wo, wi, h);
+        if pdf <= 0.0 || !pdf.is_finite() {
+            return None;
+        }
+        let side = if wi.z >= 0.0 {
+            LobeFlags::REFLECTION
+        } else {
+            LobeFlags::TRANSMISSION
+        };
+        Some(BsdfSample::new(
+            wi,
+            value / pdf,
+            pdf,
+            LobeFlags::GLOSSY | side,
+        ))
+    }
+
+    fn pdf(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        self.evaluate(wo, wi, fiber_offset(hit_record)).1
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        LobeFlags::GLOSSY | LobeFlags::REFLECTION | LobeFlags::TRANSMISSION
+    }
+}
+
+// Offset across the fiber in [-1, 1]. The azimuths only matter relative to
+// `wo`, so any shading frame with x along the fiber works.
+fn fiber_offset(hit_record: &HitRecord) -> f32 {
+    (-1.0 + 2.0 * hit_record.v).clamp(-1.0, 1.0)
+}
+
+fn safe_sqrt(x: f32) -> f32 {
+    x.max(0.0).sqrt()
+}
+
+fn safe_asin(x: f32) -> f32 {
+    x.clamp(-1.0, 1.0).asin()
+}
+
+fn exp(v: Vec3) -> Vec3 {
+    Vec3::new(v.x.exp(), v.y.exp(), v.z.exp())
+}
+
+// Modified Bessel function of the first kind, order zero.
+fn i0(x: f32) -> f32 {
+    let mut value = 0.0;
+    let mut x2i = 1.0;
+    let mut factorial: f32 = 1.0;
+    let mut four_i = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as f32;
+        }
+        value += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4.0;
+    }
+    value
+}
+
+fn log_i0(x: f32) -> f32 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+// Longitudinal scattering.
+fn mp(cos_theta_i: f32, cos_theta_o: f32, sin_theta_i: f32, sin_theta_o: f32, v: f32) -> f32 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        (log_i0(a) - b - 1.0 / v + LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+// Attenuation of the R, TT, TRT and remaining lobes.
+fn ap(cos_theta_o: f32, eta: f32, h: f32, transmittance: Vec3) -> [Vec3; P_MAX + 1] {
+    let cos_gamma_o = safe_sqrt(1.0 - h * h);
+    let f = fresnel_dielectric(cos_theta_o * cos_gamma_o, eta);
+    let mut ap = [Vec3::new(0.0, 0.0, 0.0); P_MAX + 1];
+    ap[0] = Vec3::new(f, f, f);
+    ap[1] = (1.0 - f) * (1.0 - f) * transmittance;
+    for p in 2..P_MAX {
+        ap[p] = f * ap[p - 1] * transmittance;
+    }
+    let remainder = f * transmittance;
+    ap[P_MAX] = ap[P_MAX - 1] * remainder
+        / Vec3::new(1.0 - remainder.x, 1.0 - remainder.y, 1.0 - remainder.z);
+    ap
+}
+
+// Probability of sampling each lobe, from its share of the attenuation.
+fn lobe_pdf(ap: &[Vec3; P_MAX + 1]) -> [f32; P_MAX + 1] {
+    let total: f32 = ap.iter().map(|a| a.luminance()).sum();
+    ap.map(|a| a.luminance() / total)
+}
+
+// Net change in azimuth of lobe `p`.
+fn phi(p: usize, gamma_o: f32, gamma_t: f32) -> f32 {
+    2.0 * p as f32 * gamma_t - 2.0 * gamma_o + p as f32 * PI
+}
+
+fn logistic(x: f32, s: f32) -> f32 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f32, s: f32) -> f32 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: f32, s: f32, a: f32, b: f32) -> f32 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f32, s: f32, a: f32, b: f32) -> f32 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+    x.clamp(a, b)
+}
+
+// Azimuthal scattering.
+fn np(phi_difference: f32, p: usize, s: f32, gamma_o: f32, gamma_t: f32) -> f32 {
+    let mut delta = phi_difference - phi(p, gamma_o, gamma_t);
+    while delta > PI {
+        delta -= 2.0 * PI;
+    }
+    while delta < -PI {
+        delta += 2.0 * PI;
+    }
+    trimmed_logistic(delta, s, -PI, PI)
+}
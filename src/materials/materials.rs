@@ -1,15 +1,159 @@
 use crate::image_utils::hittable::HitRecord;
-use crate::image_utils::ray::Ray;
 use crate::image_utils::texture::{SolidColor, Texture};
-use crate::utils::random_double;
+use crate::lights::ies::IesProfile;
+use crate::materials::microfacet::TrowbridgeReitz;
+use crate::utils::onb::ONB;
 use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::ops::BitOr;
 use std::sync::Arc;
 
+// Scattering at a surface or medium point. Directions are unit vectors in
+// the local shading frame of the hit (`HitRecord::shading_frame`), where z is
+// the shading normal; both `wo` (towards the viewer) and `wi` (towards the
+// light) point away from the point, so `wo.z` is never negative.
 pub trait Material {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Ray, Vec3)>;
-    fn emitted(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+    // BSDF times |cos theta_i|. Zero for specular lobes, which can only be
+    // sampled.
+    fn eval(&self, _hit_record: &HitRecord, _wo: Vec3, _wi: Vec3) -> Vec3 {
         Vec3::new(0.0, 0.0, 0.0)
     }
+
+    // Picks `wi` from uniform numbers, `uc` choosing between lobes and `u`
+    // the direction in the chosen one, as in pbrt-v4. `None` when the path
+    // is absorbed.
+    fn sample(
+        &self,
+        _hit_record: &HitRecord,
+        _wo: Vec3,
+        _uc: f32,
+        _u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        None
+    }
+
+    // Density, in solid angle, of `sample` picking `wi` from its non
+    // specular lobes.
+    fn pdf(&self, _hit_record: &HitRecord, _wo: Vec3, _wi: Vec3) -> f32 {
+        0.0
+    }
+
+    // Every lobe `sample` may pick from.
+    fn lobes(&self) -> LobeFlags {
+        LobeFlags::NONE
+    }
+
+    // Radiance leaving the surface towards `wo`.
+    fn emitted(&self, _hit_record: &HitRecord, _wo: Vec3) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    // Bounds on `emitted` anywhere on the surface, `None` when nothing is
+    // emitted.
+    fn emission_bounds(&self) -> Option<EmissionBounds> {
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LobeFlags(u8);
+
+impl LobeFlags {
+    pub const NONE: LobeFlags = LobeFlags(0);
+    pub const REFLECTION: LobeFlags = LobeFlags(1);
+    pub const TRANSMISSION: LobeFlags = LobeFlags(1 << 1);
+    pub const DIFFUSE: LobeFlags = LobeFlags(1 << 2);
+    pub const GLOSSY: LobeFlags = LobeFlags(1 << 3);
+    pub const SPECULAR: LobeFlags = LobeFlags(1 << 4);
+    // Scattering inside a participating medium rather than at a surface.
+    pub const VOLUME: LobeFlags = LobeFlags(1 << 5);
+
+    pub fn contains(self, other: LobeFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: LobeFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    // Whether some lobe can be evaluated, which light sampling needs.
+    pub fn is_non_specular(self) -> bool {
+        self.intersects(LobeFlags::DIFFUSE | LobeFlags::GLOSSY | LobeFlags::VOLUME)
+    }
+}
+
+impl BitOr for LobeFlags {
+    type Output = LobeFlags;
+
+    fn bitor(self, other: LobeFlags) -> LobeFlags {
+        LobeFlags(self.0 | other.0)
+    }
+}
+
+pub struct BsdfSample {
+    pub wi: Vec3,
+    // BSDF times |cos theta_i| over the pdf.
+    pub weight: Vec3,
+    // Density of `wi`; for specular lobes the probability of picking them.
+    pub pdf: f32,
+    pub flags: LobeFlags,
+}
+
+impl BsdfSample {
+    pub fn new(wi: Vec3, weight: Vec3, pdf: f32, flags: LobeFlags) -> BsdfSample {
+        BsdfSample {
+            wi,
+            weight,
+            pdf,
+            flags,
+        }
+    }
+}
+
+// `radiance` bounds the largest channel of the emission, which is zero
+// further than `cos_theta_e` from the normal and on the inward facing side
+// unless `two_sided`.
+#[derive(Debug, Copy, Clone)]
+pub struct EmissionBounds {
+    pub radiance: f32,
+    pub cos_theta_e: f32,
+    pub two_sided: bool,
+}
+
+impl EmissionBounds {
+    pub fn new(radiance: f32, cos_theta_e: f32, two_sided: bool) -> EmissionBounds {
+        EmissionBounds {
+            radiance,
+            cos_theta_e,
+            two_sided,
+        }
+    }
+
+    pub fn union(&self, other: &EmissionBounds) -> EmissionBounds {
+        EmissionBounds::new(
+            self.radiance.max(other.radiance),
+            self.cos_theta_e.min(other.cos_theta_e),
+            self.two_sided || other.two_sided,
+        )
+    }
+}
+
+fn reflect(wo: Vec3) -> Vec3 {
+    Vec3::new(-wo.x, -wo.y, wo.z)
+}
+
+// Cosine weighted direction on the hemisphere around z.
+pub fn sample_cosine_hemisphere(u: (f32, f32)) -> Vec3 {
+    let r = u.0.sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - u.0).max(0.0).sqrt())
+}
+
+pub fn sample_uniform_sphere(u: (f32, f32)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
 pub struct Lambertian {
@@ -26,115 +170,817 @@ impl Lambertian {
     pub fn from_texture(texture: Arc<dyn Texture + Send + Sync>) -> Lambertian {
         Lambertian { texture }
     }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        self.texture
+            .value(hit_record.u, hit_record.v, hit_record.position)
+    }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit_record: &HitRecord) -> Option<(Ray, Vec3)> {
-        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = hit_record.normal;
-        }
-        let scattered = Ray::new(hit_record.position, scatter_direction, _ray.time);
-        Some((
-            scattered,
-            self.texture
-                .value(hit_record.u, hit_record.v, hit_record.position),
+    fn eval(&self, hit_record: &HitRecord, _wo: Vec3, wi: Vec3) -> Vec3 {
+        wi.z.max(0.0) / PI * self.albedo(hit_record)
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        _wo: Vec3,
+        _uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let wi = sample_cosine_hemisphere(u);
+        Some(BsdfSample::new(
+            wi,
+            self.albedo(hit_record),
+            wi.z / PI,
+            LobeFlags::DIFFUSE | LobeFlags::REFLECTION,
         ))
     }
+
+    fn pdf(&self, _hit_record: &HitRecord, _wo: Vec3, wi: Vec3) -> f32 {
+        wi.z.max(0.0) / PI
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        LobeFlags::DIFFUSE | LobeFlags::REFLECTION
+    }
 }
 
+// Rough diffuse surface after Oren and Nayar, made of V-shaped Lambertian
+// facets whose slope angles have a standard deviation of `sigma` degrees,
+// for clay, concrete or plaster. Rougher surfaces look flatter, reflecting
+// more back towards the light. A `sigma` of 0 is `Lambertian`.
+pub struct OrenNayar {
+    pub texture: Arc<dyn Texture + Send + Sync>,
+    a: f32,
+    b: f32,
+}
+
+impl OrenNayar {
+    pub fn new(color: Vec3, sigma: f32) -> OrenNayar {
+        OrenNayar::from_texture(Arc::new(SolidColor::new(color)), sigma)
+    }
+
+    pub fn from_texture(texture: Arc<dyn Texture + Send + Sync>, sigma: f32) -> OrenNayar {
+        let sigma2 = sigma.to_radians().powi(2);
+        OrenNayar {
+            texture,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        self.texture
+            .value(hit_record.u, hit_record.v, hit_record.position)
+    }
+}
+
+impl Material for OrenNayar {
+    fn eval(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wi.z <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let sin_o = (1.0 - wo.z * wo.z).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z * wi.z).max(0.0).sqrt();
+        // cos(phi_i - phi_o) from the directions projected on the surface,
+        // only its positive part brightening the surface.
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.x * wi.x + wo.y * wi.y) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+        // sin(alpha) tan(beta), alpha and beta being the larger and smaller
+        // of the two angles to the normal.
+        let (sin_alpha, tan_beta) = if wi.z.abs() > wo.z.abs() {
+            (sin_o, sin_i / wi.z.abs())
+        } else {
+            (sin_i, sin_o / wo.z.abs().max(1e-4))
+        };
+        let factor = self.a + self.b * cos_phi * sin_alpha * tan_beta;
+        wi.z / PI * factor * self.albedo(hit_record)
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        _uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let wi = sample_cosine_hemisphere(u);
+        let pdf = wi.z / PI;
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample::new(
+            wi,
+            self.eval(hit_record, wo, wi) / pdf,
+            pdf,
+            LobeFlags::DIFFUSE | LobeFlags::REFLECTION,
+        ))
+    }
+
+    fn pdf(&self, _hit_record: &HitRecord, _wo: Vec3, wi: Vec3) -> f32 {
+        wi.z.max(0.0) / PI
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        LobeFlags::DIFFUSE | LobeFlags::REFLECTION
+    }
+}
+
+// Retroreflective fibres standing up from a surface, for velvet, satin or
+// the fuzz of cloth, which brighten at grazing angles. The microfacet
+// distribution and shadowing are Estevez and Kulla's "Charlie" sheen,
+// `roughness` going from 0 (tight rims) to 1.
+// The lobe reflects only part of the light; mix it with a diffuse material
+// for the cloth underneath.
+pub struct Sheen {
+    pub texture: Arc<dyn Texture + Send + Sync>,
+    pub roughness: f32,
+}
+
+impl Sheen {
+    pub fn new(color: Vec3, roughness: f32) -> Sheen {
+        Sheen::from_texture(Arc::new(SolidColor::new(color)), roughness)
+    }
+
+    pub fn from_texture(texture: Arc<dyn Texture + Send + Sync>, roughness: f32) -> Sheen {
+        Sheen {
+            texture,
+            roughness: roughness.clamp(0.01, 1.0),
+        }
+    }
+
+    // Masking of the fibres seen at `cos_theta` from the normal, from
+    // Estevez and Kulla's fit.
+    fn lambda(&self, cos_theta: f32) -> f32 {
+        let t = (1.0 - self.roughness).powi(2);
+        let fit = |x: f32| {
+            let a = 21.5473 + (25.3245 - 21.5473) * t;
+            let b = 3.82987 + (3.32435 - 3.82987) * t;
+            let c = 0.19823 + (0.16801 - 0.19823) * t;
+            let d = -1.97760 + (-1.27393 + 1.97760) * t;
+            let e = -4.32054 + (-4.85967 + 4.32054) * t;
+            a / (1.0 + b * x.powf(c)) + d * x + e
+        };
+        if cos_theta < 0.5 {
+            fit(cos_theta).exp()
+        } else {
+            (2.0 * fit(0.5) - fit(1.0 - cos_theta)).exp()
+        }
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        self.texture
+            .value(hit_record.u, hit_record.v, hit_record.position)
+    }
+}
+
+impl Material for Sheen {
+    fn eval(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wi.z <= 0.0 || wo.z <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let h = wo + wi;
+        if h.near_zero() {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let cos_h = h.unit_vector().z;
+        let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+        let inv = 1.0 / self.roughness;
+        let d = (2.0 + inv) * sin_h.powf(inv) / (2.0 * PI);
+        // Shadowing from the light side is softened near the terminator.
+        let lambda_o = self.lambda(wo.z);
+        let lambda_i = self.lambda(wi.z).powf(1.0 + 2.0 * (1.0 - wi.z).powi(8));
+        let g = 1.0 / (1.0 + lambda_o + lambda_i);
+        d * g / (4.0 * wo.z) * self.albedo(hit_record)
+    }
+
+    // Uniform over the hemisphere, which covers grazing directions better
+    // than cosine weighting.
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        _uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let z = 1.0 - u.0;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let wi = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+        let pdf = 1.0 / (2.0 * PI);
+        Some(BsdfSample::new(
+            wi,
+            self.eval(hit_record, wo, wi) / pdf,
+            pdf,
+            LobeFlags::GLOSSY | LobeFlags::REFLECTION,
+        ))
+    }
+
+    fn pdf(&self, _hit_record: &HitRecord, _wo: Vec3, wi: Vec3) -> f32 {
+        if wi.z > 0.0 {
+            1.0 / (2.0 * PI)
+        } else {
+            0.0
+        }
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        LobeFlags::GLOSSY | LobeFlags::REFLECTION
+    }
+}
+
+// Conductor with a GGX microfacet surface (see `TrowbridgeReitz`), the
+// Fresnel reflectance following the complex index of refraction
+// `eta` + i`k` of each channel. Smooth surfaces reflect as a mirror.
 pub struct Metal {
-    pub albedo: Vec3,
-    pub fuzz: f32,
+    pub eta: Vec3,
+    pub k: Vec3,
+    pub distribution: TrowbridgeReitz,
 }
 
 impl Metal {
-    pub fn new(albedo: Vec3, fuzz: f32) -> Metal {
+    // Metal reflecting `albedo` at normal incidence, rising to white at
+    // grazing angles. The roughness goes from 0 (a mirror) to 1.
+    pub fn new(albedo: Vec3, roughness: f32) -> Metal {
+        Metal::new_anisotropic(albedo, roughness, 0.0)
+    }
+
+    // As `new`, the highlights stretched along the surface tangent by
+    // `anisotropy` in [0, 1).
+    pub fn new_anisotropic(albedo: Vec3, roughness: f32, anisotropy: f32) -> Metal {
+        // With eta 1 the reflectance at normal incidence is k² / (4 + k²).
+        let k = |r: f32| {
+            let r = r.clamp(0.0, 0.999);
+            2.0 * (r / (1.0 - r)).sqrt()
+        };
+        Metal::from_ior(
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(k(albedo.x), k(albedo.y), k(albedo.z)),
+            roughness,
+            anisotropy,
+        )
+    }
+
+    pub fn from_ior(eta: Vec3, k: Vec3, roughness: f32, anisotropy: f32) -> Metal {
         Metal {
-            albedo,
-            fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
+            eta,
+            k,
+            distribution: TrowbridgeReitz::from_roughness(roughness, anisotropy),
         }
     }
+
+    // Measured indices averaged over the red, green and blue parts of the
+    // spectrum.
+    pub fn gold(roughness: f32) -> Metal {
+        Metal::from_ior(
+            Vec3::new(0.143, 0.375, 1.442),
+            Vec3::new(3.983, 2.386, 1.603),
+            roughness,
+            0.0,
+        )
+    }
+
+    pub fn copper(roughness: f32) -> Metal {
+        Metal::from_ior(
+            Vec3::new(0.200, 0.924, 1.102),
+            Vec3::new(3.913, 2.453, 2.142),
+            roughness,
+            0.0,
+        )
+    }
+
+    pub fn aluminium(roughness: f32) -> Metal {
+        Metal::from_ior(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+            roughness,
+            0.0,
+        )
+    }
+
+    pub fn silver(roughness: f32) -> Metal {
+        Metal::from_ior(
+            Vec3::new(0.155, 0.117, 0.138),
+            Vec3::new(4.828, 3.122, 2.147),
+            roughness,
+            0.0,
+        )
+    }
+
+    fn fresnel(&self, cos_theta: f32) -> Vec3 {
+        Vec3::new(
+            fresnel_conductor(cos_theta, self.eta.x, self.k.x),
+            fresnel_conductor(cos_theta, self.eta.y, self.k.y),
+            fresnel_conductor(cos_theta, self.eta.z, self.k.z),
+        )
+    }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Ray, Vec3)> {
-        let reflected = ray.direction.unit_vector().reflect(hit_record.normal);
-        let scattered = Ray::new(
-            hit_record.position,
-            reflected + self.fuzz * Vec3::random_in_unit_sphere(),
-            ray.time,
-        );
-        if scattered.direction.dot(hit_record.normal) > 0.0 {
-            Some((scattered, self.albedo))
+    fn eval(&self, _hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        if self.distribution.is_smooth() || wo.z <= 0.0 || wi.z <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let wm = wo + wi;
+        if wm.near_zero() {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let wm = wm.unit_vector();
+        let d = &self.distribution;
+        // BSDF D F G / (4 cos_o cos_i), times cos_i.
+        d.d(wm) * d.g(wo, wi) / (4.0 * wo.z) * self.fresnel(wo.dot(wm).abs())
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        _uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        if self.distribution.is_smooth() {
+            return Some(BsdfSample::new(
+                reflect(wo),
+                self.fresnel(wo.z),
+                1.0,
+                LobeFlags::SPECULAR | LobeFlags::REFLECTION,
+            ));
+        }
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let wm = self.distribution.sample_wm(wo, u);
+        let wi = -wo + 2.0 * wo.dot(wm) * wm;
+        if wi.z <= 0.0 {
+            return None;
+        }
+        let pdf = self.pdf(hit_record, wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample::new(
+            wi,
+            self.eval(hit_record, wo, wi) / pdf,
+            pdf,
+            LobeFlags::GLOSSY | LobeFlags::REFLECTION,
+        ))
+    }
+
+    // Visible normal density over the Jacobian of reflection.
+    fn pdf(&self, _hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        if self.distribution.is_smooth() || wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = wo + wi;
+        if wm.near_zero() {
+            return 0.0;
+        }
+        let wm = wm.unit_vector();
+        self.distribution.pdf(wo, wm) / (4.0 * wo.dot(wm).abs())
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        if self.distribution.is_smooth() {
+            LobeFlags::SPECULAR | LobeFlags::REFLECTION
         } else {
-            None
+            LobeFlags::GLOSSY | LobeFlags::REFLECTION
         }
     }
 }
 
+// Interface between the outside and a medium of index `refraction_index`,
+// with exact Fresnel reflectance. A rough `distribution` scatters both
+// reflection and transmission over GGX microfacets, as pbrt-v4 does.
+//
+// Light is absorbed inside following Beer-Lambert with the coefficient
+// `absorption` per unit of distance, over the segment that reached a back
+// face: paths travel along unit directions and that segment ran inside,
+// which holds for closed objects that do not overlap others.
+//
+// A `thin` interface is a sheet of glass, like a window, passing light
+// straight through after the reflections between its two faces. It is
+// always smooth and does not absorb.
 pub struct Dielectric {
     pub refraction_index: f32,
+    pub distribution: TrowbridgeReitz,
+    pub thin: bool,
+    pub absorption: Vec3,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f32) -> Dielectric {
-        Dielectric { refraction_index }
+        Dielectric::new_rough(refraction_index, 0.0)
     }
 
-    pub fn reflectance(cosine: f32, refraction_index: f32) -> f32 {
-        let r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
-        let r0 = r0 * r0;
-        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    pub fn new_rough(refraction_index: f32, roughness: f32) -> Dielectric {
+        Dielectric {
+            refraction_index,
+            distribution: TrowbridgeReitz::from_roughness(roughness, 0.0),
+            thin: false,
+            absorption: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn new_thin(refraction_index: f32) -> Dielectric {
+        Dielectric {
+            thin: true,
+            ..Dielectric::new(refraction_index)
+        }
+    }
+
+    // Coloured glass or liquid letting `color` through over `distance`.
+    pub fn new_absorbing(
+        refraction_index: f32,
+        roughness: f32,
+        color: Vec3,
+        distance: f32,
+    ) -> Dielectric {
+        let coefficient = |c: f32| -c.clamp(1e-6, 1.0).ln() / distance;
+        Dielectric {
+            absorption: Vec3::new(
+                coefficient(color.x),
+                coefficient(color.y),
+                coefficient(color.z),
+            ),
+            ..Dielectric::new_rough(refraction_index, roughness)
+        }
+    }
+
+    // Index on the far side of the interface over the one on the side of
+    // the hit.
+    fn relative_index(&self, hit_record: &HitRecord) -> f32 {
+        if hit_record.front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        }
+    }
+
+    // Beer-Lambert transmittance of the segment that reached the hit.
+    fn transmittance(&self, hit_record: &HitRecord) -> Vec3 {
+        if hit_record.front_face || self.thin {
+            return Vec3::new(1.0, 1.0, 1.0);
+        }
+        let a = self.absorption * hit_record.t;
+        Vec3::new((-a.x).exp(), (-a.y).exp(), (-a.z).exp())
     }
+
+    // Microfacet normal turning `wo` into `wi`, facing the outside of the
+    // microsurface, with the relative index the transmission crosses.
+    fn half_vector(&self, eta: f32, wo: Vec3, wi: Vec3) -> Option<(Vec3, f32)> {
+        let etap = if wi.z > 0.0 { 1.0 } else { eta };
+        let wm = wi * etap + wo;
+        if wi.z == 0.0 || wo.z <= 0.0 || wm.near_zero() {
+            return None;
+        }
+        let wm = wm.unit_vector();
+        let wm = if wm.z < 0.0 { -wm } else { wm };
+        // Microfacets facing away from either direction.
+        if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+            return None;
+        }
+        Some((wm, etap))
+    }
+
+    fn sample_thin(&self, wo: Vec3, uc: f32) -> Option<BsdfSample> {
+        let mut reflectance = fresnel_dielectric(wo.z, self.refraction_index);
+        // Light bouncing any number of times between the two faces.
+        if reflectance < 1.0 {
+            let t = 1.0 - reflectance;
+            reflectance += t * t * reflectance / (1.0 - reflectance * reflectance);
+        }
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        if uc < reflectance {
+            Some(BsdfSample::new(
+                reflect(wo),
+                white,
+                reflectance,
+                LobeFlags::SPECULAR | LobeFlags::REFLECTION,
+            ))
+        } else {
+            Some(BsdfSample::new(
+                -wo,
+                white,
+                1.0 - reflectance,
+                LobeFlags::SPECULAR | LobeFlags::TRANSMISSION,
+            ))
+        }
+    }
+
+    fn sample_smooth(&self, eta: f32, wo: Vec3, uc: f32) -> Option<BsdfSample> {
+        let reflectance = fresnel_dielectric(wo.z, eta);
+        if uc < reflectance {
+            return Some(BsdfSample::new(
+                reflect(wo),
+                Vec3::new(1.0, 1.0, 1.0),
+                reflectance,
+                LobeFlags::SPECULAR | LobeFlags::REFLECTION,
+            ));
+        }
+        let wi = refract(wo, Vec3::new(0.0, 0.0, 1.0), eta)?;
+        // Radiance is compressed into the smaller solid angle.
+        Some(BsdfSample::new(
+            wi,
+            Vec3::new(1.0, 1.0, 1.0) / (eta * eta),
+            1.0 - reflectance,
+            LobeFlags::SPECULAR | LobeFlags::TRANSMISSION,
+        ))
+    }
+}
+
+// Direction `wo` refracts into through an interface of normal `n` on its
+// side, `eta` being the index on the far side over the one on `wo`'s side.
+// `None` under total internal reflection.
+pub fn refract(wo: Vec3, n: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_theta_i = wo.dot(n);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(-wo / eta + (cos_theta_i / eta - cos_theta_t) * n)
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Ray, Vec3)> {
-        let refraction_ratio = if hit_record.front_face {
-            1.0 / self.refraction_index
+    fn eval(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        if self.thin || self.distribution.is_smooth() {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let eta = self.relative_index(hit_record);
+        let (wm, etap) = match self.half_vector(eta, wo, wi) {
+            Some(half) => half,
+            None => return Vec3::new(0.0, 0.0, 0.0),
+        };
+        let d = &self.distribution;
+        let reflectance = fresnel_dielectric(wo.dot(wm), eta);
+        // BSDF times |cos theta_i|.
+        let value = if wi.z > 0.0 {
+            d.d(wm) * d.g(wo, wi) * reflectance / (4.0 * wo.z)
         } else {
-            self.refraction_index
+            let denom = (wi.dot(wm) + wo.dot(wm) / etap).powi(2) * wo.z;
+            d.d(wm) * (1.0 - reflectance) * d.g(wo, wi) * (wi.dot(wm) * wo.dot(wm) / denom).abs()
+                / (etap * etap)
         };
-        let unit_direction = ray.direction.unit_vector();
-        let cos_theta = (-unit_direction).dot(hit_record.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract
-            || Dielectric::reflectance(cos_theta, self.refraction_index) > random_double()
-        {
-            unit_direction.reflect(hit_record.normal)
+        value * self.transmittance(hit_record)
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        if self.thin {
+            return self.sample_thin(wo, uc);
+        }
+        let eta = self.relative_index(hit_record);
+        let mut sample = if self.distribution.is_smooth() {
+            self.sample_smooth(eta, wo, uc)?
         } else {
-            unit_direction.refract(hit_record.normal, refraction_ratio)
+            if wo.z <= 0.0 {
+                return None;
+            }
+            // `u` picks the microfacet, then `uc` reflection or
+            // transmission by its Fresnel term.
+            let wm = self.distribution.sample_wm(wo, u);
+            let reflectance = fresnel_dielectric(wo.dot(wm), eta);
+            let wi = if uc < reflectance {
+                let wi = -wo + 2.0 * wo.dot(wm) * wm;
+                if wi.z <= 0.0 {
+                    return None;
+                }
+                wi
+            } else {
+                let wi = refract(wo, wm, eta)?;
+                if wi.z >= 0.0 {
+                    return None;
+                }
+                wi
+            };
+            let pdf = self.pdf(hit_record, wo, wi);
+            if pdf <= 0.0 {
+                return None;
+            }
+            let side = if wi.z > 0.0 {
+                LobeFlags::REFLECTION
+            } else {
+                LobeFlags::TRANSMISSION
+            };
+            return Some(BsdfSample::new(
+                wi,
+                self.eval(hit_record, wo, wi) / pdf,
+                pdf,
+                LobeFlags::GLOSSY | side,
+            ));
         };
-        let ray = Ray::new(hit_record.position, direction, ray.time);
-        Some((ray, Vec3::new(1.0, 1.0, 1.0)))
+        sample.weight = sample.weight * self.transmittance(hit_record);
+        Some(sample)
     }
+
+    // Visible normal density over the Jacobian of reflection or refraction,
+    // times the chance of picking either.
+    fn pdf(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        if self.thin || self.distribution.is_smooth() {
+            return 0.0;
+        }
+        let eta = self.relative_index(hit_record);
+        let (wm, etap) = match self.half_vector(eta, wo, wi) {
+            Some(half) => half,
+            None => return 0.0,
+        };
+        let reflectance = fresnel_dielectric(wo.dot(wm), eta);
+        let density = self.distribution.pdf(wo, wm);
+        if wi.z > 0.0 {
+            density / (4.0 * wo.dot(wm).abs()) * reflectance
+        } else {
+            let denom = (wi.dot(wm) + wo.dot(wm) / etap).powi(2);
+            density * wi.dot(wm).abs() / denom * (1.0 - reflectance)
+        }
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        let lobe = if self.thin || self.distribution.is_smooth() {
+            LobeFlags::SPECULAR
+        } else {
+            LobeFlags::GLOSSY
+        };
+        lobe | LobeFlags::REFLECTION | LobeFlags::TRANSMISSION
+    }
+}
+
+// Exact Fresnel reflectance of an unpolarized ray going from a medium of
+// index 1 into one of index `eta`, `cos_theta_i` being negative for rays
+// arriving from inside.
+pub fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i.max(-1.0), 1.0 / eta)
+    } else {
+        (cos_theta_i.min(1.0), eta)
+    };
+    let sin_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt() / eta;
+    if sin_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(0.0).sqrt();
+    let parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// Fresnel reflectance of an unpolarized ray arriving from a medium of
+// index 1 onto a conductor of complex index `eta` + i`k`.
+pub fn fresnel_conductor(cos_theta_i: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos2.sqrt() * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+    0.5 * (parallel + perpendicular)
 }
 
+// Emits `emit` from both faces, or only from the one the outward normal
+// points out of when not `two_sided`. Away from the normal the emission
+// is narrowed by `spread`, the full angle in degrees of the cone it is
+// confined to (180 for a plain diffuse emitter), and scaled by `profile`
+// relative to its brightest direction, the profile's nadir along the normal.
 pub struct DiffuseLight {
     pub emit: Arc<dyn Texture + Send + Sync>,
+    pub profile: Option<Arc<IesProfile>>,
+    pub two_sided: bool,
+    pub spread: f32,
 }
 
 impl DiffuseLight {
     pub fn new(color: Vec3) -> DiffuseLight {
+        DiffuseLight::from_texture(Arc::new(SolidColor::new(color)))
+    }
+
+    pub fn from_texture(texture: Arc<dyn Texture + Send + Sync>) -> DiffuseLight {
         DiffuseLight {
-            emit: Arc::new(SolidColor::new(color)),
+            emit: texture,
+            profile: None,
+            two_sided: true,
+            spread: 180.0,
         }
     }
 
-    pub fn from_texture(texture: Arc<dyn Texture + Send + Sync>) -> DiffuseLight {
-        DiffuseLight { emit: texture }
+    pub fn new_one_sided(color: Vec3) -> DiffuseLight {
+        DiffuseLight {
+            two_sided: false,
+            ..DiffuseLight::new(color)
+        }
+    }
+
+    pub fn new_with_spread(color: Vec3, spread: f32, two_sided: bool) -> DiffuseLight {
+        DiffuseLight {
+            two_sided,
+            spread,
+            ..DiffuseLight::new(color)
+        }
+    }
+
+    pub fn with_profile(color: Vec3, profile: Arc<IesProfile>) -> DiffuseLight {
+        DiffuseLight {
+            profile: Some(profile),
+            ..DiffuseLight::new(color)
+        }
+    }
+
+    // Emitter of `area` giving off `watts` in total, the hue taken from
+    // `color` (for instance `blackbody`).
+    pub fn from_watts(
+        color: Vec3,
+        watts: f32,
+        area: f32,
+        spread: f32,
+        two_sided: bool,
+    ) -> DiffuseLight {
+        let sides = if two_sided { 2.0 } else { 1.0 };
+        let radiance = watts / (area * sides * spread_integral(spread));
+        DiffuseLight::new_with_spread(radiance * color / color.luminance(), spread, two_sided)
+    }
+
+    // As `from_watts`, at the 683 lm/W of a source all at the peak of
+    // the eye's sensitivity.
+    pub fn from_lumens(
+        color: Vec3,
+        lumens: f32,
+        area: f32,
+        spread: f32,
+        two_sided: bool,
+    ) -> DiffuseLight {
+        DiffuseLight::from_watts(color, lumens / 683.0, area, spread, two_sided)
+    }
+
+    // Falloff of the emission `cos_theta` away from the normal, as in
+    // Cycles: one along the normal, zero past half the spread.
+    fn spread_falloff(&self, cos_theta: f32) -> f32 {
+        let half_spread = 0.5 * self.spread.to_radians();
+        if half_spread >= PI / 2.0 {
+            return 1.0;
+        }
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+        let tan_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt() / cos_theta;
+        (1.0 - tan_theta / half_spread.tan()).max(0.0)
+    }
+}
+
+// Integral over the hemisphere of the spread falloff times cos theta,
+// which is pi for a plain diffuse emitter.
+fn spread_integral(spread: f32) -> f32 {
+    let half_spread = 0.5 * spread.to_radians();
+    if half_spread >= PI / 2.0 {
+        return PI;
     }
+    let sin = half_spread.sin();
+    2.0 * PI
+        * (0.5 * sin * sin
+            - (0.5 * half_spread - 0.25 * (2.0 * half_spread).sin()) / half_spread.tan())
 }
 
 impl Material for DiffuseLight {
-    fn scatter(&self, _ray: &Ray, _hit_record: &HitRecord) -> Option<(Ray, Vec3)> {
-        None
+    fn emitted(&self, hit_record: &HitRecord, wo: Vec3) -> Vec3 {
+        if !self.two_sided && !hit_record.front_face {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let mut emit = self
+            .emit
+            .value(hit_record.u, hit_record.v, hit_record.position);
+        if self.spread < 180.0 {
+            emit = self.spread_falloff(wo.z) * emit;
+        }
+        match &self.profile {
+            Some(profile) => profile.relative(wo) * emit,
+            None => emit,
+        }
     }
 
-    fn emitted(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
-        self.emit.value(u, v, p)
+    // The spread falloff and the profile never go above one.
+    fn emission_bounds(&self) -> Option<EmissionBounds> {
+        let radiance = self.emit.max_value();
+        if radiance <= 0.0 {
+            return None;
+        }
+        let half_spread = 0.5 * self.spread.min(180.0).to_radians();
+        Some(EmissionBounds::new(
+            radiance,
+            half_spread.cos(),
+            self.two_sided,
+        ))
     }
 }
 
@@ -144,23 +990,123 @@ pub struct Isotropic {
 
 impl Isotropic {
     pub fn new(color: Vec3) -> Isotropic {
-        Isotropic {
-            albedo: Arc::new(SolidColor::new(color)),
-        }
+        Isotropic::from_texture(Arc::new(SolidColor::new(color)))
     }
 
     pub fn from_texture(texture: Arc<dyn Texture + Send + Sync>) -> Isotropic {
         Isotropic { albedo: texture }
     }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        self.albedo
+            .value(hit_record.u, hit_record.v, hit_record.position)
+    }
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Ray, Vec3)> {
-        let scattered = Ray::new(hit_record.position, Vec3::random_in_unit_sphere(), ray.time);
-        Some((
-            scattered,
-            self.albedo
-                .value(hit_record.u, hit_record.v, hit_record.position),
+    fn eval(&self, hit_record: &HitRecord, _wo: Vec3, _wi: Vec3) -> Vec3 {
+        self.albedo(hit_record) / (4.0 * PI)
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        _wo: Vec3,
+        _uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        Some(BsdfSample::new(
+            sample_uniform_sphere(u),
+            self.albedo(hit_record),
+            1.0 / (4.0 * PI),
+            LobeFlags::VOLUME,
         ))
     }
+
+    fn pdf(&self, _hit_record: &HitRecord, _wo: Vec3, _wi: Vec3) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        LobeFlags::VOLUME
+    }
+}
+
+// Density of scattering from `-wo` into `wi`, `cos_theta` being wo · wi.
+pub fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let denom = 1.0 + g * g + 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denom * denom.max(1e-12).sqrt())
+}
+
+// Direction distributed as `henyey_greenstein` around the one `wo` came
+// from.
+pub fn sample_henyey_greenstein(wo: Vec3, g: f32, u: (f32, f32)) -> Vec3 {
+    // Cosine from the forward direction -wo.
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * u.0
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u.0);
+        ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    let frame = ONB::build_from_w(-wo);
+    frame.local_to_world(Vec3::new(
+        sin_theta * phi.cos(),
+        sin_theta * phi.sin(),
+        cos_theta,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::testing::{check_bsdf, hit_record, outgoing_directions};
+
+    #[test]
+    fn rough_diffuse_and_sheen_samples_match_eval() {
+        check_bsdf(&OrenNayar::new(Vec3::new(0.7, 0.5, 0.3), 20.0));
+        check_bsdf(&OrenNayar::new(Vec3::new(0.9, 0.9, 0.9), 60.0));
+        check_bsdf(&Sheen::new(Vec3::new(0.8, 0.3, 0.5), 0.5));
+        check_bsdf(&Sheen::new(Vec3::new(1.0, 1.0, 1.0), 0.1));
+    }
+
+    #[test]
+    fn rough_metal_samples_match_eval() {
+        check_bsdf(&Metal::gold(0.5));
+        check_bsdf(&Metal::new_anisotropic(Vec3::new(0.9, 0.6, 0.3), 0.6, 0.5));
+    }
+
+    #[test]
+    fn rough_glass_samples_match_eval() {
+        check_bsdf(&Dielectric::new_rough(1.5, 0.7));
+        // Seen from inside, where light can be totally reflected.
+        let mut inside = Dielectric::new_rough(1.5, 0.7);
+        inside.refraction_index = 1.0 / 1.5;
+        check_bsdf(&inside);
+    }
+
+    #[test]
+    fn glass_samples_depend_only_on_their_numbers() {
+        let hit_record = hit_record();
+        let rough = Dielectric::new_rough(1.5, 0.5);
+        for glass in [&Dielectric::new(1.5), &Dielectric::new_thin(1.5), &rough] {
+            for wo in outgoing_directions() {
+                for i in 0..100 {
+                    let uc = (i as f32 + 0.5) / 100.0;
+                    let u = (0.3 + 0.007 * i as f32, 0.9 - 0.008 * i as f32);
+                    let first = glass.sample(&hit_record, wo, uc, u);
+                    let second = glass.sample(&hit_record, wo, uc, u);
+                    match (first, second) {
+                        (Some(first), Some(second)) => {
+                            assert!((first.wi - second.wi).length() == 0.0);
+                            assert_eq!(first.pdf, second.pdf);
+                        }
+                        (None, None) => {}
+                        _ => panic!("sampling {wo:?} with {uc} and {u:?} differed"),
+                    }
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,104 @@
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+
+// Below this alpha surfaces are taken as perfectly smooth, the distribution
+// being too peaked to evaluate reliably.
+const SMOOTH_ALPHA: f32 = 1e-3;
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals in the local
+// shading frame, `alpha_x` and `alpha_y` being the roughness along the
+// tangent and the bitangent. Masking and shadowing follow Smith's height
+// correlated model and sampling picks only the normals visible from `wo`
+// (Heitz 2018), as pbrt-v4 does.
+#[derive(Debug, Copy, Clone)]
+pub struct TrowbridgeReitz {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> TrowbridgeReitz {
+        TrowbridgeReitz { alpha_x, alpha_y }
+    }
+
+    // Alpha from a perceptually linear roughness in [0, 1], stretched along
+    // the tangent by `anisotropy` in [0, 1) as in the Disney BRDF.
+    pub fn from_roughness(roughness: f32, anisotropy: f32) -> TrowbridgeReitz {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+        TrowbridgeReitz::new(alpha / aspect, alpha * aspect)
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < SMOOTH_ALPHA
+    }
+
+    // Density of microfacet normals `wm` per unit of projected area.
+    pub fn d(&self, wm: Vec3) -> f32 {
+        let cos2_theta = wm.z * wm.z;
+        let cos4_theta = cos2_theta * cos2_theta;
+        if cos4_theta < 1e-16 {
+            return 0.0;
+        }
+        let e = (wm.x * wm.x / (self.alpha_x * self.alpha_x)
+            + wm.y * wm.y / (self.alpha_y * self.alpha_y))
+            / cos2_theta;
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos4_theta * (1.0 + e) * (1.0 + e))
+    }
+
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2_theta = w.z * w.z;
+        if cos2_theta == 0.0 {
+            return f32::INFINITY;
+        }
+        let alpha2_tan2_theta = (w.x * w.x * self.alpha_x * self.alpha_x
+            + w.y * w.y * self.alpha_y * self.alpha_y)
+            / cos2_theta;
+        0.5 * ((1.0 + alpha2_tan2_theta).sqrt() - 1.0)
+    }
+
+    // Fraction of the microfacets seen from `w` that are not masked.
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Fraction of the microfacets both seen from `wo` and lit from `wi`.
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of the normals `sample_wm` picks when seen from `w`.
+    pub fn pdf(&self, w: Vec3, wm: Vec3) -> f32 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    // A microfacet normal visible from `w`, on the side of the surface `w`
+    // is on.
+    pub fn sample_wm(&self, w: Vec3, u: (f32, f32)) -> Vec3 {
+        // Stretch to the hemisphere configuration of a unit roughness.
+        let mut wh = Vec3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).unit_vector();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+        let t1 = if wh.z < 0.99999 {
+            Vec3::new(0.0, 0.0, 1.0).cross(wh).unit_vector()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(t1);
+
+        // Uniform on the disk, squeezed onto the visible half.
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let (px, py) = (r * phi.cos(), r * phi.sin());
+        let h = (1.0 - px * px).max(0.0).sqrt();
+        let s = 0.5 * (1.0 + wh.z);
+        let py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).unit_vector()
+    }
+}
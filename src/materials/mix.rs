@@ -0,0 +1,142 @@
+use crate::image_utils::hittable::HitRecord;
+use crate::image_utils::texture::{SolidColor, Texture};
+use crate::materials::materials::{BsdfSample, EmissionBounds, LobeFlags, Material};
+use crate::utils::vec3::Vec3;
+use std::sync::Arc;
+
+// Blend of two materials, `mask` giving the weight of `second` from the
+// luminance of its texture, clamped to [0, 1]. Sampling picks one of them
+// by its weight, so a mask of 0 or 1 costs no more than the material
+// itself, as for dirt over metal.
+pub struct MixMaterial {
+    pub first: Arc<dyn Material + Send + Sync>,
+    pub second: Arc<dyn Material + Send + Sync>,
+    pub mask: Arc<dyn Texture + Send + Sync>,
+}
+
+impl MixMaterial {
+    pub fn new(
+        first: Arc<dyn Material + Send + Sync>,
+        second: Arc<dyn Material + Send + Sync>,
+        factor: f32,
+    ) -> MixMaterial {
+        MixMaterial::from_texture(
+            first,
+            second,
+            Arc::new(SolidColor::new(Vec3::new(factor, factor, factor))),
+        )
+    }
+
+    pub fn from_texture(
+        first: Arc<dyn Material + Send + Sync>,
+        second: Arc<dyn Material + Send + Sync>,
+        mask: Arc<dyn Texture + Send + Sync>,
+    ) -> MixMaterial {
+        MixMaterial {
+            first,
+            second,
+            mask,
+        }
+    }
+
+    fn factor(&self, hit_record: &HitRecord) -> f32 {
+        self.mask
+            .value(hit_record.u, hit_record.v, hit_record.position)
+            .luminance()
+            .clamp(0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial {
+    fn eval(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        let factor = self.factor(hit_record);
+        let mut value = Vec3::new(0.0, 0.0, 0.0);
+        if factor < 1.0 {
+            value = value + (1.0 - factor) * self.first.eval(hit_record, wo, wi);
+        }
+        if factor > 0.0 {
+            value = value + factor * self.second.eval(hit_record, wo, wi);
+        }
+        value
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        let factor = self.factor(hit_record);
+        // `uc` picks the material, then is stretched back over [0, 1) for
+        // its own choice of lobe.
+        let (material, chance, uc) = if uc >= factor {
+            (&self.first, 1.0 - factor, (uc - factor) / (1.0 - factor))
+        } else {
+            (&self.second, factor, uc / factor)
+        };
+        let mut sample = material.sample(hit_record, wo, uc.min(1.0 - f32::EPSILON), u)?;
+        if sample.flags.contains(LobeFlags::SPECULAR) {
+            // Only the picked material has this direction.
+            sample.pdf *= chance;
+            return Some(sample);
+        }
+        let pdf = self.pdf(hit_record, wo, sample.wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        sample.weight = self.eval(hit_record, wo, sample.wi) / pdf;
+        sample.pdf = pdf;
+        Some(sample)
+    }
+
+    fn pdf(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        let factor = self.factor(hit_record);
+        let mut pdf = 0.0;
+        if factor < 1.0 {
+            pdf += (1.0 - factor) * self.first.pdf(hit_record, wo, wi);
+        }
+        if factor > 0.0 {
+            pdf += factor * self.second.pdf(hit_record, wo, wi);
+        }
+        pdf
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        self.first.lobes() | self.second.lobes()
+    }
+
+    fn emitted(&self, hit_record: &HitRecord, wo: Vec3) -> Vec3 {
+        let factor = self.factor(hit_record);
+        (1.0 - factor) * self.first.emitted(hit_record, wo)
+            + factor * self.second.emitted(hit_record, wo)
+    }
+
+    // A blend is no brighter than the brighter of the two.
+    fn emission_bounds(&self) -> Option<EmissionBounds> {
+        match (self.first.emission_bounds(), self.second.emission_bounds()) {
+            (Some(first), Some(second)) => Some(first.union(&second)),
+            (first, None) => first,
+            (None, second) => second,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::materials::{Dielectric, Lambertian, Metal};
+    use crate::materials::testing::check_bsdf;
+
+    #[test]
+    fn mixed_samples_match_eval() {
+        let dirt = Arc::new(Lambertian::new(Vec3::new(0.4, 0.3, 0.2)));
+        check_bsdf(&MixMaterial::new(
+            Arc::new(Metal::gold(0.5)),
+            dirt.clone(),
+            0.3,
+        ));
+        // Only the diffuse half has a density.
+        check_bsdf(&MixMaterial::new(Arc::new(Dielectric::new(1.5)), dirt, 0.6));
+    }
+}
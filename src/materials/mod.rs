@@ -1 +1,9 @@
-pub mod materials;
+pub mod coated;
+pub mod hair;
+#[allow(clippy::module_inception)]
+pub mod materials;
+pub mod microfacet;
+pub mod mix;
+pub mod principled;
+#[cfg(test)]
+pub mod testing;
@@ -0,0 +1,432 @@
+use crate::image_utils::hittable::HitRecord;
+use crate::image_utils::texture::{SolidColor, Texture};
+use crate::materials::materials::{
+    fresnel_dielectric, refract, sample_cosine_hemisphere, BsdfSample, EmissionBounds, LobeFlags,
+    Material,
+};
+use crate::materials::microfacet::TrowbridgeReitz;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Lobes `Principled` samples from, in the order of their weights.
+const DIFFUSE_LOBE: usize = 0;
+const SPECULAR_LOBE: usize = 1;
+const CLEARCOAT_LOBE: usize = 2;
+const TRANSMISSION_LOBE: usize = 3;
+
+// Disney's principled BRDF (Burley 2012) with the rough transmission of
+// its 2015 BSDF. Every parameter is read from a texture at the hit, the
+// ones other than colours from the luminance of their texture, clamped to
+// [0, 1]:
+//
+// - `metallic` goes from a diffuse base under a dielectric specular lobe to
+//   a conductor reflecting `base_color`.
+// - `specular` is the dielectric reflectance at normal incidence, 0.5
+//   giving the 4% of glass, and `specular_tint` tints it towards
+//   `base_color`.
+// - `sheen` adds the grazing reflection of cloth, `sheen_tint` tinting it.
+// - `clearcoat` adds a thin varnish layer, sharper with `clearcoat_gloss`.
+// - `transmission` trades the diffuse base for refraction through the
+//   surface, with the index of refraction `specular` implies.
+// - `subsurface` flattens the diffuse base as subsurface scattering does.
+//
+// `emission` is the radiance leaving the surface on either side.
+pub struct Principled {
+    pub base_color: Arc<dyn Texture + Send + Sync>,
+    pub metallic: Arc<dyn Texture + Send + Sync>,
+    pub roughness: Arc<dyn Texture + Send + Sync>,
+    pub specular: Arc<dyn Texture + Send + Sync>,
+    pub specular_tint: Arc<dyn Texture + Send + Sync>,
+    pub sheen: Arc<dyn Texture + Send + Sync>,
+    pub sheen_tint: Arc<dyn Texture + Send + Sync>,
+    pub clearcoat: Arc<dyn Texture + Send + Sync>,
+    pub clearcoat_gloss: Arc<dyn Texture + Send + Sync>,
+    pub transmission: Arc<dyn Texture + Send + Sync>,
+    pub subsurface: Arc<dyn Texture + Send + Sync>,
+    pub emission: Arc<dyn Texture + Send + Sync>,
+}
+
+impl Principled {
+    // Rough dielectric of `base_color`, with Disney's defaults for the
+    // other parameters.
+    pub fn new(base_color: Vec3) -> Principled {
+        Principled::from_texture(Arc::new(SolidColor::new(base_color)))
+    }
+
+    pub fn from_texture(base_color: Arc<dyn Texture + Send + Sync>) -> Principled {
+        Principled {
+            base_color,
+            metallic: scalar(0.0),
+            roughness: scalar(0.5),
+            specular: scalar(0.5),
+            specular_tint: scalar(0.0),
+            sheen: scalar(0.0),
+            sheen_tint: scalar(0.5),
+            clearcoat: scalar(0.0),
+            clearcoat_gloss: scalar(1.0),
+            transmission: scalar(0.0),
+            subsurface: scalar(0.0),
+            emission: Arc::new(SolidColor::new(Vec3::new(0.0, 0.0, 0.0))),
+        }
+    }
+
+    fn parameters(&self, hit_record: &HitRecord) -> Parameters {
+        let (u, v, p) = (hit_record.u, hit_record.v, hit_record.position);
+        let amount = |texture: &Arc<dyn Texture + Send + Sync>| {
+            texture.value(u, v, p).luminance().clamp(0.0, 1.0)
+        };
+        let base_color = self.base_color.value(u, v, p);
+        let metallic = amount(&self.metallic);
+        let specular = amount(&self.specular);
+
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let luminance = base_color.luminance();
+        let tint = if luminance > 0.0 {
+            base_color / luminance
+        } else {
+            white
+        };
+        let specular_color = lerp(
+            0.08 * specular * lerp(white, tint, amount(&self.specular_tint)),
+            base_color,
+            metallic,
+        );
+        // The index giving the dielectric reflectance at normal incidence,
+        // kept above 1 for transmission to bend light at all.
+        let r0 = (0.08 * specular).sqrt();
+        let eta = ((1.0 + r0) / (1.0 - r0)).max(1.01);
+
+        let roughness = amount(&self.roughness);
+        let alpha = roughness.powi(2).max(1e-3);
+        let gloss = amount(&self.clearcoat_gloss);
+        Parameters {
+            base_color,
+            metallic,
+            roughness,
+            subsurface: amount(&self.subsurface),
+            sheen: amount(&self.sheen),
+            sheen_color: lerp(white, tint, amount(&self.sheen_tint)),
+            clearcoat: amount(&self.clearcoat),
+            clearcoat_alpha: 0.1 + (0.001 - 0.1) * gloss,
+            transmission: amount(&self.transmission),
+            specular_color,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+            eta: if hit_record.front_face {
+                eta
+            } else {
+                1.0 / eta
+            },
+        }
+    }
+}
+
+// Texture of a constant parameter.
+pub fn scalar(value: f32) -> Arc<dyn Texture + Send + Sync> {
+    Arc::new(SolidColor::new(Vec3::new(value, value, value)))
+}
+
+// The parameters of `Principled` at a hit.
+struct Parameters {
+    base_color: Vec3,
+    metallic: f32,
+    roughness: f32,
+    subsurface: f32,
+    sheen: f32,
+    sheen_color: Vec3,
+    clearcoat: f32,
+    clearcoat_alpha: f32,
+    transmission: f32,
+    // Reflectance of the specular lobe at normal incidence.
+    specular_color: Vec3,
+    distribution: TrowbridgeReitz,
+    // Index on the far side of the surface over the one on the side of the
+    // hit.
+    eta: f32,
+}
+
+impl Parameters {
+    // Chance of sampling each lobe, roughly following its reflectance seen
+    // from `wo`. The specular lobe always keeps some, since its Fresnel
+    // term reaches 1 at grazing angles whatever `specular_color` is.
+    fn weights(&self, wo: Vec3) -> [f32; 4] {
+        let fo = schlick_weight(wo.z);
+        let mut weights = [0.0; 4];
+        weights[DIFFUSE_LOBE] = (1.0 - self.metallic) * (1.0 - self.transmission);
+        weights[SPECULAR_LOBE] = lerp(self.specular_color, Vec3::new(1.0, 1.0, 1.0), fo)
+            .luminance()
+            .max(0.05);
+        weights[CLEARCOAT_LOBE] = 0.25 * self.clearcoat * (0.04 + 0.96 * fo);
+        weights[TRANSMISSION_LOBE] =
+            (1.0 - self.metallic) * self.transmission * sqrt(self.base_color).luminance();
+        let sum: f32 = weights.iter().sum();
+        weights.map(|weight| weight / sum)
+    }
+
+    // Sum of the lobes, times |cos theta_i|.
+    fn eval(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        if wi.z < 0.0 {
+            return self.eval_transmission(wo, wi);
+        }
+        let wh = (wo + wi).unit_vector();
+        let cos_d = wi.dot(wh);
+        let (fl, fv, fh) = (
+            schlick_weight(wi.z),
+            schlick_weight(wo.z),
+            schlick_weight(cos_d),
+        );
+
+        // Diffuse retro-reflection rising with roughness, blended with
+        // the Hanrahan-Krueger approximation of subsurface scattering.
+        let fd90 = 0.5 + 2.0 * cos_d * cos_d * self.roughness;
+        let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+        let fss90 = cos_d * cos_d * self.roughness;
+        let fss = (1.0 + (fss90 - 1.0) * fl) * (1.0 + (fss90 - 1.0) * fv);
+        let ss = 1.25 * (fss * (1.0 / (wi.z + wo.z) - 0.5) + 0.5);
+        let diffuse = self.base_color / PI * (fd + (ss - fd) * self.subsurface)
+            + fh * self.sheen * self.sheen_color;
+
+        let d = &self.distribution;
+        let specular = d.d(wh) * d.g(wo, wi) / (4.0 * wo.z * wi.z)
+            * lerp(self.specular_color, Vec3::new(1.0, 1.0, 1.0), fh);
+
+        let clearcoat = 0.25
+            * self.clearcoat
+            * gtr1(wh.z, self.clearcoat_alpha)
+            * (0.04 + 0.96 * fh)
+            * smith_g1(wi.z, 0.25)
+            * smith_g1(wo.z, 0.25)
+            / (4.0 * wo.z * wi.z);
+
+        ((1.0 - self.metallic) * (1.0 - self.transmission) * diffuse
+            + specular
+            + Vec3::new(clearcoat, clearcoat, clearcoat))
+            * wi.z
+    }
+
+    // Rough refraction as in `Dielectric`, tinted by the base color.
+    fn eval_transmission(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        let wm = match self.refraction_half_vector(wo, wi) {
+            Some(wm) => wm,
+            None => return Vec3::new(0.0, 0.0, 0.0),
+        };
+        let d = &self.distribution;
+        let eta = self.eta;
+        let reflectance = fresnel_dielectric(wo.dot(wm), eta);
+        let denom = (wi.dot(wm) + wo.dot(wm) / eta).powi(2) * wo.z;
+        let value =
+            d.d(wm) * (1.0 - reflectance) * d.g(wo, wi) * (wi.dot(wm) * wo.dot(wm) / denom).abs()
+                / (eta * eta);
+        (1.0 - self.metallic) * self.transmission * value * sqrt(self.base_color)
+    }
+
+    // Microfacet normal refracting `wo` into `wi`, if it faces both.
+    fn refraction_half_vector(&self, wo: Vec3, wi: Vec3) -> Option<Vec3> {
+        let wm = wi * self.eta + wo;
+        if wm.near_zero() {
+            return None;
+        }
+        let wm = wm.unit_vector();
+        let wm = if wm.z < 0.0 { -wm } else { wm };
+        if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+            return None;
+        }
+        Some(wm)
+    }
+
+    // Density of each lobe sampling `wi`.
+    fn pdfs(&self, wo: Vec3, wi: Vec3) -> [f32; 4] {
+        let mut pdfs = [0.0; 4];
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return pdfs;
+        }
+        if wi.z > 0.0 {
+            let wh = (wo + wi).unit_vector();
+            let jacobian = 4.0 * wo.dot(wh).abs();
+            pdfs[DIFFUSE_LOBE] = wi.z / PI;
+            pdfs[SPECULAR_LOBE] = self.distribution.pdf(wo, wh) / jacobian;
+            pdfs[CLEARCOAT_LOBE] = gtr1(wh.z, self.clearcoat_alpha) * wh.z / jacobian;
+        } else if let Some(wm) = self.refraction_half_vector(wo, wi) {
+            let denom = (wi.dot(wm) + wo.dot(wm) / self.eta).powi(2);
+            pdfs[TRANSMISSION_LOBE] = self.distribution.pdf(wo, wm) * wi.dot(wm).abs() / denom;
+        }
+        pdfs
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let weights = self.weights(wo);
+        self.pdfs(wo, wi)
+            .iter()
+            .zip(weights)
+            .map(|(pdf, weight)| pdf * weight)
+            .sum()
+    }
+}
+
+impl Material for Principled {
+    fn eval(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        self.parameters(hit_record).eval(wo, wi)
+    }
+
+    fn sample(
+        &self,
+        hit_record: &HitRecord,
+        wo: Vec3,
+        uc: f32,
+        u: (f32, f32),
+    ) -> Option<BsdfSample> {
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let parameters = self.parameters(hit_record);
+        let weights = parameters.weights(wo);
+
+        // `uc` picks the lobe by its weight.
+        let mut lobe = 0;
+        let mut uc = uc;
+        for (i, &weight) in weights.iter().enumerate() {
+            if weight > 0.0 {
+                lobe = i;
+                if uc < weight {
+                    break;
+                }
+                uc -= weight;
+            }
+        }
+
+        let reflect = |wm: Vec3| -wo + 2.0 * wo.dot(wm) * wm;
+        let (wi, flags) = match lobe {
+            DIFFUSE_LOBE => (
+                sample_cosine_hemisphere(u),
+                LobeFlags::DIFFUSE | LobeFlags::REFLECTION,
+            ),
+            SPECULAR_LOBE => (
+                reflect(parameters.distribution.sample_wm(wo, u)),
+                LobeFlags::GLOSSY | LobeFlags::REFLECTION,
+            ),
+            CLEARCOAT_LOBE => (
+                reflect(sample_gtr1(parameters.clearcoat_alpha, u)),
+                LobeFlags::GLOSSY | LobeFlags::REFLECTION,
+            ),
+            _ => (
+                refract(wo, parameters.distribution.sample_wm(wo, u), parameters.eta)?,
+                LobeFlags::GLOSSY | LobeFlags::TRANSMISSION,
+            ),
+        };
+        if (wi.z > 0.0) != (lobe != TRANSMISSION_LOBE) {
+            return None;
+        }
+        let pdf = parameters.pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample::new(
+            wi,
+            parameters.eval(wo, wi) / pdf,
+            pdf,
+            flags,
+        ))
+    }
+
+    fn pdf(&self, hit_record: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        self.parameters(hit_record).pdf(wo, wi)
+    }
+
+    fn lobes(&self) -> LobeFlags {
+        LobeFlags::DIFFUSE | LobeFlags::GLOSSY | LobeFlags::REFLECTION | LobeFlags::TRANSMISSION
+    }
+
+    fn emitted(&self, hit_record: &HitRecord, _wo: Vec3) -> Vec3 {
+        self.emission
+            .value(hit_record.u, hit_record.v, hit_record.position)
+    }
+
+    fn emission_bounds(&self) -> Option<EmissionBounds> {
+        let radiance = self.emission.max_value();
+        if radiance <= 0.0 {
+            return None;
+        }
+        Some(EmissionBounds::new(radiance, 0.0, true))
+    }
+}
+
+fn lerp(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    a + (b - a) * t
+}
+
+fn sqrt(v: Vec3) -> Vec3 {
+    Vec3::new(v.x.sqrt(), v.y.sqrt(), v.z.sqrt())
+}
+
+// Schlick's (1 - cos)^5.
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+// Berry's distribution (GTR with an exponent of 1), whose long tail gives
+// the clearcoat its haze.
+fn gtr1(cos_theta_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_theta_h * cos_theta_h))
+}
+
+// A normal distributed as `gtr1` times its cosine.
+fn sample_gtr1(alpha: f32, u: (f32, f32)) -> Vec3 {
+    let a2 = alpha * alpha;
+    let cos_theta = ((1.0 - a2.powf(1.0 - u.0)) / (1.0 - a2)).max(0.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+// Separable Smith masking of GGX, which the clearcoat uses with a fixed
+// roughness.
+fn smith_g1(cos_theta: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let c2 = cos_theta * cos_theta;
+    2.0 * cos_theta / (cos_theta + (a2 + c2 - a2 * c2).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::testing::check_bsdf;
+
+    fn orange() -> Principled {
+        Principled::new(Vec3::new(0.8, 0.4, 0.2))
+    }
+
+    #[test]
+    fn dielectric_and_metallic_samples_match_eval() {
+        check_bsdf(&orange());
+        check_bsdf(&Principled {
+            metallic: scalar(1.0),
+            roughness: scalar(0.6),
+            ..orange()
+        });
+    }
+
+    #[test]
+    fn clearcoat_and_sheen_samples_match_eval() {
+        check_bsdf(&Principled {
+            roughness: scalar(0.8),
+            subsurface: scalar(0.5),
+            sheen: scalar(1.0),
+            clearcoat: scalar(1.0),
+            clearcoat_gloss: scalar(0.3),
+            ..orange()
+        });
+    }
+
+    #[test]
+    fn transmission_samples_match_eval() {
+        check_bsdf(&Principled {
+            roughness: scalar(0.7),
+            transmission: scalar(0.9),
+            ..orange()
+        });
+    }
+}
@@ -0,0 +1,142 @@
+use crate::image_utils::hittable::HitRecord;
+use crate::materials::materials::{sample_uniform_sphere, Lambertian, LobeFlags, Material};
+use crate::utils::random_double;
+use crate::utils::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Monte Carlo checks shared by the tests of the materials.
+
+// Strata along each side of the unit square, for sampling as for the
+// integrals over the sphere.
+const STRATA: usize = 256;
+
+// Hit at the origin facing +z, seen from outside, so the shading frame is
+// the world frame.
+pub fn hit_record() -> HitRecord {
+    let mut record = HitRecord::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        1.0,
+    );
+    record.normal = Vec3::new(0.0, 0.0, 1.0);
+    record.front_face = true;
+    record.u = 0.5;
+    record.v = 0.5;
+    record
+}
+
+// Directions towards the viewer from near the normal to grazing, off the
+// axes for anisotropic lobes.
+pub fn outgoing_directions() -> Vec<Vec3> {
+    [0.95, 0.6, 0.2]
+        .iter()
+        .map(|&cos_theta: &f32| {
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            Vec3::new(0.8 * sin_theta, 0.6 * sin_theta, cos_theta)
+        })
+        .collect()
+}
+
+fn stratified(i: usize) -> (f32, f32) {
+    let (x, y) = (i % STRATA, i / STRATA);
+    (
+        (x as f32 + random_double()) / STRATA as f32,
+        (y as f32 + random_double()) / STRATA as f32,
+    )
+}
+
+// What `material` scatters from `wo`, found both by sampling it and by
+// integrating `eval` and `pdf` over the sphere.
+pub struct Integrals {
+    // Average weight of the non specular samples, counting the others and
+    // failures as zero.
+    pub sampled: Vec3,
+    // Integral of `eval`, which `sampled` estimates too.
+    pub eval: Vec3,
+    // Integral of `pdf`.
+    pub pdf: f32,
+    // Chance of sampling a non specular lobe, which the integral of `pdf`
+    // is for an exact density.
+    pub non_specular: f32,
+}
+
+pub fn integrals(material: &dyn Material, wo: Vec3) -> Integrals {
+    let hit_record = hit_record();
+    let n = STRATA * STRATA;
+    let mut sampled = Vec3::new(0.0, 0.0, 0.0);
+    let mut non_specular = 0.0;
+    let mut eval = Vec3::new(0.0, 0.0, 0.0);
+    let mut pdf = 0.0;
+    for i in 0..n {
+        if let Some(sample) = material.sample(&hit_record, wo, random_double(), stratified(i)) {
+            if !sample.flags.contains(LobeFlags::SPECULAR) {
+                sampled = sampled + sample.weight;
+                non_specular += 1.0;
+            }
+        }
+        let wi = sample_uniform_sphere(stratified(i));
+        eval = eval + material.eval(&hit_record, wo, wi);
+        pdf += material.pdf(&hit_record, wo, wi);
+    }
+    Integrals {
+        sampled: sampled / n as f32,
+        eval: 4.0 * PI * eval / n as f32,
+        pdf: 4.0 * PI * pdf / n as f32,
+        non_specular: non_specular / n as f32,
+    }
+}
+
+pub fn assert_close(a: Vec3, b: Vec3, tolerance: f32, what: &str) {
+    assert!(
+        (a - b).length() <= tolerance * (1.0 + b.length()),
+        "{what}: {a:?} against {b:?}"
+    );
+}
+
+// Checks a material whose `eval` and `pdf` are exact from each of
+// `outgoing_directions`: every non specular sample has the density `pdf`
+// gives and the weight `eval` over it, the samples average to the
+// integral of `eval`, and `pdf` integrates to the chance of sampling it.
+pub fn check_bsdf(material: &dyn Material) {
+    let hit_record = hit_record();
+    for wo in outgoing_directions() {
+        for i in 0..STRATA * 4 {
+            let u = (random_double(), random_double());
+            let sample = match material.sample(&hit_record, wo, random_double(), u) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            if sample.flags.contains(LobeFlags::SPECULAR) {
+                continue;
+            }
+            let pdf = material.pdf(&hit_record, wo, sample.wi);
+            assert!(
+                (sample.pdf - pdf).abs() <= 1e-3 * pdf,
+                "sample {i} from {wo:?}: density {} against pdf {pdf}",
+                sample.pdf
+            );
+            let expected = material.eval(&hit_record, wo, sample.wi) / pdf;
+            assert_close(
+                sample.weight,
+                expected,
+                1e-3,
+                &format!("weight of sample {i} from {wo:?}"),
+            );
+        }
+
+        let integrals = integrals(material, wo);
+        assert_close(
+            integrals.sampled,
+            integrals.eval,
+            0.02,
+            &format!("sampled albedo from {wo:?}"),
+        );
+        assert!(
+            (integrals.pdf - integrals.non_specular).abs() < 0.02,
+            "pdf from {wo:?} integrates to {} for samples found {} of the time",
+            integrals.pdf,
+            integrals.non_specular
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::image_utils::aabb::AABB;
+use crate::utils::quaternion::Quaternion;
+use crate::utils::transform::Transform;
+use crate::utils::vec3::Vec3;
+
+// Number of instants sampled over the shutter interval when bounding an
+// animated object, on top of the keyframe times themselves.
+const BOUNDING_STEPS: usize = 64;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Keyframe {
+    pub time: f32,
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, translation: Vec3, rotation: Quaternion, scale: Vec3) -> Keyframe {
+        Keyframe {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        let scale = Transform::scale(self.scale);
+        let rotation = self.rotation.rotation_matrix();
+        let rotation = Transform::new(rotation, rotation.transpose());
+        scale
+            .then(rotation)
+            .then(Transform::translate(self.translation))
+    }
+}
+
+// Keyframed scale, rotation and translation (applied in that order).
+// Translation and scale are interpolated linearly and rotation with slerp;
+// times outside the keyframes are clamped to the first or last one.
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl AnimatedTransform {
+    pub fn new(keyframes: Vec<Keyframe>) -> AnimatedTransform {
+        assert!(
+            !keyframes.is_empty(),
+            "an animated transform needs at least one keyframe"
+        );
+        let mut keyframes = keyframes;
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        AnimatedTransform { keyframes }
+    }
+
+    pub fn between(start: Keyframe, end: Keyframe) -> AnimatedTransform {
+        AnimatedTransform::new(vec![start, end])
+    }
+
+    pub fn keyframe_at(&self, time: f32) -> Keyframe {
+        let first = self.keyframes[0];
+        let last = self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return Keyframe { time, ..first };
+        }
+        if time >= last.time {
+            return Keyframe { time, ..last };
+        }
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        let k0 = self.keyframes[next - 1];
+        let k1 = self.keyframes[next];
+        let t = (time - k0.time) / (k1.time - k0.time);
+        Keyframe::new(
+            time,
+            k0.translation + t * (k1.translation - k0.translation),
+            k0.rotation.slerp(k1.rotation, t),
+            k0.scale + t * (k1.scale - k0.scale),
+        )
+    }
+
+    pub fn at(&self, time: f32) -> Transform {
+        self.keyframe_at(time).transform()
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.keyframes.len() > 1
+    }
+
+    // Bounds `aabb` swept by the transform over [time0, time1]. Samples are
+    // taken at regular steps and at every keyframe in the interval; the
+    // result is then padded by the largest deviation of a rotating corner
+    // from the chord between two consecutive samples.
+    pub fn motion_bounds(&self, aabb: AABB, time0: f32, time1: f32) -> AABB {
+        if !self.is_animated() || time0 == time1 {
+            return self.at(time0).aabb(aabb);
+        }
+        let mut times: Vec<f32> = (0..=BOUNDING_STEPS)
+            .map(|i| time0 + (time1 - time0) * i as f32 / BOUNDING_STEPS as f32)
+            .collect();
+        times.extend(
+            self.keyframes
+                .iter()
+                .map(|k| k.time)
+                .filter(|t| *t > time0 && *t < time1),
+        );
+        times.sort_by(|a, b| a.total_cmp(b));
+
+        let mut output_box = self.at(times[0]).aabb(aabb);
+        let mut max_step_angle: f32 = 0.0;
+        let mut max_scale: f32 = 0.0;
+        for pair in times.windows(2) {
+            let k0 = self.keyframe_at(pair[0]);
+            let k1 = self.keyframe_at(pair[1]);
+            output_box = output_box.surrounding_box(k1.transform().aabb(aabb));
+            let step = k0.rotation.normalize() * k1.rotation.normalize().conjugate();
+            max_step_angle = max_step_angle.max(step.angle());
+            for k in [k0, k1] {
+                max_scale =
+                    max_scale.max(k.scale.x.abs().max(k.scale.y.abs()).max(k.scale.z.abs()));
+            }
+        }
+
+        let farthest_corner = Vec3::new(
+            aabb.min.x.abs().max(aabb.max.x.abs()),
+            aabb.min.y.abs().max(aabb.max.y.abs()),
+            aabb.min.z.abs().max(aabb.max.z.abs()),
+        );
+        let radius = farthest_corner.length() * max_scale;
+        let pad = radius * (1.0 - (max_step_angle / 2.0).cos()) + 0.0001;
+        let pad = Vec3::new(pad, pad, pad);
+        AABB::new(output_box.min - pad, output_box.max + pad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spin() -> AnimatedTransform {
+        let axis = Vec3::new(0.0, 1.0, 0.0);
+        AnimatedTransform::new(vec![
+            Keyframe::new(
+                0.0,
+                Vec3::new(3.0, 0.0, 0.0),
+                Quaternion::identity(),
+                Vec3::new(1.0, 1.0, 1.0),
+            ),
+            Keyframe::new(
+                0.5,
+                Vec3::new(3.0, 1.0, 0.0),
+                Quaternion::from_axis_angle(axis, 90.0),
+                Vec3::new(2.0, 1.0, 1.0),
+            ),
+            Keyframe::new(
+                1.0,
+                Vec3::new(3.0, 2.0, 0.0),
+                Quaternion::from_axis_angle(axis, 180.0),
+                Vec3::new(1.0, 1.0, 1.0),
+            ),
+        ])
+    }
+
+    // Every corner of `aabb` at every sampled time in [time0, time1] must lie
+    // inside the motion bounds.
+    fn assert_covers(animation: &AnimatedTransform, aabb: AABB, time0: f32, time1: f32) {
+        let bounds = animation.motion_bounds(aabb, time0, time1);
+        for step in 0..=1000 {
+            let time = time0 + (time1 - time0) * step as f32 / 1000.0;
+            let transform = animation.at(time);
+            for x in [aabb.min.x, aabb.max.x] {
+                for y in [aabb.min.y, aabb.max.y] {
+                    for z in [aabb.min.z, aabb.max.z] {
+                        let corner = transform.point(Vec3::new(x, y, z));
+                        for c in 0..3 {
+                            assert!(corner[c] >= bounds.min[c] - 1e-4, "t = {}", time);
+                            assert!(corner[c] <= bounds.max[c] + 1e-4, "t = {}", time);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keyframes_are_reached_and_clamped() {
+        let animation = spin();
+        let p = Vec3::new(1.0, 0.0, 0.0);
+        assert!((animation.at(0.0).point(p) - Vec3::new(4.0, 0.0, 0.0)).length() < 1e-4);
+        assert!((animation.at(1.0).point(p) - Vec3::new(2.0, 2.0, 0.0)).length() < 1e-4);
+        assert!((animation.at(-1.0).point(p) - Vec3::new(4.0, 0.0, 0.0)).length() < 1e-4);
+        assert!((animation.at(2.0).point(p) - Vec3::new(2.0, 2.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn motion_bounds_cover_intermediate_times() {
+        let animation = spin();
+        let aabb = AABB::new(Vec3::new(-1.0, -0.5, -0.25), Vec3::new(1.0, 0.5, 0.25));
+        assert_covers(&animation, aabb, 0.0, 1.0);
+        assert_covers(&animation, aabb, 0.1, 0.35);
+    }
+
+    #[test]
+    fn motion_bounds_of_a_static_transform_are_tight() {
+        let animation = AnimatedTransform::new(vec![Keyframe::new(
+            0.0,
+            Vec3::new(0.0, 5.0, 0.0),
+            Quaternion::identity(),
+            Vec3::new(1.0, 1.0, 1.0),
+        )]);
+        let aabb = AABB::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        let bounds = animation.motion_bounds(aabb, 0.0, 1.0);
+        assert!((bounds.min - Vec3::new(-1.0, 4.0, -1.0)).length() < 1e-4);
+        assert!((bounds.max - Vec3::new(1.0, 6.0, 1.0)).length() < 1e-4);
+    }
+}
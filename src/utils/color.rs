@@ -0,0 +1,45 @@
+use crate::utils::vec3::Vec3;
+
+// CIE XYZ to linear sRGB, clamping colors outside of the gamut.
+pub fn xyz_to_rgb(xyz: Vec3) -> Vec3 {
+    Vec3::new(
+        (3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z).max(0.0),
+        (-0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z).max(0.0),
+        (0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z).max(0.0),
+    )
+}
+
+// Color of a black body at `kelvin`, with a luminance of one. The spectrum
+// is integrated against the CIE 1931 matching functions as fitted by
+// Wyman, Sloan and Shirley.
+pub fn blackbody(kelvin: f32) -> Vec3 {
+    let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+    let mut lambda = 380.0;
+    while lambda <= 780.0 {
+        xyz = xyz + planck(lambda, kelvin) * color_matching(lambda);
+        lambda += 5.0;
+    }
+    let rgb = xyz_to_rgb(xyz);
+    rgb / rgb.luminance()
+}
+
+// Spectral radiance of a black body up to a constant factor, the
+// wavelength in nanometers.
+fn planck(lambda: f32, kelvin: f32) -> f32 {
+    // Second radiation constant, in nm·K.
+    const C2: f32 = 1.4388e7;
+    let l = lambda / 1000.0;
+    1.0 / (l.powi(5) * ((C2 / (lambda * kelvin)).exp() - 1.0))
+}
+
+fn color_matching(lambda: f32) -> Vec3 {
+    let g = |mu: f32, sigma1: f32, sigma2: f32| {
+        let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
@@ -0,0 +1,102 @@
+// Piecewise constant 1D distribution over [0, 1), as in pbrt.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    pub function: Vec<f32>,
+    pub cdf: Vec<f32>,
+    pub integral: f32,
+}
+
+impl Distribution1D {
+    pub fn new(function: &[f32]) -> Distribution1D {
+        let n = function.len();
+        let function: Vec<f32> = function.iter().map(|f| f.abs()).collect();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + function[i] / n as f32;
+        }
+        let integral = cdf[n];
+        // An all zero function is sampled uniformly.
+        for (i, value) in cdf.iter_mut().enumerate().skip(1) {
+            *value = if integral == 0.0 {
+                i as f32 / n as f32
+            } else {
+                *value / integral
+            };
+        }
+        Distribution1D {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.function.len()
+    }
+
+    // Point in [0, 1), its density and the index of its segment.
+    pub fn sample_continuous(&self, u: f32) -> (f32, f32, usize) {
+        // Last entry of the cdf not above `u`.
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let pdf = if self.integral > 0.0 {
+            self.function[offset] / self.integral
+        } else {
+            1.0
+        };
+        let x = ((offset as f32 + du) / self.count() as f32).min(1.0 - f32::EPSILON);
+        (x, pdf, offset)
+    }
+
+    pub fn pdf(&self, x: f32) -> f32 {
+        let offset = ((x * self.count() as f32) as usize).min(self.count() - 1);
+        if self.integral > 0.0 {
+            self.function[offset] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+// Piecewise constant distribution over [0, 1)², stored as rows of `nu`
+// values: a marginal over the rows and a conditional for each row.
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    pub conditional: Vec<Distribution1D>,
+    pub marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(function: &[f32], nu: usize, nv: usize) -> Distribution2D {
+        let conditional: Vec<Distribution1D> = (0..nv)
+            .map(|v| Distribution1D::new(&function[v * nu..(v + 1) * nu]))
+            .collect();
+        let marginal_function: Vec<f32> = conditional.iter().map(|c| c.integral).collect();
+        Distribution2D {
+            conditional,
+            marginal: Distribution1D::new(&marginal_function),
+        }
+    }
+
+    // Point (u, v) and its density.
+    pub fn sample_continuous(&self, u: (f32, f32)) -> ((f32, f32), f32) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u.1);
+        let (u, pdf_u, _) = self.conditional[row].sample_continuous(u.0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, point: (f32, f32)) -> f32 {
+        let row =
+            ((point.1 * self.marginal.count() as f32) as usize).min(self.marginal.count() - 1);
+        self.marginal.pdf(point.1) * self.conditional[row].pdf(point.0)
+    }
+}
@@ -0,0 +1,160 @@
+use crate::utils::vec3::Vec3;
+use std::ops::Mul;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4::new(out)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, done in f64 so that
+    // chains of small rotations and large translations stay accurate.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = [[0.0f64; 8]; 4];
+        for (i, row) in a.iter_mut().enumerate() {
+            for (j, value) in self.m[i].iter().enumerate() {
+                row[j] = *value as f64;
+            }
+            row[i + 4] = 1.0;
+        }
+        for column in 0..4 {
+            let mut pivot = column;
+            for row in column + 1..4 {
+                if a[row][column].abs() > a[pivot][column].abs() {
+                    pivot = row;
+                }
+            }
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            let inv_pivot = 1.0 / a[column][column];
+            for value in a[column].iter_mut() {
+                *value *= inv_pivot;
+            }
+            let pivot_row = a[column];
+            for (i, row) in a.iter_mut().enumerate() {
+                let factor = row[column];
+                if i != column && factor != 0.0 {
+                    for (value, pivot_value) in row.iter_mut().zip(pivot_row.iter()) {
+                        *value -= factor * pivot_value;
+                    }
+                }
+            }
+        }
+        let mut out = [[0.0; 4]; 4];
+        for (row, inverse_row) in out.iter_mut().zip(a.iter()) {
+            for (value, inverse_value) in row.iter_mut().zip(inverse_row[4..].iter()) {
+                *value = *inverse_value as f32;
+            }
+        }
+        Some(Mat4::new(out))
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x, y, z) / w
+        }
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4::new(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(m: Mat4) {
+        for (i, row) in m.m.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!(
+                    (value - expected).abs() < 1e-4,
+                    "m[{}][{}] = {}",
+                    i,
+                    j,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let m = Mat4::new([
+            [0.0, -2.0, 0.0, 5.0],
+            [3.0, 0.0, 0.5, -1.0],
+            [0.0, 0.25, 4.0, 100.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inverse = m.inverse().unwrap();
+        assert_identity(m * inverse);
+        assert_identity(inverse * m);
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        let m = Mat4::new([
+            [1.0, 2.0, 3.0, 0.0],
+            [2.0, 4.0, 6.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert!(m.inverse().is_none());
+    }
+
+    #[test]
+    fn vectors_ignore_translation() {
+        let m = Mat4::new([
+            [1.0, 0.0, 0.0, 7.0],
+            [0.0, 1.0, 0.0, 8.0],
+            [0.0, 0.0, 1.0, 9.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert!((m.transform_vector(v) - v).length() < 1e-6);
+        assert!((m.transform_point(v) - Vec3::new(8.0, 10.0, 12.0)).length() < 1e-6);
+    }
+}
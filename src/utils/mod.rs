@@ -1,4 +1,12 @@
+pub mod animated_transform;
+pub mod color;
+pub mod distribution;
+pub mod mat4;
+pub mod onb;
 pub mod perlin;
+pub mod polynomial;
+pub mod quaternion;
+pub mod transform;
 pub mod vec3;
 
 pub fn random_double() -> f32 {
@@ -17,3 +25,12 @@ pub fn random_double_range(min: f32, max: f32) -> f32 {
     let x: f32 = rng.gen_range(min..max);
     x
 }
+
+// Veach's power heuristic with an exponent of 2, one sample per strategy.
+pub fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
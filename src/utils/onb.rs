@@ -0,0 +1,33 @@
+use crate::utils::vec3::Vec3;
+
+// Orthonormal basis with `w` as the given axis.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Copy, Clone)]
+pub struct ONB {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl ONB {
+    // Duff et al., "Building an Orthonormal Basis, Revisited".
+    pub fn build_from_w(w: Vec3) -> ONB {
+        let w = w.unit_vector();
+        let sign = 1.0f32.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        let u = Vec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+        let v = Vec3::new(b, sign + w.y * w.y * a, -w.y);
+        ONB { u, v, w }
+    }
+
+    // Local coordinates to world space.
+    pub fn local_to_world(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+
+    // World space to local coordinates.
+    pub fn world_to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
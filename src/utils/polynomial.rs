@@ -0,0 +1,179 @@
+// Real roots of low degree polynomials, in f64 since the torus quartic loses
+// most of its precision in f32. Roots are returned in no particular order.
+
+const EPSILON: f64 = 1e-12;
+
+// a*x^2 + b*x + c = 0
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < EPSILON {
+        if b.abs() < EPSILON {
+            return vec![];
+        }
+        return vec![-c / b];
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    // Avoids the cancellation of the textbook formula.
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return vec![0.0];
+    }
+    vec![q / a, c / q]
+}
+
+// x^3 + a*x^2 + b*x + c = 0
+pub fn solve_normalized_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let a_over_3 = a / 3.0;
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    let q3 = q * q * q;
+    if r * r < q3 {
+        let theta = (r / q3.sqrt()).clamp(-1.0, 1.0).acos();
+        let m = -2.0 * q.sqrt();
+        vec![
+            m * (theta / 3.0).cos() - a_over_3,
+            m * ((theta + 2.0 * std::f64::consts::PI) / 3.0).cos() - a_over_3,
+            m * ((theta - 2.0 * std::f64::consts::PI) / 3.0).cos() - a_over_3,
+        ]
+    } else {
+        let s = -r.signum() * (r.abs() + (r * r - q3).sqrt()).cbrt();
+        let t = if s == 0.0 { 0.0 } else { q / s };
+        // The other two roots are complex, their imaginary part being
+        // proportional to s - t, unless they meet as a double root.
+        if (s - t).abs() <= 1e-6 * s.abs().max(1.0) {
+            return vec![s + t - a_over_3, -0.5 * (s + t) - a_over_3];
+        }
+        vec![s + t - a_over_3]
+    }
+}
+
+// x^4 + a*x^3 + b*x^2 + c*x + d = 0, by Ferrari's method followed by a few
+// Newton iterations on the original polynomial to polish each root.
+pub fn solve_normalized_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // Depressed quartic y^4 + p*y^2 + q*y + r with x = y - a/4.
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut roots = Vec::new();
+    if q.abs() < EPSILON {
+        // Biquadratic.
+        for z in solve_quadratic(1.0, p, r) {
+            if z >= 0.0 {
+                let y = z.sqrt();
+                roots.push(y);
+                roots.push(-y);
+            }
+        }
+    } else {
+        // Largest real root of the resolvent cubic gives a safe factorization.
+        let m = solve_normalized_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            return vec![];
+        }
+        let sqrt_2m = (2.0 * m).sqrt();
+        let s = q / (2.0 * sqrt_2m);
+        roots.extend(solve_quadratic(1.0, sqrt_2m, p / 2.0 + m - s));
+        roots.extend(solve_quadratic(1.0, -sqrt_2m, p / 2.0 + m + s));
+    }
+
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y - a / 4.0;
+            for _ in 0..2 {
+                let f = (((x + a) * x + b) * x + c) * x + d;
+                let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+                if df.abs() > EPSILON {
+                    x -= f / df;
+                }
+            }
+            x
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(mut roots: Vec<f64>, expected: &[f64], tolerance: f64) {
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        roots.dedup_by(|a, b| (*a - *b).abs() <= tolerance);
+        assert_eq!(roots.len(), expected.len(), "roots {roots:?}");
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() <= tolerance, "roots {roots:?}");
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(solve_quadratic(1.0, -4.0, 3.0), &[1.0, 3.0], 1e-12);
+        assert_roots(solve_quadratic(1.0, -4.0, 4.0), &[2.0], 1e-12);
+        assert_roots(solve_quadratic(0.0, 2.0, -1.0), &[0.5], 1e-12);
+        assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[], 1e-12);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(
+            solve_normalized_cubic(-6.0, 11.0, -6.0),
+            &[1.0, 2.0, 3.0],
+            1e-9,
+        );
+        // (x - 1)^2 (x + 2)
+        assert_roots(solve_normalized_cubic(0.0, -3.0, 2.0), &[-2.0, 1.0], 1e-6);
+        // (x - 2)^3
+        assert_roots(solve_normalized_cubic(-6.0, 12.0, -8.0), &[2.0], 1e-6);
+        // (x - 1)(x^2 + 1)
+        assert_roots(solve_normalized_cubic(-1.0, 1.0, -1.0), &[1.0], 1e-9);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            solve_normalized_quartic(-10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+            1e-9,
+        );
+        // (x - 2)^2 (x + 2)^2
+        assert_roots(
+            solve_normalized_quartic(0.0, -8.0, 0.0, 16.0),
+            &[-2.0, 2.0],
+            1e-6,
+        );
+        // (x - 1)^4
+        assert_roots(solve_normalized_quartic(-4.0, 6.0, -4.0, 1.0), &[1.0], 1e-6);
+        // (x - 1)^2 (x - 2)(x + 3)
+        assert_roots(
+            solve_normalized_quartic(-1.0, -7.0, 13.0, -6.0),
+            &[-3.0, 1.0, 2.0],
+            1e-6,
+        );
+        // (x^2 + 1)(x^2 + 4)
+        assert_roots(solve_normalized_quartic(0.0, 5.0, 0.0, 4.0), &[], 1e-9);
+    }
+
+    #[test]
+    fn quartic_near_double_root() {
+        // (x - 1)(x - 1.001)(x + 2)(x - 5), roots close enough to lose
+        // precision in the resolvent cubic.
+        let (r0, r1, r2, r3) = (1.0, 1.001, -2.0, 5.0);
+        let a = -(r0 + r1 + r2 + r3);
+        let b = r0 * r1 + r0 * r2 + r0 * r3 + r1 * r2 + r1 * r3 + r2 * r3;
+        let c = -(r0 * r1 * r2 + r0 * r1 * r3 + r0 * r2 * r3 + r1 * r2 * r3);
+        let d = r0 * r1 * r2 * r3;
+        assert_roots(
+            solve_normalized_quartic(a, b, c, d),
+            &[-2.0, 1.0, 1.001, 5.0],
+            1e-7,
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::utils::mat4::Mat4;
+use crate::utils::vec3::Vec3;
+use std::ops::Mul;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // Rotation of `angle` degrees around `axis`.
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Quaternion {
+        let axis = axis.unit_vector();
+        let (sin_half, cos_half) = (angle.to_radians() / 2.0).sin_cos();
+        Quaternion::new(
+            cos_half,
+            axis.x * sin_half,
+            axis.y * sin_half,
+            axis.z * sin_half,
+        )
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn dot(&self, other: Quaternion) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        let length = self.length();
+        Quaternion::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    // Angle in radians of the rotation represented by this (unit) quaternion.
+    pub fn angle(&self) -> f32 {
+        2.0 * self.w.abs().min(1.0).acos()
+    }
+
+    pub fn slerp(&self, other: Quaternion, t: f32) -> Quaternion {
+        let mut other = other;
+        let mut cos_theta = self.dot(other);
+        // q and -q are the same rotation, take the shortest arc.
+        if cos_theta < 0.0 {
+            other = Quaternion::new(-other.w, -other.x, -other.y, -other.z);
+            cos_theta = -cos_theta;
+        }
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+        Quaternion::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .normalize()
+    }
+
+    pub fn rotation_matrix(&self) -> Mat4 {
+        let Quaternion { w, x, y, z } = self.normalize();
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // q and -q are the same rotation, so compare up to sign.
+    fn same_rotation(a: Quaternion, b: Quaternion) -> bool {
+        a.dot(b).abs() > 1.0 - 1e-5
+    }
+
+    #[test]
+    fn slerp_hits_endpoints() {
+        let a = Quaternion::from_axis_angle(Vec3::new(1.0, 2.0, 0.5), 20.0);
+        let b = Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, -1.0), 110.0);
+        assert!(same_rotation(a.slerp(b, 0.0), a));
+        assert!(same_rotation(a.slerp(b, 1.0), b));
+    }
+
+    #[test]
+    fn slerp_midpoint_halves_the_angle() {
+        let axis = Vec3::new(0.0, 1.0, 0.0);
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(axis, 90.0);
+        let middle = a.slerp(b, 0.5);
+        assert!(same_rotation(
+            middle,
+            Quaternion::from_axis_angle(axis, 45.0)
+        ));
+        assert!((middle.length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slerp_takes_the_shortest_arc() {
+        let axis = Vec3::new(0.0, 0.0, 1.0);
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(axis, 60.0);
+        let negated = Quaternion::new(-b.w, -b.x, -b.y, -b.z);
+        let middle = a.slerp(negated, 0.5);
+        assert!((middle.angle() - 30f32.to_radians()).abs() < 1e-4);
+        assert!(same_rotation(
+            middle,
+            Quaternion::from_axis_angle(axis, 30.0)
+        ));
+    }
+
+    #[test]
+    fn rotation_matrix_turns_x_into_y() {
+        let q = Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let rotated = q
+            .rotation_matrix()
+            .transform_vector(Vec3::new(1.0, 0.0, 0.0));
+        assert!((rotated - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-5);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::image_utils::aabb::AABB;
+use crate::image_utils::ray::Ray;
+use crate::utils::mat4::Mat4;
+use crate::utils::vec3::Vec3;
+use std::ops::Mul;
+
+// An affine transform together with its inverse, so that rays can be moved
+// into object space and hit records back into world space without inverting
+// a matrix per ray.
+#[derive(Debug, Copy, Clone)]
+pub struct Transform {
+    pub matrix: Mat4,
+    pub inverse: Mat4,
+}
+
+impl Transform {
+    pub fn new(matrix: Mat4, inverse: Mat4) -> Transform {
+        Transform { matrix, inverse }
+    }
+
+    pub fn from_matrix(matrix: Mat4) -> Transform {
+        let inverse = matrix
+            .inverse()
+            .expect("transform matrix is not invertible");
+        Transform { matrix, inverse }
+    }
+
+    pub fn translate(offset: Vec3) -> Transform {
+        let matrix = Mat4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inverse = Mat4::new([
+            [1.0, 0.0, 0.0, -offset.x],
+            [0.0, 1.0, 0.0, -offset.y],
+            [0.0, 0.0, 1.0, -offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform::new(matrix, inverse)
+    }
+
+    pub fn scale(factors: Vec3) -> Transform {
+        let matrix = Mat4::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inverse = Mat4::new([
+            [1.0 / factors.x, 0.0, 0.0, 0.0],
+            [0.0, 1.0 / factors.y, 0.0, 0.0],
+            [0.0, 0.0, 1.0 / factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform::new(matrix, inverse)
+    }
+
+    pub fn uniform_scale(factor: f32) -> Transform {
+        Transform::scale(Vec3::new(factor, factor, factor))
+    }
+
+    pub fn rotate_x(angle: f32) -> Transform {
+        let (sin_theta, cos_theta) = angle.to_radians().sin_cos();
+        let matrix = Mat4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos_theta, -sin_theta, 0.0],
+            [0.0, sin_theta, cos_theta, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform::new(matrix, matrix.transpose())
+    }
+
+    pub fn rotate_y(angle: f32) -> Transform {
+        let (sin_theta, cos_theta) = angle.to_radians().sin_cos();
+        let matrix = Mat4::new([
+            [cos_theta, 0.0, sin_theta, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin_theta, 0.0, cos_theta, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform::new(matrix, matrix.transpose())
+    }
+
+    pub fn rotate_z(angle: f32) -> Transform {
+        let (sin_theta, cos_theta) = angle.to_radians().sin_cos();
+        let matrix = Mat4::new([
+            [cos_theta, -sin_theta, 0.0, 0.0],
+            [sin_theta, cos_theta, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform::new(matrix, matrix.transpose())
+    }
+
+    // Rotation of `angle` degrees around an arbitrary axis (Rodrigues' formula).
+    pub fn rotate(axis: Vec3, angle: f32) -> Transform {
+        let a = axis.unit_vector();
+        let (sin_theta, cos_theta) = angle.to_radians().sin_cos();
+        let t = 1.0 - cos_theta;
+        let matrix = Mat4::new([
+            [
+                a.x * a.x * t + cos_theta,
+                a.x * a.y * t - a.z * sin_theta,
+                a.x * a.z * t + a.y * sin_theta,
+                0.0,
+            ],
+            [
+                a.x * a.y * t + a.z * sin_theta,
+                a.y * a.y * t + cos_theta,
+                a.y * a.z * t - a.x * sin_theta,
+                0.0,
+            ],
+            [
+                a.x * a.z * t - a.y * sin_theta,
+                a.y * a.z * t + a.x * sin_theta,
+                a.z * a.z * t + cos_theta,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Transform::new(matrix, matrix.transpose())
+    }
+
+    // Applies `self` first, then `next`.
+    pub fn then(&self, next: Transform) -> Transform {
+        next * *self
+    }
+
+    pub fn inverted(&self) -> Transform {
+        Transform::new(self.inverse, self.matrix)
+    }
+
+    pub fn normal_matrix(&self) -> Mat4 {
+        self.inverse.transpose()
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    // Normals transform with the inverse transpose; the result is not
+    // normalized since scaling changes its length.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.normal_matrix().transform_vector(n)
+    }
+
+    // The direction is left unnormalized so that `t` values are identical in
+    // both spaces.
+    pub fn ray(&self, ray: &Ray) -> Ray {
+        Ray::new(self.point(ray.origin), self.vector(ray.direction), ray.time)
+    }
+
+    pub fn aabb(&self, aabb: AABB) -> AABB {
+        let mut min = Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let corner = Vec3::new(
+                        if i == 0 { aabb.min.x } else { aabb.max.x },
+                        if j == 0 { aabb.min.y } else { aabb.max.y },
+                        if k == 0 { aabb.min.z } else { aabb.max.z },
+                    );
+                    let transformed = self.point(corner);
+                    for c in 0..3 {
+                        min[c] = min[c].min(transformed[c]);
+                        max[c] = max[c].max(transformed[c]);
+                    }
+                }
+            }
+        }
+        AABB::new(min, max)
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform::new(self.matrix * rhs.matrix, rhs.inverse * self.inverse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squash() -> Transform {
+        Transform::scale(Vec3::new(1.0, 3.0, 0.5))
+            .then(Transform::rotate(Vec3::new(1.0, 1.0, 0.0), 35.0))
+            .then(Transform::translate(Vec3::new(2.0, -1.0, 4.0)))
+    }
+
+    #[test]
+    fn inverse_round_trips_points() {
+        let transform = squash();
+        let p = Vec3::new(0.3, -1.7, 2.5);
+        let back = transform.inverted().point(transform.point(p));
+        assert!((back - p).length() < 1e-4);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let transform = squash();
+        // On a unit sphere the normal at `p` is `p`, and `p × axis` is tangent.
+        for p in [
+            Vec3::new(1.0, 1.0, 1.0).unit_vector(),
+            Vec3::new(0.2, -0.9, 0.4).unit_vector(),
+            Vec3::new(-0.6, 0.1, 0.8).unit_vector(),
+        ] {
+            let normal = transform.normal(p).unit_vector();
+            for axis in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+                let tangent = transform.vector(p.cross(axis)).unit_vector();
+                assert!(normal.dot(tangent).abs() < 1e-4);
+            }
+            // A plain linear map would tilt the normal off perpendicular.
+            let naive = transform.vector(p).unit_vector();
+            assert!(
+                naive
+                    .dot(transform.vector(p.cross(Vec3::new(1.0, 0.0, 0.0))))
+                    .abs()
+                    > 1e-2
+            );
+        }
+    }
+
+    #[test]
+    fn aabb_contains_and_touches_transformed_corners() {
+        let transform = squash();
+        let aabb = AABB::new(Vec3::new(-1.0, -0.5, 0.0), Vec3::new(2.0, 1.0, 3.0));
+        let bounds = transform.aabb(aabb);
+        let mut touches_min = [false; 3];
+        let mut touches_max = [false; 3];
+        for x in [aabb.min.x, aabb.max.x] {
+            for y in [aabb.min.y, aabb.max.y] {
+                for z in [aabb.min.z, aabb.max.z] {
+                    let corner = transform.point(Vec3::new(x, y, z));
+                    for c in 0..3 {
+                        assert!(corner[c] >= bounds.min[c] - 1e-4);
+                        assert!(corner[c] <= bounds.max[c] + 1e-4);
+                        touches_min[c] |= (corner[c] - bounds.min[c]).abs() < 1e-4;
+                        touches_max[c] |= (corner[c] - bounds.max[c]).abs() < 1e-4;
+                    }
+                }
+            }
+        }
+        assert_eq!(touches_min, [true; 3]);
+        assert_eq!(touches_max, [true; 3]);
+    }
+}
@@ -69,6 +69,11 @@ impl Vec3 {
         self.x.abs() < S && self.y.abs() < S && self.z.abs() < S
     }
 
+    // Rec. 709 luminance of a linear RGB color.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+
     pub fn reflect(&self, normal: Vec3) -> Vec3 {
         *self - 2.0 * self.dot(normal) * normal
     }